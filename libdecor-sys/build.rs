@@ -1,10 +1,121 @@
+use std::env;
+#[cfg(feature = "bindgen")]
+use std::path::PathBuf;
+
 use pkg_config::Config;
 
+/// Environment variable overriding the location of the libdecor sources
+#[cfg(feature = "bindgen")]
+const SOURCE_DIR_VAR: &str = "LIBDECOR_SOURCE_DIR";
+
 fn main() {
-    if std::env::var_os("CARGO_FEATURE_DLOPEN").is_some() {
+    #[cfg(feature = "bindgen")]
+    generate_bindings();
+
+    if env::var_os("CARGO_FEATURE_DLOPEN").is_some() {
         // Do not link to anything
         return;
     }
 
-    Config::new().probe("libdecor-0").unwrap();
+    if env::var_os("CARGO_FEATURE_MOCK").is_some() {
+        // The mock implementation replaces the library
+        return;
+    }
+
+    let mut config = Config::new();
+    if env::var_os("CARGO_FEATURE_LIBDECOR_0_2").is_some() {
+        config.atleast_version("0.2.0");
+    }
+    if let Err(err) = config.probe("libdecor-0") {
+        fail(&format!("failed to find libdecor with pkg-config: {}", err));
+    }
+}
+
+/// Abort the build explaining the ways to provide libdecor.
+fn fail(reason: &str) -> ! {
+    panic!(
+        "\n{}\n\n\
+         libdecor-sys needs libdecor, either:\n\
+         - install the development files of libdecor, e.g. `libdecor-0-dev`, or \
+         point PKG_CONFIG_PATH to `libdecor-0.pc`,\n\
+         - or enable the `dlopen` feature to load libdecor at runtime.\n",
+        reason
+    );
+}
+
+/// Directory of the libdecor sources providing `libdecor-plugin.h`
+#[cfg(feature = "bindgen")]
+fn source_dir() -> PathBuf {
+    println!("cargo:rerun-if-env-changed={}", SOURCE_DIR_VAR);
+    let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    env::var_os(SOURCE_DIR_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| manifest_dir.join("vendor").join("libdecor"))
+}
+
+/// Generate bindings from `libdecor.h` to verify the handwritten ones.
+///
+/// The header of the installed libdecor is used. With the `plugin` feature
+/// `libdecor-plugin.h` is verified as well, which libdecor does not install,
+/// so it is taken from the libdecor sources if they are available.
+#[cfg(feature = "bindgen")]
+fn generate_bindings() {
+    let mut include_paths = match Config::new()
+        .cargo_metadata(false)
+        .env_metadata(false)
+        .probe("libdecor-0")
+    {
+        Ok(library) => library.include_paths,
+        Err(err) => fail(&format!(
+            "failed to find the libdecor header with pkg-config: {}",
+            err
+        )),
+    };
+
+    let mut header = String::from("#include <libdecor.h>\n");
+    println!("cargo:rustc-check-cfg=cfg(libdecor_plugin_header)");
+    if env::var_os("CARGO_FEATURE_PLUGIN").is_some() {
+        let source_dir = source_dir().join("src");
+        if source_dir.join("libdecor-plugin.h").is_file() {
+            // The installed header is still preferred for libdecor.h
+            include_paths.push(source_dir);
+            header.push_str("#include <libdecor-plugin.h>\n");
+            println!("cargo:rustc-cfg=libdecor_plugin_header");
+        } else {
+            println!(
+                "cargo:warning=libdecor-plugin.h is not installed by libdecor and missing in {}, \
+                 skipping the verification of the plugin declarations, check out the libdecor \
+                 sources there or set ${} to verify them",
+                source_dir.display(),
+                SOURCE_DIR_VAR
+            );
+        }
+    }
+    if let Ok(wayland) = Config::new()
+        .cargo_metadata(false)
+        .env_metadata(false)
+        .probe("wayland-client")
+    {
+        include_paths.extend(wayland.include_paths);
+    }
+
+    let bindings = bindgen::Builder::default()
+        .header_contents("wrapper.h", &header)
+        .clang_args(
+            include_paths
+                .iter()
+                .map(|path| format!("-I{}", path.display())),
+        )
+        .allowlist_type("libdecor_.*")
+        .allowlist_var("LIBDECOR_.*")
+        .prepend_enum_name(false)
+        .layout_tests(false)
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
+        .generate()
+        .unwrap_or_else(|err| fail(&format!("failed to generate the bindings: {}", err)));
+
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    bindings
+        .write_to_file(out_dir.join("bindings.rs"))
+        .expect("failed to write the bindings");
 }
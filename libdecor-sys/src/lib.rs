@@ -5,7 +5,21 @@ use std::{
     os::raw::{c_char, c_int, c_uint},
 };
 
+#[cfg(not(any(feature = "dlopen", feature = "mock")))]
 use dlib::external_library;
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
+use std::ffi::OsStr;
+
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
+mod load;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod plugin;
+#[cfg(feature = "bindgen")]
+mod verify;
+
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
+pub use load::{load, load_error, load_from_candidates, set_library, LoadError};
 
 pub enum libdecor {}
 pub enum libdecor_frame {}
@@ -15,6 +29,8 @@ pub type libdecor_error = c_uint;
 pub type libdecor_window_state = c_uint;
 pub type libdecor_resize_edge = c_uint;
 pub type libdecor_capabilities = c_uint;
+#[cfg(feature = "libdecor_0_2")]
+pub type libdecor_wm_capabilities = c_uint;
 pub enum wl_seat {}
 pub enum wl_display {}
 pub enum wl_surface {}
@@ -32,6 +48,8 @@ pub const LIBDECOR_WINDOW_STATE_TILED_LEFT: libdecor_window_state = 8;
 pub const LIBDECOR_WINDOW_STATE_TILED_RIGHT: libdecor_window_state = 16;
 pub const LIBDECOR_WINDOW_STATE_TILED_TOP: libdecor_window_state = 32;
 pub const LIBDECOR_WINDOW_STATE_TILED_BOTTOM: libdecor_window_state = 64;
+#[cfg(feature = "libdecor_0_2")]
+pub const LIBDECOR_WINDOW_STATE_SUSPENDED: libdecor_window_state = 128;
 pub const LIBDECOR_RESIZE_EDGE_NONE: libdecor_resize_edge = 0;
 pub const LIBDECOR_RESIZE_EDGE_TOP: libdecor_resize_edge = 1;
 pub const LIBDECOR_RESIZE_EDGE_BOTTOM: libdecor_resize_edge = 2;
@@ -46,6 +64,14 @@ pub const LIBDECOR_ACTION_RESIZE: libdecor_capabilities = 2;
 pub const LIBDECOR_ACTION_MINIMIZE: libdecor_capabilities = 4;
 pub const LIBDECOR_ACTION_FULLSCREEN: libdecor_capabilities = 8;
 pub const LIBDECOR_ACTION_CLOSE: libdecor_capabilities = 16;
+#[cfg(feature = "libdecor_0_2")]
+pub const LIBDECOR_WM_CAPABILITIES_WINDOW_MENU: libdecor_wm_capabilities = 1;
+#[cfg(feature = "libdecor_0_2")]
+pub const LIBDECOR_WM_CAPABILITIES_MAXIMIZE: libdecor_wm_capabilities = 2;
+#[cfg(feature = "libdecor_0_2")]
+pub const LIBDECOR_WM_CAPABILITIES_FULLSCREEN: libdecor_wm_capabilities = 4;
+#[cfg(feature = "libdecor_0_2")]
+pub const LIBDECOR_WM_CAPABILITIES_MINIMIZE: libdecor_wm_capabilities = 8;
 
 pub type libdecor_error_callback =
     unsafe extern "C" fn(context: *mut libdecor, error: libdecor_error, message: *const c_char);
@@ -116,7 +142,153 @@ pub struct libdecor_frame_interface {
     pub reserved9: libdecor_reserver_callback,
 }
 
-external_library!(Libdecor, "decor-0",
+/// Declares the functions of libdecor
+///
+/// Expands to the [`external_library!`] bindings, or to a function table
+/// backed by the [`mock`] implementation if the `mock` feature is enabled.
+/// With the `dlopen` feature the functions are resolved at runtime and a
+/// plain [`LibdecorFunctions`] table is generated in addition.
+///
+/// The functions added in libdecor 0.2 are only included with the
+/// `libdecor_0_2` feature. With `dlopen` they are optional, a library
+/// missing them can still be loaded, see [`ffi_dispatch_optional!`].
+macro_rules! libdecor_library {
+    (
+        functions: $(fn $fname: ident($($farg: ty),*) -> $fret: ty),+,
+        functions_0_2: $(fn $fname_0_2: ident($($farg_0_2: ty),*) -> $fret_0_2: ty),+,
+    ) => {
+        #[cfg(not(feature = "libdecor_0_2"))]
+        libdecor_library!(@expand
+            functions: $(fn $fname($($farg),*) -> $fret,)+
+            optional:
+        );
+        #[cfg(feature = "libdecor_0_2")]
+        libdecor_library!(@expand
+            functions: $(fn $fname($($farg),*) -> $fret,)+
+            optional: $(fn $fname_0_2($($farg_0_2),*) -> $fret_0_2,)+
+        );
+    };
+    (@expand
+        functions: $(fn $fname: ident($($farg: ty),*) -> $fret: ty,)+
+        optional: $(fn $oname: ident($($oarg: ty),*) -> $oret: ty,)*
+    ) => {
+        #[cfg(not(any(feature = "dlopen", feature = "mock")))]
+        external_library!(Libdecor, "decor-0",
+            functions:
+                $(fn $fname($($farg),*) -> $fret,)+
+                $(fn $oname($($oarg),*) -> $oret,)*
+        );
+
+        /// Functions of the loaded libdecor
+        ///
+        /// Optional functions missing in the library are [`None`].
+        #[cfg(all(feature = "dlopen", not(feature = "mock")))]
+        pub struct Libdecor {
+            _lib: libloading::Library,
+            path: std::path::PathBuf,
+            version: LibdecorVersion,
+            $(pub $fname: unsafe extern "C" fn($($farg),*) -> $fret,)+
+            $(pub $oname: Option<unsafe extern "C" fn($($oarg),*) -> $oret>,)*
+        }
+
+        #[cfg(all(feature = "dlopen", not(feature = "mock")))]
+        impl Libdecor {
+            /// Open the library and resolve its functions.
+            ///
+            /// # Safety
+            ///
+            /// Loading a library runs its initialization routines, the
+            /// library has to export the functions with the declared
+            /// signatures.
+            pub unsafe fn open<P: AsRef<OsStr>>(path: P) -> Result<Libdecor, LoadError> {
+                let path = path.as_ref();
+                let lib = libloading::Library::new(path).map_err(|source| LoadError::Open {
+                    path: path.into(),
+                    source,
+                })?;
+                Ok(Libdecor {
+                    $($fname: {
+                        let name = concat!(stringify!($fname), "\0");
+                        *lib.get::<unsafe extern "C" fn($($farg),*) -> $fret>(name.as_bytes())
+                            .map_err(|_| LoadError::MissingSymbol {
+                                path: path.into(),
+                                symbol: stringify!($fname),
+                            })?
+                    },)+
+                    $($oname: lib
+                        .get::<unsafe extern "C" fn($($oarg),*) -> $oret>(
+                            concat!(stringify!($oname), "\0").as_bytes(),
+                        )
+                        .ok()
+                        .map(|symbol| *symbol),)*
+                    version: if lib.get::<*const c_void>(VERSION_0_2_SYMBOL).is_ok() {
+                        LibdecorVersion::V0_2
+                    } else {
+                        LibdecorVersion::V0_1
+                    },
+                    _lib: lib,
+                    path: path.into(),
+                })
+            }
+
+            /// Returns the path the library was opened from.
+            pub fn path(&self) -> &std::path::Path {
+                &self.path
+            }
+
+            /// Returns the version of the libdecor API provided by the library.
+            pub fn version(&self) -> LibdecorVersion {
+                self.version
+            }
+        }
+
+        #[cfg(all(feature = "dlopen", not(feature = "mock")))]
+        impl std::fmt::Debug for Libdecor {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("Libdecor")
+                    .field("path", &self.path)
+                    .field("version", &self.version)
+                    .finish_non_exhaustive()
+            }
+        }
+
+        /// Function table of the mock implementation
+        #[cfg(feature = "mock")]
+        pub struct Libdecor {
+            $(pub $fname: unsafe extern "C" fn($($farg),*) -> $fret,)+
+            $(pub $oname: unsafe extern "C" fn($($oarg),*) -> $oret,)*
+        }
+
+        #[cfg(feature = "mock")]
+        pub static LIBDECOR_HANDLE: &Libdecor = &Libdecor {
+            $($fname: mock::$fname,)+
+            $($oname: mock::$oname,)*
+        };
+
+        /// Function table independent of where the functions come from
+        ///
+        /// Allows to substitute the functions of a library that could not
+        /// be loaded by an alternative implementation.
+        #[cfg(all(feature = "dlopen", not(feature = "mock")))]
+        #[derive(Debug, Clone, Copy)]
+        pub struct LibdecorFunctions {
+            $(pub $fname: unsafe extern "C" fn($($farg),*) -> $fret,)+
+            $(pub $oname: Option<unsafe extern "C" fn($($oarg),*) -> $oret>,)*
+        }
+
+        #[cfg(all(feature = "dlopen", not(feature = "mock")))]
+        impl From<&Libdecor> for LibdecorFunctions {
+            fn from(lib: &Libdecor) -> Self {
+                LibdecorFunctions {
+                    $($fname: lib.$fname,)+
+                    $($oname: lib.$oname,)*
+                }
+            }
+        }
+    };
+}
+
+libdecor_library!(
     functions:
         fn libdecor_new(*mut wl_display, *mut libdecor_interface) -> *mut libdecor,
         fn libdecor_unref(*mut libdecor) -> (),
@@ -226,35 +398,73 @@ external_library!(Libdecor, "decor-0",
             *mut libdecor_configuration,
             *mut libdecor_window_state
         ) -> bool,
+    functions_0_2:
+        fn libdecor_frame_get_min_content_size(
+            *const libdecor_frame,
+            *mut c_int,
+            *mut c_int
+        ) -> (),
+        fn libdecor_frame_get_max_content_size(
+            *const libdecor_frame,
+            *mut c_int,
+            *mut c_int
+        ) -> (),
+        fn libdecor_frame_get_wm_capabilities(*mut libdecor_frame) -> libdecor_wm_capabilities,
 );
 
-#[cfg(feature = "dlopen")]
+/// A function only available since libdecor 0.2
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
+const VERSION_0_2_SYMBOL: &[u8] = b"libdecor_frame_get_wm_capabilities\0";
+
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
 lazy_static::lazy_static!(
-    pub static ref LIBDECOR_OPTION: Option<Libdecor> = {
-        let library_filename = libloading::library_filename("decor-0");
-        match unsafe { Libdecor::open(library_filename.to_str().unwrap()) } {
-            Ok(lib) => Some(lib),
-            Err(err) => {
-                eprintln!("Failed to load {}: {}", library_filename.to_str().unwrap(), err);
-                None
-            },
-        }
-    };
+    /// The library selected with [`set_library`], or `libdecor-0.so`
+    ///
+    /// [`None`] if the library could not be loaded, see [`load_error`].
+    pub static ref LIBDECOR_OPTION: Option<&'static Libdecor> = load::library().ok();
     pub static ref LIBDECOR_HANDLE: &'static Libdecor = {
-        LIBDECOR_OPTION.as_ref().expect("Library decor-0 could not be loaded.")
+        LIBDECOR_OPTION.expect("Library decor-0 could not be loaded.")
     };
 );
 
-#[cfg(not(feature = "dlopen"))]
+/// Version of the libdecor API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LibdecorVersion {
+    /// libdecor 0.1
+    V0_1,
+    /// libdecor 0.2, adds getters for the content size limits and the
+    /// window management capabilities of the compositor
+    V0_2,
+}
+
+/// Returns the version of the libdecor API provided by the library.
+///
+/// With the `dlopen` feature the version is probed at runtime, [`None`]
+/// is returned if the library could not be loaded. Otherwise the version
+/// the crate has been built for is returned.
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
+pub fn libdecor_version() -> Option<LibdecorVersion> {
+    LIBDECOR_OPTION.map(Libdecor::version)
+}
+#[cfg(any(not(feature = "dlopen"), feature = "mock"))]
+pub fn libdecor_version() -> Option<LibdecorVersion> {
+    if cfg!(feature = "libdecor_0_2") {
+        Some(LibdecorVersion::V0_2)
+    } else {
+        Some(LibdecorVersion::V0_1)
+    }
+}
+
+#[cfg(any(not(feature = "dlopen"), feature = "mock"))]
 pub fn is_lib_available() -> bool {
     true
 }
-#[cfg(feature = "dlopen")]
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
 pub fn is_lib_available() -> bool {
     LIBDECOR_OPTION.is_some()
 }
 
-#[cfg(feature = "dlopen")]
+#[cfg(any(feature = "dlopen", feature = "mock"))]
 #[macro_export]
 macro_rules! ffi_dispatch(
     ($handle: ident, $func: ident, $($arg: expr),*) => (
@@ -262,10 +472,39 @@ macro_rules! ffi_dispatch(
     )
 );
 
-#[cfg(not(feature = "dlopen"))]
+#[cfg(not(any(feature = "dlopen", feature = "mock")))]
 #[macro_export]
 macro_rules! ffi_dispatch(
     ($handle: ident, $func: ident, $($arg: expr),*) => (
         $func($($arg),*)
     )
 );
+
+/// Call an optional function, evaluates to [`None`] if the function is
+/// missing in the loaded library
+///
+/// The functions are only optional with the `dlopen` feature, otherwise
+/// the result is always [`Some`].
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
+#[macro_export]
+macro_rules! ffi_dispatch_optional(
+    ($handle: ident, $func: ident, $($arg: expr),*) => (
+        $handle.$func.map(|func| func($($arg),*))
+    )
+);
+
+#[cfg(feature = "mock")]
+#[macro_export]
+macro_rules! ffi_dispatch_optional(
+    ($handle: ident, $func: ident, $($arg: expr),*) => (
+        Some(($handle.$func)($($arg),*))
+    )
+);
+
+#[cfg(not(any(feature = "dlopen", feature = "mock")))]
+#[macro_export]
+macro_rules! ffi_dispatch_optional(
+    ($handle: ident, $func: ident, $($arg: expr),*) => (
+        Some($func($($arg),*))
+    )
+);
@@ -0,0 +1,143 @@
+//! Loading libdecor at runtime
+//!
+//! By default the library is looked up by its name `libdecor-0.so` on the
+//! first call. Another copy, e.g. one bundled with the application, can be
+//! loaded with [`load`] or [`load_from_candidates`] and selected with
+//! [`set_library`] before that.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use crate::Libdecor;
+
+/// The library used by [`LIBDECOR_OPTION`](crate::LIBDECOR_OPTION)
+static LIBRARY: OnceLock<Result<&'static Libdecor, LoadError>> = OnceLock::new();
+
+/// An error loading libdecor
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum LoadError {
+    /// The library could not be opened
+    Open {
+        /// Path of the library
+        path: PathBuf,
+        /// The error reported by the dynamic loader
+        source: libloading::Error,
+    },
+    /// A function required by this crate is missing in the library
+    MissingSymbol {
+        /// Path of the library
+        path: PathBuf,
+        /// Name of the missing function
+        symbol: &'static str,
+    },
+    /// None of the candidates could be loaded
+    ///
+    /// Contains the error for each candidate in the order they were tried.
+    NoCandidate(Vec<LoadError>),
+    /// A library has already been selected or loaded for this process
+    AlreadyLoaded,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Open { path, source } => {
+                write!(f, "failed to open {}: {}", path.display(), source)
+            }
+            LoadError::MissingSymbol { path, symbol } => {
+                write!(f, "{} is missing the function {}", path.display(), symbol)
+            }
+            LoadError::NoCandidate(errors) if errors.is_empty() => {
+                f.write_str("no libdecor library to load")
+            }
+            LoadError::NoCandidate(errors) => {
+                f.write_str("failed to load libdecor")?;
+                for error in errors {
+                    write!(f, "; {}", error)?;
+                }
+                Ok(())
+            }
+            LoadError::AlreadyLoaded => f.write_str("libdecor has already been loaded"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Open { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Load libdecor from the given path.
+///
+/// A path without a slash is looked up in the search path of the dynamic
+/// loader, see `dlopen(3)`.
+///
+/// # Safety
+///
+/// Loading a library runs its initialization routines, the library has to
+/// be libdecor or export the same functions.
+pub unsafe fn load<P: AsRef<Path>>(path: P) -> Result<Libdecor, LoadError> {
+    Libdecor::open(path.as_ref().as_os_str())
+}
+
+/// Load libdecor from the first of the given paths that succeeds.
+///
+/// # Safety
+///
+/// See [`load`].
+pub unsafe fn load_from_candidates<P: AsRef<Path>>(
+    candidates: &[P],
+) -> Result<Libdecor, LoadError> {
+    let mut errors = Vec::new();
+    for candidate in candidates {
+        match load(candidate) {
+            Ok(lib) => return Ok(lib),
+            Err(err) => errors.push(err),
+        }
+    }
+    Err(LoadError::NoCandidate(errors))
+}
+
+/// Use `lib` for all calls to libdecor in this process.
+///
+/// Has to be called before libdecor is used for the first time, otherwise
+/// [`LoadError::AlreadyLoaded`] is returned. The library is never unloaded,
+/// a loaded library can be leaked with [`Box::leak`].
+pub fn set_library(lib: &'static Libdecor) -> Result<(), LoadError> {
+    let mut selected = false;
+    LIBRARY.get_or_init(|| {
+        selected = true;
+        Ok(lib)
+    });
+    if selected {
+        Ok(())
+    } else {
+        Err(LoadError::AlreadyLoaded)
+    }
+}
+
+/// Returns the library selected by [`set_library`], or loads the default one.
+pub(crate) fn library() -> Result<&'static Libdecor, &'static LoadError> {
+    LIBRARY
+        .get_or_init(|| unsafe {
+            let name = libloading::library_filename("decor-0");
+            load(Path::new(&name)).map(|lib| &*Box::leak(Box::new(lib)))
+        })
+        .as_ref()
+        .copied()
+}
+
+/// Returns why libdecor could not be loaded.
+///
+/// Returns [`None`] if the library has been loaded or has not been used yet.
+pub fn load_error() -> Option<&'static LoadError> {
+    LIBRARY.get().and_then(|result| result.as_ref().err())
+}
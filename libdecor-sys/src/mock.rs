@@ -0,0 +1,962 @@
+//! A pure Rust stand-in for libdecor
+//!
+//! Enabled by the `mock` feature. All functions of [`Libdecor`](crate::Libdecor)
+//! are implemented without talking to a compositor, every call is recorded
+//! and frame events can be injected to test applications headless.
+//!
+//! Injected events are queued on the context of the frame and delivered by
+//! the next `libdecor_dispatch`, which never blocks. The file descriptor
+//! returned by `libdecor_get_fd` becomes readable while events are queued.
+//!
+//! With the `plugin` feature the functions exported by libdecor to plugins
+//! are mocked as well, so a plugin can be constructed for a mocked context.
+//!
+//! The state of the mock is thread local.
+
+// The functions mirror the C API of libdecor
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    ffi::{c_void, CStr, CString},
+    io::{Read, Write},
+    os::{
+        raw::{c_char, c_int},
+        unix::{io::AsRawFd, net::UnixStream},
+    },
+    path::PathBuf,
+    ptr,
+};
+
+use crate::*;
+
+/// A recorded call of a libdecor function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    /// The name of the called function
+    pub function: &'static str,
+    /// The frame the function was called for, null for other functions
+    pub frame: *mut libdecor_frame,
+}
+
+/// An event that can be injected for a frame, see [`inject`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// Invokes the configure callback
+    Configure {
+        /// The content size returned by the configuration
+        content_size: Option<(c_int, c_int)>,
+        /// The window state returned by the configuration
+        window_state: Option<libdecor_window_state>,
+    },
+    /// Invokes the close callback
+    Close,
+    /// Invokes the commit callback
+    Commit,
+    /// Invokes the dismiss popup callback
+    DismissPopup {
+        /// The name of the seat
+        seat_name: String,
+    },
+}
+
+/// The observable state of a mocked frame, see [`frame_info`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameInfo {
+    /// The current reference count
+    pub ref_count: usize,
+    /// The title set with `libdecor_frame_set_title`
+    pub title: Option<String>,
+    /// The application id set with `libdecor_frame_set_app_id`
+    pub app_id: Option<String>,
+    /// The current capabilities
+    pub capabilities: libdecor_capabilities,
+    /// Whether the frame is visible
+    pub visible: bool,
+    /// Whether the frame has been mapped
+    pub mapped: bool,
+    /// The min content size
+    pub min_content_size: (c_int, c_int),
+    /// The max content size
+    pub max_content_size: (c_int, c_int),
+    /// The window state of the last committed configuration
+    pub window_state: libdecor_window_state,
+    /// The sizes of all committed states
+    pub committed: Vec<(c_int, c_int)>,
+    /// The number of committed configurations
+    pub acked_configurations: usize,
+    /// The seats with an active popup grab
+    pub popup_grabs: Vec<String>,
+    /// The capabilities of the compositor, all by default
+    #[cfg(feature = "libdecor_0_2")]
+    pub wm_capabilities: libdecor_wm_capabilities,
+    /// The window geometry set by a plugin as `(x, y, width, height)`
+    #[cfg(feature = "plugin")]
+    pub window_geometry: Option<(c_int, c_int, c_int, c_int)>,
+}
+
+/// The plugin directory seen by `libdecor_new`, see [`plugin_dir`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PluginDir {
+    /// The value of `LIBDECOR_PLUGIN_DIR`
+    pub path: Option<PathBuf>,
+    /// The sorted file names in the directory
+    pub files: Vec<String>,
+}
+
+enum Pending {
+    Frame(*mut libdecor_frame, Event),
+    Error(libdecor_error, CString),
+}
+
+struct MockContext {
+    #[cfg(feature = "plugin")]
+    display: *mut wl_display,
+    interface: *const libdecor_interface,
+    plugin_dir: PluginDir,
+    pending: VecDeque<Pending>,
+    // The receiving end is handed out as the file descriptor of the context
+    receiver: UnixStream,
+    sender: UnixStream,
+}
+
+struct MockFrame {
+    context: *mut libdecor,
+    #[cfg(feature = "plugin")]
+    surface: *mut wl_surface,
+    interface: *const libdecor_frame_interface,
+    user_data: *mut c_void,
+    title: CString,
+    info: FrameInfo,
+}
+
+struct MockConfiguration {
+    content_size: Option<(c_int, c_int)>,
+    window_state: Option<libdecor_window_state>,
+}
+
+#[derive(Default)]
+// Boxed to keep the addresses handed out as pointers stable
+#[allow(clippy::vec_box)]
+struct Mock {
+    contexts: Vec<Box<MockContext>>,
+    frames: Vec<Box<MockFrame>>,
+    calls: Vec<Call>,
+    fail_next_new: bool,
+    #[cfg(feature = "plugin")]
+    plugins: Vec<(
+        *mut plugin::libdecor_plugin,
+        *mut plugin::libdecor_plugin_interface,
+    )>,
+}
+
+impl Mock {
+    fn context(&mut self, context: *mut libdecor) -> &mut MockContext {
+        self.contexts
+            .iter_mut()
+            .find(|c| &***c as *const MockContext as *mut libdecor == context)
+            .expect("unknown libdecor context")
+    }
+
+    fn find_frame(&mut self, frame: *mut libdecor_frame) -> Option<&mut MockFrame> {
+        self.frames
+            .iter_mut()
+            .find(|f| &***f as *const MockFrame as *mut libdecor_frame == frame)
+            .map(|f| &mut **f)
+    }
+
+    fn frame(&mut self, frame: *mut libdecor_frame) -> &mut MockFrame {
+        self.find_frame(frame).expect("unknown libdecor frame")
+    }
+}
+
+thread_local! {
+    static MOCK: RefCell<Mock> = RefCell::new(Mock::default());
+}
+
+fn with_mock<F, R>(function: &'static str, frame: *mut libdecor_frame, f: F) -> R
+where
+    F: FnOnce(&mut Mock) -> R,
+{
+    MOCK.with(|mock| {
+        let mut mock = mock.borrow_mut();
+        mock.calls.push(Call { function, frame });
+        f(&mut mock)
+    })
+}
+
+/// Returns all recorded calls.
+pub fn calls() -> Vec<Call> {
+    MOCK.with(|mock| mock.borrow().calls.clone())
+}
+
+/// Returns and clears all recorded calls.
+pub fn take_calls() -> Vec<Call> {
+    MOCK.with(|mock| std::mem::take(&mut mock.borrow_mut().calls))
+}
+
+/// Returns all live contexts in creation order.
+pub fn contexts() -> Vec<*mut libdecor> {
+    MOCK.with(|mock| {
+        mock.borrow()
+            .contexts
+            .iter()
+            .map(|c| &**c as *const MockContext as *mut libdecor)
+            .collect()
+    })
+}
+
+/// Returns all live frames in creation order.
+pub fn frames() -> Vec<*mut libdecor_frame> {
+    MOCK.with(|mock| {
+        mock.borrow()
+            .frames
+            .iter()
+            .map(|f| &**f as *const MockFrame as *mut libdecor_frame)
+            .collect()
+    })
+}
+
+/// Returns the state of the given frame, [`None`] if it has been destroyed.
+pub fn frame_info(frame: *mut libdecor_frame) -> Option<FrameInfo> {
+    MOCK.with(|mock| mock.borrow_mut().find_frame(frame).map(|f| f.info.clone()))
+}
+
+/// Returns the plugin directory seen when creating the given context,
+/// [`None`] if it has been destroyed.
+pub fn plugin_dir(context: *mut libdecor) -> Option<PluginDir> {
+    MOCK.with(|mock| {
+        mock.borrow()
+            .contexts
+            .iter()
+            .find(|c| &***c as *const MockContext as *mut libdecor == context)
+            .map(|c| c.plugin_dir.clone())
+    })
+}
+
+/// Let the next call to `libdecor_new` fail.
+pub fn fail_next_new() {
+    MOCK.with(|mock| mock.borrow_mut().fail_next_new = true)
+}
+
+/// Set the capabilities of the compositor reported for the given frame.
+#[cfg(feature = "libdecor_0_2")]
+pub fn set_wm_capabilities(frame: *mut libdecor_frame, wm_capabilities: libdecor_wm_capabilities) {
+    MOCK.with(|mock| mock.borrow_mut().frame(frame).info.wm_capabilities = wm_capabilities)
+}
+
+/// Returns all initialized plugins with their interface in creation order.
+#[cfg(feature = "plugin")]
+pub fn plugins() -> Vec<(
+    *mut plugin::libdecor_plugin,
+    *mut plugin::libdecor_plugin_interface,
+)> {
+    MOCK.with(|mock| mock.borrow().plugins.clone())
+}
+
+/// Queue an event for the given frame.
+pub fn inject(frame: *mut libdecor_frame, event: Event) {
+    MOCK.with(|mock| {
+        let mut mock = mock.borrow_mut();
+        let context = mock.frame(frame).context;
+        mock.context(context).push(Pending::Frame(frame, event));
+    })
+}
+
+/// Queue an error for the given context.
+pub fn inject_error(context: *mut libdecor, error: libdecor_error, message: &str) {
+    let message = CString::new(message).unwrap();
+    MOCK.with(|mock| {
+        mock.borrow_mut()
+            .context(context)
+            .push(Pending::Error(error, message));
+    })
+}
+
+impl MockContext {
+    fn push(&mut self, pending: Pending) {
+        self.pending.push_back(pending);
+        let _ = self.sender.write(&[0]);
+    }
+}
+
+/// Deliver an error to the context, must not be called with the mock borrowed
+fn notify_error(context: *mut libdecor, error: libdecor_error, message: &CStr) {
+    let interface = MOCK.with(|mock| {
+        let mut mock = mock.borrow_mut();
+        mock.contexts
+            .iter_mut()
+            .find(|c| &***c as *const MockContext as *mut libdecor == context)
+            .map(|c| c.interface)
+    });
+
+    if let Some(interface) = interface {
+        unsafe { ((*interface).error)(context, error, message.as_ptr()) }
+    }
+}
+
+/// Deliver an event to the frame, must not be called with the mock borrowed
+fn notify_frame(frame: *mut libdecor_frame, event: Event) {
+    let callbacks = MOCK.with(|mock| {
+        mock.borrow_mut()
+            .find_frame(frame)
+            .map(|f| (f.interface, f.user_data))
+    });
+
+    // The frame has been destroyed in the meantime
+    let (interface, user_data) = match callbacks {
+        Some(callbacks) => callbacks,
+        None => return,
+    };
+    let interface = unsafe { &*interface };
+
+    unsafe {
+        match event {
+            Event::Configure {
+                content_size,
+                window_state,
+            } => {
+                let configuration = Box::into_raw(Box::new(MockConfiguration {
+                    content_size,
+                    window_state,
+                }));
+                (interface.configure)(frame, configuration as *mut _, user_data);
+                drop(Box::from_raw(configuration));
+            }
+            Event::Close => (interface.close)(frame, user_data),
+            Event::Commit => (interface.commit)(frame, user_data),
+            Event::DismissPopup { seat_name } => {
+                let seat_name = CString::new(seat_name).unwrap();
+                (interface.dismiss_popup)(frame, seat_name.as_ptr(), user_data)
+            }
+        }
+    }
+}
+
+pub unsafe extern "C" fn libdecor_new(
+    #[cfg_attr(not(feature = "plugin"), allow(unused_variables))] display: *mut wl_display,
+    interface: *mut libdecor_interface,
+) -> *mut libdecor {
+    with_mock("libdecor_new", ptr::null_mut(), |mock| {
+        if std::mem::take(&mut mock.fail_next_new) {
+            return ptr::null_mut();
+        }
+
+        let (receiver, sender) = UnixStream::pair().expect("failed to create socket pair");
+        receiver.set_nonblocking(true).unwrap();
+        sender.set_nonblocking(true).unwrap();
+
+        // libdecor loads the plugins while creating the context
+        let path = std::env::var_os("LIBDECOR_PLUGIN_DIR").map(PathBuf::from);
+        let mut files: Vec<String> = path
+            .as_ref()
+            .and_then(|path| std::fs::read_dir(path).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .collect();
+        files.sort();
+
+        let context = Box::new(MockContext {
+            #[cfg(feature = "plugin")]
+            display,
+            interface,
+            plugin_dir: PluginDir { path, files },
+            pending: VecDeque::new(),
+            receiver,
+            sender,
+        });
+        let ptr = &*context as *const MockContext as *mut libdecor;
+        mock.contexts.push(context);
+        ptr
+    })
+}
+
+pub unsafe extern "C" fn libdecor_unref(context: *mut libdecor) {
+    with_mock("libdecor_unref", ptr::null_mut(), |mock| {
+        mock.contexts
+            .retain(|c| &**c as *const MockContext as *mut libdecor != context);
+    })
+}
+
+pub unsafe extern "C" fn libdecor_get_fd(context: *mut libdecor) -> c_int {
+    with_mock("libdecor_get_fd", ptr::null_mut(), |mock| {
+        mock.context(context).receiver.as_raw_fd()
+    })
+}
+
+pub unsafe extern "C" fn libdecor_dispatch(context: *mut libdecor, _timeout: c_int) -> c_int {
+    with_mock("libdecor_dispatch", ptr::null_mut(), |_| ());
+
+    let mut count = 0;
+    loop {
+        let pending = MOCK.with(|mock| {
+            let mut mock = mock.borrow_mut();
+            let context = mock.context(context);
+            let pending = context.pending.pop_front();
+            if context.pending.is_empty() {
+                let mut buf = [0; 64];
+                while let Ok(n) = context.receiver.read(&mut buf) {
+                    if n == 0 {
+                        break;
+                    }
+                }
+            }
+            pending
+        });
+
+        match pending {
+            Some(Pending::Frame(frame, event)) => notify_frame(frame, event),
+            Some(Pending::Error(error, message)) => notify_error(context, error, &message),
+            None => break,
+        }
+        count += 1;
+    }
+
+    count
+}
+
+pub unsafe extern "C" fn libdecor_decorate(
+    context: *mut libdecor,
+    #[cfg_attr(not(feature = "plugin"), allow(unused_variables))] surface: *mut wl_surface,
+    interface: *mut libdecor_frame_interface,
+    user_data: *mut c_void,
+) -> *mut libdecor_frame {
+    with_mock("libdecor_decorate", ptr::null_mut(), |mock| {
+        let frame = Box::new(MockFrame {
+            context,
+            #[cfg(feature = "plugin")]
+            surface,
+            interface,
+            user_data,
+            title: CString::default(),
+            info: FrameInfo {
+                ref_count: 1,
+                visible: true,
+                capabilities: LIBDECOR_ACTION_MOVE
+                    | LIBDECOR_ACTION_RESIZE
+                    | LIBDECOR_ACTION_MINIMIZE
+                    | LIBDECOR_ACTION_FULLSCREEN
+                    | LIBDECOR_ACTION_CLOSE,
+                #[cfg(feature = "libdecor_0_2")]
+                wm_capabilities: LIBDECOR_WM_CAPABILITIES_WINDOW_MENU
+                    | LIBDECOR_WM_CAPABILITIES_MAXIMIZE
+                    | LIBDECOR_WM_CAPABILITIES_FULLSCREEN
+                    | LIBDECOR_WM_CAPABILITIES_MINIMIZE,
+                ..FrameInfo::default()
+            },
+        });
+        let ptr = &*frame as *const MockFrame as *mut libdecor_frame;
+        mock.frames.push(frame);
+        ptr
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_ref(frame: *mut libdecor_frame) {
+    with_mock("libdecor_frame_ref", frame, |mock| {
+        mock.frame(frame).info.ref_count += 1
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_unref(frame: *mut libdecor_frame) {
+    with_mock("libdecor_frame_unref", frame, |mock| {
+        let info = &mut mock.frame(frame).info;
+        info.ref_count -= 1;
+        if info.ref_count == 0 {
+            mock.frames
+                .retain(|f| &**f as *const MockFrame as *mut libdecor_frame != frame);
+        }
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_set_visibility(frame: *mut libdecor_frame, visible: bool) {
+    with_mock("libdecor_frame_set_visibility", frame, |mock| {
+        mock.frame(frame).info.visible = visible
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_is_visible(frame: *mut libdecor_frame) -> bool {
+    with_mock("libdecor_frame_is_visible", frame, |mock| {
+        mock.frame(frame).info.visible
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_set_parent(
+    frame: *mut libdecor_frame,
+    _parent: *mut libdecor_frame,
+) {
+    with_mock("libdecor_frame_set_parent", frame, |_| ())
+}
+
+pub unsafe extern "C" fn libdecor_frame_set_title(
+    frame: *mut libdecor_frame,
+    title: *const c_char,
+) {
+    let title = CStr::from_ptr(title);
+    with_mock("libdecor_frame_set_title", frame, |mock| {
+        let frame = mock.frame(frame);
+        frame.title = title.to_owned();
+        frame.info.title = Some(title.to_string_lossy().into_owned());
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_get_title(frame: *mut libdecor_frame) -> *const c_char {
+    with_mock("libdecor_frame_get_title", frame, |mock| {
+        mock.frame(frame).title.as_ptr()
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_set_app_id(
+    frame: *mut libdecor_frame,
+    app_id: *const c_char,
+) {
+    let app_id = CStr::from_ptr(app_id);
+    with_mock("libdecor_frame_set_app_id", frame, |mock| {
+        mock.frame(frame).info.app_id = Some(app_id.to_string_lossy().into_owned());
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_set_capabilities(
+    frame: *mut libdecor_frame,
+    capabilities: libdecor_capabilities,
+) {
+    with_mock("libdecor_frame_set_capabilities", frame, |mock| {
+        mock.frame(frame).info.capabilities |= capabilities
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_unset_capabilities(
+    frame: *mut libdecor_frame,
+    capabilities: libdecor_capabilities,
+) {
+    with_mock("libdecor_frame_unset_capabilities", frame, |mock| {
+        mock.frame(frame).info.capabilities &= !capabilities
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_has_capability(
+    frame: *mut libdecor_frame,
+    capabilities: libdecor_capabilities,
+) -> bool {
+    with_mock("libdecor_frame_has_capability", frame, |mock| {
+        mock.frame(frame).info.capabilities & capabilities != 0
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_show_window_menu(
+    frame: *mut libdecor_frame,
+    _seat: *mut wl_seat,
+    _serial: u32,
+    _x: c_int,
+    _y: c_int,
+) {
+    with_mock("libdecor_frame_show_window_menu", frame, |_| ())
+}
+
+pub unsafe extern "C" fn libdecor_frame_popup_grab(
+    frame: *mut libdecor_frame,
+    seat_name: *const c_char,
+) {
+    let seat_name = CStr::from_ptr(seat_name).to_string_lossy().into_owned();
+    with_mock("libdecor_frame_popup_grab", frame, |mock| {
+        mock.frame(frame).info.popup_grabs.push(seat_name)
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_popup_ungrab(
+    frame: *mut libdecor_frame,
+    seat_name: *const c_char,
+) {
+    let seat_name = CStr::from_ptr(seat_name).to_string_lossy().into_owned();
+    with_mock("libdecor_frame_popup_ungrab", frame, |mock| {
+        mock.frame(frame)
+            .info
+            .popup_grabs
+            .retain(|s| *s != seat_name)
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_translate_coordinate(
+    frame: *mut libdecor_frame,
+    surface_x: c_int,
+    surface_y: c_int,
+    frame_x: *mut c_int,
+    frame_y: *mut c_int,
+) {
+    with_mock("libdecor_frame_translate_coordinate", frame, |_| ());
+    *frame_x = surface_x;
+    *frame_y = surface_y;
+}
+
+pub unsafe extern "C" fn libdecor_frame_set_max_content_size(
+    frame: *mut libdecor_frame,
+    width: c_int,
+    height: c_int,
+) {
+    with_mock("libdecor_frame_set_max_content_size", frame, |mock| {
+        mock.frame(frame).info.max_content_size = (width, height)
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_set_min_content_size(
+    frame: *mut libdecor_frame,
+    width: c_int,
+    height: c_int,
+) {
+    with_mock("libdecor_frame_set_min_content_size", frame, |mock| {
+        mock.frame(frame).info.min_content_size = (width, height)
+    })
+}
+
+#[cfg(feature = "libdecor_0_2")]
+pub unsafe extern "C" fn libdecor_frame_get_min_content_size(
+    frame: *const libdecor_frame,
+    width: *mut c_int,
+    height: *mut c_int,
+) {
+    let frame = frame as *mut libdecor_frame;
+    let (w, h) = with_mock("libdecor_frame_get_min_content_size", frame, |mock| {
+        mock.frame(frame).info.min_content_size
+    });
+    *width = w;
+    *height = h;
+}
+
+#[cfg(feature = "libdecor_0_2")]
+pub unsafe extern "C" fn libdecor_frame_get_max_content_size(
+    frame: *const libdecor_frame,
+    width: *mut c_int,
+    height: *mut c_int,
+) {
+    let frame = frame as *mut libdecor_frame;
+    let (w, h) = with_mock("libdecor_frame_get_max_content_size", frame, |mock| {
+        mock.frame(frame).info.max_content_size
+    });
+    *width = w;
+    *height = h;
+}
+
+#[cfg(feature = "libdecor_0_2")]
+pub unsafe extern "C" fn libdecor_frame_get_wm_capabilities(
+    frame: *mut libdecor_frame,
+) -> libdecor_wm_capabilities {
+    with_mock("libdecor_frame_get_wm_capabilities", frame, |mock| {
+        mock.frame(frame).info.wm_capabilities
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_resize(
+    frame: *mut libdecor_frame,
+    _seat: *mut wl_seat,
+    _serial: u32,
+    _edge: libdecor_resize_edge,
+) {
+    with_mock("libdecor_frame_resize", frame, |_| ())
+}
+
+pub unsafe extern "C" fn libdecor_frame_move(
+    frame: *mut libdecor_frame,
+    _seat: *mut wl_seat,
+    _serial: u32,
+) {
+    with_mock("libdecor_frame_move", frame, |_| ())
+}
+
+/// Commits the state and reports an invalid frame configuration like
+/// libdecor if the min content size exceeds the max content size.
+pub unsafe extern "C" fn libdecor_frame_commit(
+    frame: *mut libdecor_frame,
+    state: *mut libdecor_state,
+    configuration: *mut libdecor_configuration,
+) {
+    let size = *(state as *mut (c_int, c_int));
+    let window_state = if configuration.is_null() {
+        None
+    } else {
+        Some((*(configuration as *mut MockConfiguration)).window_state)
+    };
+
+    let invalid = with_mock("libdecor_frame_commit", frame, |mock| {
+        let frame = mock.frame(frame);
+        let info = &mut frame.info;
+
+        info.committed.push(size);
+        if let Some(window_state) = window_state {
+            info.acked_configurations += 1;
+            if let Some(window_state) = window_state {
+                info.window_state = window_state;
+            }
+        }
+
+        let (min, max) = (info.min_content_size, info.max_content_size);
+        let invalid = max.0 > 0 && min.0 > max.0 || max.1 > 0 && min.1 > max.1;
+        if invalid {
+            Some(frame.context)
+        } else {
+            None
+        }
+    });
+
+    if let Some(context) = invalid {
+        let message = CString::new("min content size is larger than max content size").unwrap();
+        notify_error(
+            context,
+            LIBDECOR_ERROR_INVALID_FRAME_CONFIGURATION,
+            &message,
+        );
+    }
+}
+
+pub unsafe extern "C" fn libdecor_frame_set_minimized(frame: *mut libdecor_frame) {
+    with_mock("libdecor_frame_set_minimized", frame, |_| ())
+}
+
+pub unsafe extern "C" fn libdecor_frame_set_maximized(frame: *mut libdecor_frame) {
+    with_mock("libdecor_frame_set_maximized", frame, |_| ())
+}
+
+pub unsafe extern "C" fn libdecor_frame_unset_maximized(frame: *mut libdecor_frame) {
+    with_mock("libdecor_frame_unset_maximized", frame, |_| ())
+}
+
+pub unsafe extern "C" fn libdecor_frame_set_fullscreen(
+    frame: *mut libdecor_frame,
+    _output: *mut wl_output,
+) {
+    with_mock("libdecor_frame_set_fullscreen", frame, |_| ())
+}
+
+pub unsafe extern "C" fn libdecor_frame_unset_fullscreen(frame: *mut libdecor_frame) {
+    with_mock("libdecor_frame_unset_fullscreen", frame, |_| ())
+}
+
+pub unsafe extern "C" fn libdecor_frame_is_floating(frame: *mut libdecor_frame) -> bool {
+    with_mock("libdecor_frame_is_floating", frame, |mock| {
+        mock.frame(frame).info.window_state
+            & (LIBDECOR_WINDOW_STATE_MAXIMIZED
+                | LIBDECOR_WINDOW_STATE_FULLSCREEN
+                | LIBDECOR_WINDOW_STATE_TILED_LEFT
+                | LIBDECOR_WINDOW_STATE_TILED_RIGHT
+                | LIBDECOR_WINDOW_STATE_TILED_TOP
+                | LIBDECOR_WINDOW_STATE_TILED_BOTTOM)
+            == 0
+    })
+}
+
+/// Invokes the close callback synchronously like libdecor.
+pub unsafe extern "C" fn libdecor_frame_close(frame: *mut libdecor_frame) {
+    with_mock("libdecor_frame_close", frame, |_| ());
+    notify_frame(frame, Event::Close);
+}
+
+pub unsafe extern "C" fn libdecor_frame_map(frame: *mut libdecor_frame) {
+    with_mock("libdecor_frame_map", frame, |mock| {
+        mock.frame(frame).info.mapped = true
+    })
+}
+
+pub unsafe extern "C" fn libdecor_frame_get_xdg_surface(
+    frame: *mut libdecor_frame,
+) -> *mut xdg_surface {
+    with_mock("libdecor_frame_get_xdg_surface", frame, |_| ptr::null_mut())
+}
+
+pub unsafe extern "C" fn libdecor_frame_get_xdg_toplevel(
+    frame: *mut libdecor_frame,
+) -> *mut xdg_toplevel {
+    with_mock("libdecor_frame_get_xdg_toplevel", frame, |_| {
+        ptr::null_mut()
+    })
+}
+
+pub unsafe extern "C" fn libdecor_state_new(width: c_int, height: c_int) -> *mut libdecor_state {
+    with_mock("libdecor_state_new", ptr::null_mut(), |_| ());
+    Box::into_raw(Box::new((width, height))) as *mut libdecor_state
+}
+
+pub unsafe extern "C" fn libdecor_state_free(state: *mut libdecor_state) {
+    with_mock("libdecor_state_free", ptr::null_mut(), |_| ());
+    drop(Box::from_raw(state as *mut (c_int, c_int)));
+}
+
+pub unsafe extern "C" fn libdecor_configuration_get_content_size(
+    configuration: *mut libdecor_configuration,
+    frame: *mut libdecor_frame,
+    width: *mut c_int,
+    height: *mut c_int,
+) -> bool {
+    with_mock("libdecor_configuration_get_content_size", frame, |_| ());
+    match (*(configuration as *mut MockConfiguration)).content_size {
+        Some((w, h)) => {
+            *width = w;
+            *height = h;
+            true
+        }
+        None => false,
+    }
+}
+
+pub unsafe extern "C" fn libdecor_configuration_get_window_state(
+    configuration: *mut libdecor_configuration,
+    window_state: *mut libdecor_window_state,
+) -> bool {
+    with_mock(
+        "libdecor_configuration_get_window_state",
+        ptr::null_mut(),
+        |_| (),
+    );
+    match (*(configuration as *mut MockConfiguration)).window_state {
+        Some(state) => {
+            *window_state = state;
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_plugin_init(
+    plugin: *mut plugin::libdecor_plugin,
+    _context: *mut libdecor,
+    interface: *mut plugin::libdecor_plugin_interface,
+) -> c_int {
+    with_mock("libdecor_plugin_init", ptr::null_mut(), |mock| {
+        mock.plugins.push((plugin, interface));
+        0
+    })
+}
+
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_plugin_release(plugin: *mut plugin::libdecor_plugin) {
+    with_mock("libdecor_plugin_release", ptr::null_mut(), |mock| {
+        mock.plugins.retain(|(p, _)| *p != plugin)
+    })
+}
+
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_get_wl_display(context: *mut libdecor) -> *mut wl_display {
+    with_mock("libdecor_get_wl_display", ptr::null_mut(), |mock| {
+        mock.context(context).display
+    })
+}
+
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_notify_plugin_ready(_context: *mut libdecor) {
+    with_mock("libdecor_notify_plugin_ready", ptr::null_mut(), |_| ())
+}
+
+/// Reports the error to the context, only supports `"%s"` as format.
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_notify_plugin_error(
+    context: *mut libdecor,
+    error: libdecor_error,
+    _format: *const c_char,
+    message: *const c_char,
+) {
+    with_mock("libdecor_notify_plugin_error", ptr::null_mut(), |_| ());
+    notify_error(context, error, CStr::from_ptr(message));
+}
+
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_frame_get_wl_surface(
+    frame: *mut libdecor_frame,
+) -> *mut wl_surface {
+    with_mock("libdecor_frame_get_wl_surface", frame, |mock| {
+        mock.frame(frame).surface
+    })
+}
+
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_frame_get_content_width(frame: *mut libdecor_frame) -> c_int {
+    with_mock("libdecor_frame_get_content_width", frame, |mock| {
+        mock.frame(frame)
+            .info
+            .committed
+            .last()
+            .map_or(0, |size| size.0)
+    })
+}
+
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_frame_get_content_height(frame: *mut libdecor_frame) -> c_int {
+    with_mock("libdecor_frame_get_content_height", frame, |mock| {
+        mock.frame(frame)
+            .info
+            .committed
+            .last()
+            .map_or(0, |size| size.1)
+    })
+}
+
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_frame_get_window_state(
+    frame: *mut libdecor_frame,
+) -> libdecor_window_state {
+    with_mock("libdecor_frame_get_window_state", frame, |mock| {
+        mock.frame(frame).info.window_state
+    })
+}
+
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_frame_get_capabilities(
+    frame: *const libdecor_frame,
+) -> libdecor_capabilities {
+    let frame = frame as *mut libdecor_frame;
+    with_mock("libdecor_frame_get_capabilities", frame, |mock| {
+        mock.frame(frame).info.capabilities
+    })
+}
+
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_frame_set_window_geometry(
+    frame: *mut libdecor_frame,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+) {
+    with_mock("libdecor_frame_set_window_geometry", frame, |mock| {
+        mock.frame(frame).info.window_geometry = Some((x, y, width, height))
+    })
+}
+
+/// Invokes the dismiss popup callback of the frame.
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_frame_dismiss_popup(
+    frame: *mut libdecor_frame,
+    seat_name: *const c_char,
+) {
+    with_mock("libdecor_frame_dismiss_popup", frame, |_| ());
+    let seat_name = CStr::from_ptr(seat_name).to_string_lossy().into_owned();
+    notify_frame(frame, Event::DismissPopup { seat_name });
+}
+
+/// Invokes the commit callback of the frame.
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_frame_toplevel_commit(frame: *mut libdecor_frame) {
+    with_mock("libdecor_frame_toplevel_commit", frame, |_| ());
+    notify_frame(frame, Event::Commit);
+}
+
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_state_get_content_width(state: *mut libdecor_state) -> c_int {
+    with_mock("libdecor_state_get_content_width", ptr::null_mut(), |_| ());
+    (*(state as *mut (c_int, c_int))).0
+}
+
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_state_get_content_height(state: *mut libdecor_state) -> c_int {
+    with_mock("libdecor_state_get_content_height", ptr::null_mut(), |_| ());
+    (*(state as *mut (c_int, c_int))).1
+}
+
+/// The mocked states carry no window state, always returns
+/// `LIBDECOR_WINDOW_STATE_NONE`.
+#[cfg(feature = "plugin")]
+pub unsafe extern "C" fn libdecor_state_get_window_state(
+    _state: *mut libdecor_state,
+) -> libdecor_window_state {
+    with_mock("libdecor_state_get_window_state", ptr::null_mut(), |_| ());
+    LIBDECOR_WINDOW_STATE_NONE
+}
@@ -0,0 +1,218 @@
+//! Bindings to the plugin interface of libdecor
+//!
+//! Mirrors `libdecor-plugin.h` which is used by decoration plugins loaded by
+//! libdecor from its plugin directory. The functions are exported by
+//! libdecor itself and enabled by the `plugin` feature.
+
+#[cfg(feature = "libdecor_0_2")]
+use std::os::raw::c_uint;
+use std::os::raw::{c_char, c_int};
+
+#[cfg(all(feature = "plugin", not(feature = "mock")))]
+use dlib::external_library;
+
+use crate::*;
+
+pub const LIBDECOR_PLUGIN_API_VERSION: c_int = 1;
+/// Length of [`libdecor_plugin_description::conflicting_symbols`]
+pub const LIBDECOR_PLUGIN_CONFLICTING_SYMBOLS_MAX: usize = 1024;
+pub const LIBDECOR_PLUGIN_PRIORITY_HIGH: c_int = 1000;
+pub const LIBDECOR_PLUGIN_PRIORITY_MEDIUM: c_int = 100;
+pub const LIBDECOR_PLUGIN_PRIORITY_LOW: c_int = 0;
+
+#[cfg(feature = "libdecor_0_2")]
+pub type libdecor_plugin_capabilities = c_uint;
+
+#[cfg(feature = "libdecor_0_2")]
+pub const LIBDECOR_PLUGIN_CAPABILITY_BASE: libdecor_plugin_capabilities = 1 << 0;
+
+pub enum libdecor_plugin_private {}
+pub enum libdecor_frame_private {}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct wl_list {
+    pub prev: *mut wl_list,
+    pub next: *mut wl_list,
+}
+
+/// Has to be the first member of the plugin instance
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct libdecor_plugin {
+    pub priv_: *mut libdecor_plugin_private,
+}
+
+/// Layout of `struct libdecor_frame`
+///
+/// Has to be the first member of the frames created by a plugin. The
+/// frames are freed by libdecor after `frame_free`, so they have to be
+/// allocated with `malloc`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct libdecor_frame_base {
+    pub priv_: *mut libdecor_frame_private,
+    pub link: wl_list,
+}
+
+pub type libdecor_plugin_constructor =
+    unsafe extern "C" fn(context: *mut libdecor) -> *mut libdecor_plugin;
+
+/// The priority of a plugin on a desktop
+///
+/// A list of priorities is terminated by an entry without a desktop, which
+/// is used on all other desktops.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct libdecor_plugin_priority {
+    /// Matched against `XDG_CURRENT_DESKTOP`
+    pub desktop: *const c_char,
+    pub priority: c_int,
+}
+
+/// Exported by a plugin as `libdecor_plugin_description`
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct libdecor_plugin_description {
+    /// API version the plugin is compatible with
+    pub api_version: c_int,
+    /// Capabilities of the plugin, libdecor 0.2 only loads plugins with
+    /// [`LIBDECOR_PLUGIN_CAPABILITY_BASE`]
+    #[cfg(feature = "libdecor_0_2")]
+    pub capabilities: libdecor_plugin_capabilities,
+    /// Human readable string describing the plugin
+    pub description: *mut c_char,
+    pub priorities: *const libdecor_plugin_priority,
+    pub constructor: libdecor_plugin_constructor,
+    /// Symbols the plugin conflicts with, terminated by a null pointer
+    pub conflicting_symbols: [*mut c_char; LIBDECOR_PLUGIN_CONFLICTING_SYMBOLS_MAX],
+}
+
+/// Functions of a plugin called by libdecor
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct libdecor_plugin_interface {
+    pub destroy: unsafe extern "C" fn(plugin: *mut libdecor_plugin),
+    pub get_fd: unsafe extern "C" fn(plugin: *mut libdecor_plugin) -> c_int,
+    pub dispatch: unsafe extern "C" fn(plugin: *mut libdecor_plugin, timeout: c_int) -> c_int,
+    #[cfg(feature = "libdecor_0_2")]
+    pub set_handle_application_cursor:
+        unsafe extern "C" fn(plugin: *mut libdecor_plugin, handle_cursor: bool),
+    pub frame_new: unsafe extern "C" fn(plugin: *mut libdecor_plugin) -> *mut libdecor_frame,
+    pub frame_free: unsafe extern "C" fn(plugin: *mut libdecor_plugin, frame: *mut libdecor_frame),
+    pub frame_commit: unsafe extern "C" fn(
+        plugin: *mut libdecor_plugin,
+        frame: *mut libdecor_frame,
+        state: *mut libdecor_state,
+        configuration: *mut libdecor_configuration,
+    ),
+    pub frame_property_changed:
+        unsafe extern "C" fn(plugin: *mut libdecor_plugin, frame: *mut libdecor_frame),
+    pub frame_popup_grab: unsafe extern "C" fn(
+        plugin: *mut libdecor_plugin,
+        frame: *mut libdecor_frame,
+        seat_name: *const c_char,
+    ),
+    pub frame_popup_ungrab: unsafe extern "C" fn(
+        plugin: *mut libdecor_plugin,
+        frame: *mut libdecor_frame,
+        seat_name: *const c_char,
+    ),
+    pub frame_get_border_size: unsafe extern "C" fn(
+        plugin: *mut libdecor_plugin,
+        frame: *mut libdecor_frame,
+        configuration: *mut libdecor_configuration,
+        left: *mut c_int,
+        right: *mut c_int,
+        top: *mut c_int,
+        bottom: *mut c_int,
+    ) -> bool,
+    pub reserved0: libdecor_reserver_callback,
+    pub reserved1: libdecor_reserver_callback,
+    pub reserved2: libdecor_reserver_callback,
+    pub reserved3: libdecor_reserver_callback,
+    pub reserved4: libdecor_reserver_callback,
+    pub reserved5: libdecor_reserver_callback,
+    pub reserved6: libdecor_reserver_callback,
+    pub reserved7: libdecor_reserver_callback,
+    pub reserved8: libdecor_reserver_callback,
+    pub reserved9: libdecor_reserver_callback,
+}
+
+/// Declares the functions of libdecor used by plugins
+///
+/// Expands to the [`external_library!`] bindings, or to a function table
+/// backed by the [`mock`](crate::mock) implementation if the `mock` feature is
+/// enabled. The mocked variadic functions only take a single argument for
+/// the format.
+macro_rules! plugin_library {
+    (
+        functions: $(fn $fname: ident($($farg: ty),*) -> $fret: ty),+,
+        varargs: $(fn $vname: ident($($varg: ty),+) -> $vret: ty),+,
+    ) => {
+        #[cfg(all(feature = "plugin", not(feature = "mock")))]
+        external_library!(LibdecorPlugin, "decor-0",
+            functions: $(fn $fname($($farg),*) -> $fret,)+
+            varargs: $(fn $vname($($varg),+) -> $vret,)+
+        );
+
+        /// Function table of the mock implementation
+        #[cfg(all(feature = "plugin", feature = "mock"))]
+        pub struct LibdecorPlugin {
+            $(pub $fname: unsafe extern "C" fn($($farg),*) -> $fret,)+
+            $(pub $vname: unsafe extern "C" fn($($varg),+, *const c_char) -> $vret,)+
+        }
+
+        #[cfg(all(feature = "plugin", feature = "mock"))]
+        pub static LIBDECOR_PLUGIN_HANDLE: &LibdecorPlugin = &LibdecorPlugin {
+            $($fname: crate::mock::$fname,)+
+            $($vname: crate::mock::$vname,)+
+        };
+    };
+}
+
+plugin_library!(
+    functions:
+        fn libdecor_plugin_init(
+            *mut libdecor_plugin,
+            *mut libdecor,
+            *mut libdecor_plugin_interface
+        ) -> c_int,
+        fn libdecor_plugin_release(*mut libdecor_plugin) -> (),
+        fn libdecor_get_wl_display(*mut libdecor) -> *mut wl_display,
+        fn libdecor_notify_plugin_ready(*mut libdecor) -> (),
+        fn libdecor_frame_get_wl_surface(*mut libdecor_frame) -> *mut wl_surface,
+        fn libdecor_frame_get_content_width(*mut libdecor_frame) -> c_int,
+        fn libdecor_frame_get_content_height(*mut libdecor_frame) -> c_int,
+        fn libdecor_frame_get_window_state(*mut libdecor_frame) -> libdecor_window_state,
+        fn libdecor_frame_get_capabilities(*const libdecor_frame) -> libdecor_capabilities,
+        fn libdecor_frame_set_window_geometry(
+            *mut libdecor_frame,
+            i32,
+            i32,
+            i32,
+            i32
+        ) -> (),
+        fn libdecor_frame_dismiss_popup(*mut libdecor_frame, *const c_char) -> (),
+        fn libdecor_frame_toplevel_commit(*mut libdecor_frame) -> (),
+        fn libdecor_state_get_content_width(*mut libdecor_state) -> c_int,
+        fn libdecor_state_get_content_height(*mut libdecor_state) -> c_int,
+        fn libdecor_state_get_window_state(*mut libdecor_state) -> libdecor_window_state,
+    varargs:
+        fn libdecor_notify_plugin_error(*mut libdecor, libdecor_error, *const c_char) -> (),
+);
+
+#[cfg(all(feature = "plugin", feature = "dlopen", not(feature = "mock")))]
+lazy_static::lazy_static!(
+    /// The plugin functions of the library used by [`LIBDECOR_OPTION`]
+    ///
+    /// The library is opened again from the same path, which returns the
+    /// handle already loaded by the dynamic loader.
+    pub static ref LIBDECOR_PLUGIN_OPTION: Option<LibdecorPlugin> = {
+        let path = LIBDECOR_OPTION.as_ref()?.path().to_str()?;
+        unsafe { LibdecorPlugin::open(path) }.ok()
+    };
+    pub static ref LIBDECOR_PLUGIN_HANDLE: &'static LibdecorPlugin = {
+        LIBDECOR_PLUGIN_OPTION.as_ref().expect("Library decor-0 could not be loaded.")
+    };
+);
@@ -0,0 +1,178 @@
+//! Compile time comparison of the handwritten declarations with the
+//! bindings generated from `libdecor.h`, and `libdecor-plugin.h` with the
+//! `plugin` feature if the libdecor sources are available
+//!
+//! Only built with the `bindgen` feature. A constant or struct layout
+//! drifting from the header fails the build.
+
+use std::mem::{align_of, offset_of, size_of};
+
+#[cfg(all(feature = "plugin", libdecor_plugin_header))]
+use crate::plugin::*;
+use crate::*;
+
+mod generated {
+    #![allow(dead_code, non_camel_case_types, non_upper_case_globals)]
+    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
+
+macro_rules! assert_constants {
+    ($($name: ident),+ $(,)?) => {
+        $(
+            const _: () = assert!(
+                $name as u64 == generated::$name as u64,
+                concat!(stringify!($name), " differs from the header")
+            );
+        )+
+    };
+}
+
+macro_rules! assert_layout {
+    ($ty: ident { $($(#[$attr: meta])* $field: ident),+ $(,)? }) => {
+        assert_layout!($ty as $ty { $($(#[$attr])* $field),+ });
+    };
+    ($ty: ident as $c_ty: ident { $($(#[$attr: meta])* $field: ident),+ $(,)? }) => {
+        const _: () = assert!(
+            size_of::<$ty>() == size_of::<generated::$c_ty>()
+                && align_of::<$ty>() == align_of::<generated::$c_ty>(),
+            concat!("the layout of ", stringify!($c_ty), " differs from the header")
+        );
+        $(
+            $(#[$attr])*
+            const _: () = assert!(
+                offset_of!($ty, $field) == offset_of!(generated::$c_ty, $field),
+                concat!(
+                    "the offset of ",
+                    stringify!($c_ty),
+                    "::",
+                    stringify!($field),
+                    " differs from the header"
+                )
+            );
+        )+
+    };
+}
+
+assert_constants!(
+    LIBDECOR_ERROR_COMPOSITOR_INCOMPATIBLE,
+    LIBDECOR_ERROR_INVALID_FRAME_CONFIGURATION,
+    LIBDECOR_WINDOW_STATE_NONE,
+    LIBDECOR_WINDOW_STATE_ACTIVE,
+    LIBDECOR_WINDOW_STATE_MAXIMIZED,
+    LIBDECOR_WINDOW_STATE_FULLSCREEN,
+    LIBDECOR_WINDOW_STATE_TILED_LEFT,
+    LIBDECOR_WINDOW_STATE_TILED_RIGHT,
+    LIBDECOR_WINDOW_STATE_TILED_TOP,
+    LIBDECOR_WINDOW_STATE_TILED_BOTTOM,
+    LIBDECOR_RESIZE_EDGE_NONE,
+    LIBDECOR_RESIZE_EDGE_TOP,
+    LIBDECOR_RESIZE_EDGE_BOTTOM,
+    LIBDECOR_RESIZE_EDGE_LEFT,
+    LIBDECOR_RESIZE_EDGE_TOP_LEFT,
+    LIBDECOR_RESIZE_EDGE_BOTTOM_LEFT,
+    LIBDECOR_RESIZE_EDGE_RIGHT,
+    LIBDECOR_RESIZE_EDGE_TOP_RIGHT,
+    LIBDECOR_RESIZE_EDGE_BOTTOM_RIGHT,
+    LIBDECOR_ACTION_MOVE,
+    LIBDECOR_ACTION_RESIZE,
+    LIBDECOR_ACTION_MINIMIZE,
+    LIBDECOR_ACTION_FULLSCREEN,
+    LIBDECOR_ACTION_CLOSE,
+);
+
+#[cfg(feature = "libdecor_0_2")]
+assert_constants!(
+    LIBDECOR_WINDOW_STATE_SUSPENDED,
+    LIBDECOR_WM_CAPABILITIES_WINDOW_MENU,
+    LIBDECOR_WM_CAPABILITIES_MAXIMIZE,
+    LIBDECOR_WM_CAPABILITIES_FULLSCREEN,
+    LIBDECOR_WM_CAPABILITIES_MINIMIZE,
+);
+
+assert_layout!(libdecor_interface {
+    error,
+    reserved0,
+    reserved1,
+    reserved2,
+    reserved3,
+    reserved4,
+    reserved5,
+    reserved6,
+    reserved7,
+    reserved8,
+    reserved9,
+});
+
+assert_layout!(libdecor_frame_interface {
+    configure,
+    close,
+    commit,
+    dismiss_popup,
+    reserved0,
+    reserved1,
+    reserved2,
+    reserved3,
+    reserved4,
+    reserved5,
+    reserved6,
+    reserved7,
+    reserved8,
+    reserved9,
+});
+
+#[cfg(all(feature = "plugin", libdecor_plugin_header))]
+assert_constants!(
+    LIBDECOR_PLUGIN_API_VERSION,
+    LIBDECOR_PLUGIN_PRIORITY_HIGH,
+    LIBDECOR_PLUGIN_PRIORITY_MEDIUM,
+    LIBDECOR_PLUGIN_PRIORITY_LOW,
+);
+
+#[cfg(all(feature = "plugin", feature = "libdecor_0_2", libdecor_plugin_header))]
+assert_constants!(LIBDECOR_PLUGIN_CAPABILITY_BASE);
+
+#[cfg(all(feature = "plugin", libdecor_plugin_header))]
+assert_layout!(libdecor_plugin { priv_ });
+
+#[cfg(all(feature = "plugin", libdecor_plugin_header))]
+assert_layout!(libdecor_frame_base as libdecor_frame { priv_, link });
+
+#[cfg(all(feature = "plugin", libdecor_plugin_header))]
+assert_layout!(libdecor_plugin_priority { desktop, priority });
+
+#[cfg(all(feature = "plugin", libdecor_plugin_header))]
+assert_layout!(libdecor_plugin_description {
+    api_version,
+    #[cfg(feature = "libdecor_0_2")]
+    capabilities,
+    description,
+    priorities,
+    constructor,
+    conflicting_symbols,
+});
+
+#[cfg(all(feature = "plugin", libdecor_plugin_header))]
+assert_layout!(libdecor_plugin_interface {
+    destroy,
+    get_fd,
+    dispatch,
+    #[cfg(feature = "libdecor_0_2")]
+    set_handle_application_cursor,
+    frame_new,
+    frame_free,
+    frame_commit,
+    frame_property_changed,
+    frame_popup_grab,
+    frame_popup_ungrab,
+    frame_get_border_size,
+    reserved0,
+    reserved1,
+    reserved2,
+    reserved3,
+    reserved4,
+    reserved5,
+    reserved6,
+    reserved7,
+    reserved8,
+    reserved9,
+});
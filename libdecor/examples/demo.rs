@@ -268,48 +268,45 @@ fn main() {
         context
             .decorate(demo_state.window.content_surface.detach(), {
                 let shm = shm.clone();
-                move |frame, request, mut ddata| {
-                    let mut demo_state = ddata.get::<DemoState>().unwrap();
-                    match request {
-                        libdecor::FrameRequest::Configure(configuration) => {
-                            let size = configuration
-                                .content_size(frame)
-                                .unwrap_or(demo_state.window.floating_size);
-                            demo_state.window.configured_size = size;
-
-                            if let Some(state) = configuration.window_state() {
-                                demo_state.window.window_state = Some(state);
-                            }
-
-                            let state = libdecor::State::new(size.0, size.1);
-                            frame.commit(&state, Some(configuration));
+                move |frame, request, demo_state: &mut DemoState| match request {
+                    libdecor::FrameRequest::Configure(configuration) => {
+                        let size = configuration
+                            .content_size(frame)
+                            .unwrap_or(demo_state.window.floating_size);
+                        demo_state.window.configured_size = size;
+
+                        if let Some(state) = configuration.window_state() {
+                            demo_state.window.window_state = Some(state);
+                        }
 
-                            if frame.is_floating() {
-                                demo_state.window.floating_size = size;
-                            }
+                        let state = libdecor::State::new(size.0, size.1);
+                        frame.commit(&state, Some(configuration));
 
-                            redraw(
-                                &shm,
-                                &demo_state.window.content_surface,
-                                size.0,
-                                size.1,
-                                demo_state.window.window_state,
-                            );
-                        }
-                        libdecor::FrameRequest::Close => {
-                            demo_state.exit.store(true, Ordering::SeqCst);
+                        if frame.is_floating() {
+                            demo_state.window.floating_size = size;
                         }
-                        libdecor::FrameRequest::Commit => {
-                            demo_state.window.content_surface.commit();
-                        }
-                        libdecor::FrameRequest::DismissPopup { .. } => {
-                            if let Some(popup) = demo_state.popup.take() {
-                                frame.popup_ungrab(demo_state.seat_name.as_ref().unwrap());
-                                popup.destroy();
-                            }
+
+                        redraw(
+                            &shm,
+                            &demo_state.window.content_surface,
+                            size.0,
+                            size.1,
+                            demo_state.window.window_state,
+                        );
+                    }
+                    libdecor::FrameRequest::Close => {
+                        demo_state.exit.store(true, Ordering::SeqCst);
+                    }
+                    libdecor::FrameRequest::Commit => {
+                        demo_state.window.content_surface.commit();
+                    }
+                    libdecor::FrameRequest::DismissPopup { .. } => {
+                        if let Some(popup) = demo_state.popup.take() {
+                            frame.popup_ungrab(demo_state.seat_name.as_ref().unwrap());
+                            popup.destroy();
                         }
-                        _ => unreachable!(),
                     }
+                    _ => unreachable!(),
                 }
             })
             .expect("Failed to create frame"),
@@ -727,7 +724,10 @@ fn main() {
 
     frame.dispatch(&mut demo_state, |f| f.map());
 
-    while context.dispatch(&mut demo_state, Some(Duration::from_millis(16))) {
+    while context
+        .dispatch(&mut demo_state, Some(Duration::from_millis(16)))
+        .is_ok()
+    {
         if demo_state.exit.load(Ordering::SeqCst) {
             break;
         }
@@ -0,0 +1,306 @@
+//! Integration with async runtimes
+
+use std::{
+    future::Future,
+    io,
+    os::unix::prelude::{AsRawFd, RawFd},
+    pin::Pin,
+    rc::Rc,
+    task::{Context as TaskContext, Poll},
+    time::Duration,
+};
+
+use futures_core::Stream;
+use wayland_client::{protocol::wl_surface::WlSurface, Display};
+
+use crate::{
+    queue::{EventQueue, QueueHandler, SharedEventQueue},
+    Context, DispatchError, Frame, FrameEvent, Request,
+};
+
+#[derive(Debug)]
+struct ContextFd(RawFd);
+
+impl AsRawFd for ContextFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+#[derive(Debug)]
+enum Registration {
+    #[cfg(feature = "tokio")]
+    Tokio(tokio::io::unix::AsyncFd<ContextFd>),
+    #[cfg(feature = "async-io")]
+    AsyncIo(async_io::Async<ContextFd>),
+}
+
+#[derive(Debug)]
+struct Inner {
+    context: Context,
+    registration: Registration,
+}
+
+impl Inner {
+    /// Wait for the file descriptor to become readable and dispatch it.
+    ///
+    /// Outgoing requests are flushed and already queued events dispatched
+    /// first, the reply to a request might never arrive otherwise.
+    ///
+    /// Resolves to the number of dispatched events.
+    fn poll_dispatch(&self, cx: &mut TaskContext<'_>) -> Poll<Result<usize, DispatchError>> {
+        let count = self.dispatch()?;
+        if count > 0 {
+            return Poll::Ready(Ok(count));
+        }
+
+        match &self.registration {
+            #[cfg(feature = "tokio")]
+            Registration::Tokio(fd) => loop {
+                let mut guard = match fd.poll_read_ready(cx) {
+                    Poll::Ready(Ok(guard)) => guard,
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(DispatchError::Io(err))),
+                    Poll::Pending => return Poll::Pending,
+                };
+
+                let count = self.dispatch()?;
+                if count > 0 {
+                    return Poll::Ready(Ok(count));
+                }
+
+                // Nothing was dispatched, clear the readiness and wait
+                // for the next wakeup
+                guard.clear_ready();
+            },
+            #[cfg(feature = "async-io")]
+            Registration::AsyncIo(fd) => loop {
+                match fd.poll_readable(cx) {
+                    Poll::Ready(Ok(())) => {}
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(DispatchError::Io(err))),
+                    Poll::Pending => return Poll::Pending,
+                };
+
+                let count = self.dispatch()?;
+                if count > 0 {
+                    return Poll::Ready(Ok(count));
+                }
+            },
+        }
+    }
+
+    fn dispatch(&self) -> Result<usize, DispatchError> {
+        self.context.dispatch(&mut (), Some(Duration::ZERO))
+    }
+}
+
+/// A [`Context`] driven by an async runtime.
+///
+/// The file descriptor of the context is registered with the reactor of
+/// the selected runtime. Events are dispatched by awaiting
+/// [`dispatch_ready`](#method.dispatch_ready) or by polling the
+/// [`FrameEvents`] stream of a frame.
+///
+/// The context is not [`Send`] and has to be used from a local task,
+/// e.g. a `tokio::task::LocalSet`.
+///
+/// ```no_run
+/// # async fn run() {
+/// # use libdecor::{AsyncContext, FrameEvent};
+/// # use futures_util::StreamExt;
+/// # use wayland_client::{protocol::wl_compositor, Display, GlobalManager};
+/// #
+/// # let display = Display::connect_to_env().unwrap();
+/// # let mut event_queue = display.create_event_queue();
+/// # let attached_display = (*display).clone().attach(event_queue.token());
+/// # let globals = GlobalManager::new(&attached_display);
+/// # event_queue
+/// #     .sync_roundtrip(&mut (), |_, _, _| unreachable!())
+/// #     .unwrap();
+/// # let compositor = globals
+/// #     .instantiate_exact::<wl_compositor::WlCompositor>(4)
+/// #     .unwrap();
+/// # let content_surface = compositor.create_surface();
+/// # #[cfg(feature = "tokio")]
+/// let context = AsyncContext::new_tokio(display, |_| {}).unwrap();
+/// # #[cfg(not(feature = "tokio"))]
+/// # let context = AsyncContext::new_async_io(display, |_| {}).unwrap();
+/// let (frame, mut events) = context
+///     .decorate(content_surface.detach(), (800, 600))
+///     .unwrap();
+///
+/// frame.dispatch(&mut (), |f| f.map());
+///
+/// while let Some(event) = events.next().await {
+///     match event {
+///         FrameEvent::Configure { content_size, .. } => {
+///             // Draw surface content
+///         }
+///         FrameEvent::Close => break,
+///         _ => {}
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncContext {
+    inner: Rc<Inner>,
+}
+
+impl AsyncContext {
+    /// Create a new libdecor context for the given [`Display`] registered
+    /// with the current [`tokio`] reactor.
+    ///
+    /// Must be called from within a tokio runtime.
+    #[cfg(feature = "tokio")]
+    pub fn new_tokio<C>(display: Display, cb: C) -> io::Result<Self>
+    where
+        C: FnMut(Request) + 'static,
+    {
+        let context = Context::try_new(display, cb).map_err(io::Error::other)?;
+        let fd = tokio::io::unix::AsyncFd::with_interest(
+            ContextFd(context.fd()),
+            tokio::io::Interest::READABLE,
+        )?;
+
+        Ok(Self::from_parts(context, Registration::Tokio(fd)))
+    }
+
+    /// Create a new libdecor context for the given [`Display`] registered
+    /// with the [`async_io`] reactor, as used by e.g. `smol`.
+    #[cfg(feature = "async-io")]
+    pub fn new_async_io<C>(display: Display, cb: C) -> io::Result<Self>
+    where
+        C: FnMut(Request) + 'static,
+    {
+        let context = Context::try_new(display, cb).map_err(io::Error::other)?;
+        let fd = async_io::Async::new(ContextFd(context.fd()))?;
+
+        Ok(Self::from_parts(context, Registration::AsyncIo(fd)))
+    }
+
+    fn from_parts(context: Context, registration: Registration) -> Self {
+        Self {
+            inner: Rc::new(Inner {
+                context,
+                registration,
+            }),
+        }
+    }
+
+    /// Access the wrapped [`Context`]
+    pub fn context(&self) -> &Context {
+        &self.inner.context
+    }
+
+    /// Decorate the given content [`WlSurface`].
+    ///
+    /// See [`Context::decorate`] for details. Events for the created
+    /// [`Frame`] are delivered through the returned [`FrameEvents`] stream.
+    ///
+    /// Configurations are committed right away, using the configured content
+    /// size or the last committed size if the configuration does not contain one.
+    /// `initial_size` is used until the first size has been committed.
+    ///
+    /// The commit happens inside of the libdecor callback, before the event
+    /// is queued, as the configuration is only valid during the callback.
+    /// The application can neither pick another size nor delay the commit
+    /// until its content is ready, the size reported by
+    /// [`FrameEvent::Configure`](crate::FrameEvent::Configure) has already
+    /// been committed. Use [`Context::decorate_with_handler`] to commit
+    /// configurations with a different policy.
+    pub fn decorate(
+        &self,
+        surface: WlSurface,
+        initial_size: (i32, i32),
+    ) -> Option<(Frame, FrameEvents)> {
+        let queue = EventQueue::new(initial_size);
+        let frame = self
+            .inner
+            .context
+            .decorate_with_handler(surface, QueueHandler::new(queue.clone()))?;
+
+        Some((
+            frame,
+            FrameEvents {
+                context: self.inner.clone(),
+                queue,
+                error: None,
+                failed: false,
+            },
+        ))
+    }
+
+    /// Wait until the file descriptor of the context becomes readable and
+    /// dispatch the pending events.
+    ///
+    /// Returns the number of dispatched events.
+    pub async fn dispatch_ready(&self) -> io::Result<usize> {
+        DispatchReady(&self.inner).await
+    }
+}
+
+struct DispatchReady<'a>(&'a Inner);
+
+impl<'a> Future for DispatchReady<'a> {
+    type Output = io::Result<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        self.0.poll_dispatch(cx).map_err(io::Error::from)
+    }
+}
+
+/// A [`Stream`] of [`FrameEvent`]s for a single [`Frame`].
+///
+/// Polling the stream also dispatches the [`AsyncContext`] it was created
+/// from, so it is sufficient to only await the stream. The stream
+/// ends when the [`Frame`] has been dropped or dispatching the context
+/// failed, see [`take_error`](#method.take_error).
+#[derive(Debug)]
+pub struct FrameEvents {
+    context: Rc<Inner>,
+    queue: SharedEventQueue,
+    error: Option<DispatchError>,
+    failed: bool,
+}
+
+impl FrameEvents {
+    /// Take the error which ended the stream.
+    ///
+    /// Returns the [`DispatchError`] if dispatching the context failed, e.g.
+    /// because the connection to the compositor was lost, and [`None`] if
+    /// the stream has not ended or the [`Frame`] has been dropped.
+    pub fn take_error(&mut self) -> Option<DispatchError> {
+        self.error.take()
+    }
+}
+
+impl Stream for FrameEvents {
+    type Item = FrameEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            {
+                let mut queue = this.queue.borrow_mut();
+                if let Some(event) = queue.events.pop_front() {
+                    return Poll::Ready(Some(event));
+                }
+                if queue.closed || this.failed {
+                    return Poll::Ready(None);
+                }
+                queue.waker = Some(cx.waker().clone());
+            }
+
+            match this.context.poll_dispatch(cx) {
+                Poll::Ready(Ok(_)) => continue,
+                // The context is no longer usable
+                Poll::Ready(Err(err)) => {
+                    this.error = Some(err);
+                    this.failed = true;
+                    return Poll::Ready(None);
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
@@ -1,23 +1,34 @@
 use std::{
-    any::Any,
     cell::RefCell,
+    collections::{BTreeMap, VecDeque},
     ffi::CStr,
+    io,
     os::{
         raw::{c_char, c_int},
         unix::prelude::RawFd,
     },
+    path::PathBuf,
     rc::Rc,
     time::Duration,
 };
-use wayland_client::{protocol::wl_surface::WlSurface, DispatchData, Display};
+use wayland_client::{protocol::wl_surface::WlSurface, Display, ProtocolError};
 
-use crate::{frame::LIBDECOR_FRAME_INTERFACE, FrameCallback, FrameRef};
+use crate::{
+    frame::{FrameDispatch, FrameState, LIBDECOR_FRAME_INTERFACE},
+    queue::{EventQueue, QueueHandler},
+    selection::PluginSelection,
+    ActivePlugin, CallbackHandler, CallbackState, DecorationMode, DispatchSlot, FrameEventReceiver,
+    FrameHandler, FrameId, FrameRef, Unsupported,
+};
 use libdecor_sys::*;
 
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
+use crate::fallback::LIBDECOR_HANDLE;
+
 use crate::{Frame, FrameRequest};
 
 /// Kind of error in a [`Context`]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ErrorKind {
     /// The compositor is incompatible with [`libdecor`]
     ///
@@ -27,13 +38,20 @@ pub enum ErrorKind {
     ///
     /// E.g.: The min_size is greater as the max_size
     InvalidFrameConfiguration,
+    /// The context could not be created
+    ///
+    /// Returned by [`Context::try_new`]
+    ContextCreationFailed,
+    /// An error unknown to this crate, reported by a newer libdecor
+    Unknown(u32),
 }
 
 /// An error that has occurred in a [`Context`]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Error {
     message: String,
     kind: ErrorKind,
+    frame: Option<FrameId>,
 }
 
 impl Error {
@@ -41,6 +59,14 @@ impl Error {
     pub fn kind(&self) -> ErrorKind {
         self.kind
     }
+
+    /// Returns the identifier of the frame that caused this error.
+    ///
+    /// Errors are attributed to a frame if they are reported while the frame
+    /// is in use, i.e. inside of one of its callbacks or [`Frame::dispatch`].
+    pub fn frame(&self) -> Option<FrameId> {
+        self.frame
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -56,7 +82,7 @@ impl From<libdecor_error> for ErrorKind {
         match error {
             LIBDECOR_ERROR_COMPOSITOR_INCOMPATIBLE => Self::CompositorIncompatible,
             LIBDECOR_ERROR_INVALID_FRAME_CONFIGURATION => Self::InvalidFrameConfiguration,
-            _ => unreachable!(),
+            error => Self::Unknown(error),
         }
     }
 }
@@ -68,19 +94,29 @@ extern "C" fn error_callback_trampolin(
 ) {
     let message = unsafe { CStr::from_ptr(message) };
 
+    let callback = match ContextCallback::lookup(context) {
+        Some(callback) => callback,
+        None => return,
+    };
+
+    let state = &callback.state;
+    let frame = state.current_frame();
     let error = Error {
         message: message.to_string_lossy().to_string(),
         kind: ErrorKind::from(error),
+        frame: frame.map(|(frame, _)| FrameRef(frame).id()),
     };
 
-    LIBDECOR_CALLBACK_REGISTRATIONS.with(|c| {
-        let mut registrations = c.borrow_mut();
-        let registration = registrations.iter_mut().find(|r| r.context == context);
+    callback.deliver(Request::Error(error.clone()));
 
-        if let Some(registration) = registration {
-            (*registration.cb)(Request::Error(error));
-        }
-    });
+    // Also surface the error through the handler of the frame in use
+    if let Some((frame, dispatch)) = frame {
+        // SAFETY: The dispatch of a frame is valid as long as it is in use
+        let dispatch = unsafe { &*dispatch };
+        state
+            .panic
+            .catch(|| dispatch.error(&FrameRef(frame), &error));
+    }
 }
 
 static LIBDECOR_INTERFACE: libdecor_interface = libdecor_interface {
@@ -97,13 +133,79 @@ static LIBDECOR_INTERFACE: libdecor_interface = libdecor_interface {
     reserved9: None,
 };
 
-struct ContextCallbackRegistration {
-    context: *mut libdecor,
-    cb: Box<dyn FnMut(Request)>,
+/// The callback of a context, owned by its [`InnerContext`]
+///
+/// The trampoline keeps its own reference while the callback runs, so the
+/// callback may drop the last handle of its context.
+struct ContextCallback {
+    cb: RefCell<Box<dyn FnMut(Request)>>,
+    /// Requests raised while the callback is running
+    pending: RefCell<VecDeque<Request>>,
+    state: Rc<CallbackState>,
 }
 
 thread_local! {
-    static LIBDECOR_CALLBACK_REGISTRATIONS: RefCell<Vec<ContextCallbackRegistration>> = RefCell::new(Vec::new());
+    /// The [`ContextCallback`]s by the address of their libdecor context
+    ///
+    /// A context can not be sent to another thread, so its callback is only
+    /// ever registered and looked up on the thread owning it.
+    static CONTEXT_CALLBACKS: RefCell<BTreeMap<usize, *const ContextCallback>> =
+        const { RefCell::new(BTreeMap::new()) };
+}
+
+impl ContextCallback {
+    /// Make the callback reachable from the libdecor context.
+    ///
+    /// The context has to keep a reference to the callback until unregistered.
+    fn register(self: &Rc<Self>, context: *mut libdecor) {
+        CONTEXT_CALLBACKS.with(|callbacks| {
+            callbacks
+                .borrow_mut()
+                .insert(context as usize, Rc::as_ptr(self))
+        });
+    }
+
+    fn unregister(context: *mut libdecor) {
+        // The map is already gone if the context outlives the thread locals
+        let _ = CONTEXT_CALLBACKS.try_with(|callbacks| {
+            callbacks.borrow_mut().remove(&(context as usize));
+        });
+    }
+
+    /// Find the callback of a libdecor context.
+    fn lookup(context: *mut libdecor) -> Option<Rc<Self>> {
+        let callback = CONTEXT_CALLBACKS
+            .try_with(|callbacks| callbacks.borrow().get(&(context as usize)).copied())
+            .ok()
+            .flatten()?;
+        // SAFETY: The callback is unregistered before the reference of its
+        // context is released
+        unsafe {
+            Rc::increment_strong_count(callback);
+            Some(Rc::from_raw(callback))
+        }
+    }
+
+    /// Invoke the callback with `request`.
+    ///
+    /// Requests raised re-entrant from the running callback, e.g. by
+    /// committing an invalid frame configuration, are delivered after it
+    /// has returned.
+    fn deliver(&self, request: Request) {
+        let mut cb = match self.cb.try_borrow_mut() {
+            Ok(cb) => cb,
+            Err(_) => {
+                self.pending.borrow_mut().push_back(request);
+                return;
+            }
+        };
+
+        let mut next = Some(request);
+        while let Some(request) = next {
+            self.state.panic.catch(|| (*cb)(request));
+            next = self.pending.borrow_mut().pop_front();
+        }
+    }
 }
 
 /// Possible variants for the [`Context`] callback
@@ -114,14 +216,125 @@ pub enum Request {
     Error(Error),
 }
 
+/// Error returned by [`Context::dispatch`]
 #[derive(Debug)]
-struct InnerContext(*mut libdecor);
+#[non_exhaustive]
+pub enum DispatchError {
+    /// The connection to the compositor has been lost
+    Disconnected(io::Error),
+    /// The compositor raised a protocol error and closed the connection
+    Protocol(ProtocolError),
+    /// Any other I/O error
+    Io(io::Error),
+}
+
+impl DispatchError {
+    /// Returns the OS error code (errno) reported by the failed dispatch.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match self {
+            DispatchError::Disconnected(err) | DispatchError::Io(err) => err.raw_os_error(),
+            DispatchError::Protocol(_) => None,
+        }
+    }
+
+    fn last_error(display: &Display) -> Self {
+        // Taken before anything else might overwrite errno
+        let err = io::Error::last_os_error();
+
+        if let Some(protocol_error) = display.protocol_error() {
+            return DispatchError::Protocol(protocol_error);
+        }
+
+        match err.kind() {
+            io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset => {
+                DispatchError::Disconnected(err)
+            }
+            _ => DispatchError::Io(err),
+        }
+    }
+}
+
+impl std::fmt::Display for DispatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DispatchError::Disconnected(err) => {
+                write!(f, "connection to the compositor lost: {}", err)
+            }
+            DispatchError::Protocol(err) => err.fmt(f),
+            DispatchError::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for DispatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DispatchError::Disconnected(err) | DispatchError::Io(err) => Some(err),
+            DispatchError::Protocol(err) => Some(err),
+        }
+    }
+}
+
+impl From<DispatchError> for io::Error {
+    fn from(err: DispatchError) -> Self {
+        match err {
+            DispatchError::Disconnected(err) | DispatchError::Io(err) => err,
+            err @ DispatchError::Protocol(_) => io::Error::other(err),
+        }
+    }
+}
+
+struct InnerContext {
+    ptr: *mut libdecor,
+    display: Display,
+    plugin: ActivePlugin,
+    _callback: Rc<ContextCallback>,
+}
+
+impl std::fmt::Debug for InnerContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InnerContext")
+            .field("ptr", &self.ptr)
+            .field("display", &self.display)
+            .field("plugin", &self.plugin)
+            .finish_non_exhaustive()
+    }
+}
 
 impl InnerContext {
-    fn new<C>(display: Display, cb: C) -> Self
+    fn new<C>(
+        display: Display,
+        cb: C,
+        state: Rc<CallbackState>,
+        builder: &ContextBuilder,
+    ) -> Result<Self, Error>
     where
         C: FnMut(Request) + 'static,
     {
+        #[cfg(all(feature = "dlopen", not(feature = "mock")))]
+        if let Some(library) = builder.library {
+            // Selecting the same library again is fine
+            if set_library(library).is_err()
+                && !LIBDECOR_OPTION.is_some_and(|current| std::ptr::eq(current, library))
+            {
+                return Err(Error {
+                    message: "another libdecor library has already been loaded".to_owned(),
+                    kind: ErrorKind::ContextCreationFailed,
+                    frame: None,
+                });
+            }
+        }
+
+        let selection = PluginSelection::apply(
+            builder.plugin_dir.as_deref(),
+            builder.preferred_plugin.as_deref(),
+        )
+        .map_err(|err| Error {
+            message: format!("failed to select the libdecor plugin: {}", err),
+            kind: ErrorKind::ContextCreationFailed,
+            frame: None,
+        })?;
+
         let context = unsafe {
             ffi_dispatch!(
                 LIBDECOR_HANDLE,
@@ -130,49 +343,205 @@ impl InnerContext {
                 &LIBDECOR_INTERFACE as *const _ as *mut _
             )
         };
+        // The plugins are loaded while creating the context
+        drop(selection);
 
-        let callback_registration = ContextCallbackRegistration {
-            context,
-            cb: Box::new(cb),
-        };
+        if context.is_null() {
+            return Err(Error {
+                message: "failed to create the libdecor context".to_owned(),
+                kind: ErrorKind::ContextCreationFailed,
+                frame: None,
+            });
+        }
 
-        LIBDECOR_CALLBACK_REGISTRATIONS.with(|c| {
-            let mut registrations = c.borrow_mut();
-            registrations.push(callback_registration);
+        let callback = Rc::new(ContextCallback {
+            cb: RefCell::new(Box::new(cb)),
+            pending: RefCell::new(VecDeque::new()),
+            state,
         });
+        callback.register(context);
 
-        Self(context)
+        Ok(Self {
+            ptr: context,
+            display,
+            plugin: ActivePlugin::detect(),
+            _callback: callback,
+        })
     }
 }
 
 impl Drop for InnerContext {
     fn drop(&mut self) {
-        unsafe { ffi_dispatch!(LIBDECOR_HANDLE, libdecor_unref, self.0) }
+        // Unregister first, the address may be reused by another thread
+        // once the context has been freed
+        ContextCallback::unregister(self.ptr);
+        unsafe { ffi_dispatch!(LIBDECOR_HANDLE, libdecor_unref, self.ptr) }
+    }
+}
 
-        LIBDECOR_CALLBACK_REGISTRATIONS.with(|c| {
-            let mut registrations = c.borrow_mut();
-            registrations.retain(|r| r.context != self.0);
-        });
+/// Builder for a [`Context`] with a custom plugin selection
+///
+/// libdecor only reads the plugin directory from the `LIBDECOR_PLUGIN_DIR`
+/// environment variable. The variable is overridden while the context is
+/// created, which races with other threads accessing the environment.
+///
+/// ```no_run
+/// # use libdecor::{Context, ContextBuilder};
+/// # use wayland_client::Display;
+/// #
+/// # let display = Display::connect_to_env().unwrap();
+/// let context: Context = ContextBuilder::new()
+///     .preferred_plugin("libdecor-cairo")
+///     .build(display, |_| {})
+///     .unwrap();
+/// println!("decorations: {}", context.active_plugin());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ContextBuilder {
+    plugin_dir: Option<PathBuf>,
+    preferred_plugin: Option<String>,
+    #[cfg(all(feature = "dlopen", not(feature = "mock")))]
+    library: Option<&'static Libdecor>,
+}
+
+impl ContextBuilder {
+    /// Create a builder using the default plugin selection of libdecor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the plugins from the given directory instead of the plugin
+    /// directory of libdecor.
+    ///
+    /// # Threads
+    ///
+    /// `LIBDECOR_PLUGIN_DIR` is set while [`build`](#method.build) runs,
+    /// which is not synchronized with other threads reading the environment,
+    /// e.g. through `getenv` in C libraries. Build the context before
+    /// spawning other threads, or while no other thread accesses the
+    /// environment.
+    pub fn plugin_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.plugin_dir = Some(dir.into());
+        self
+    }
+
+    /// Use the plugin with the given name, e.g. `libdecor-cairo`, if it is
+    /// installed in the plugin directory.
+    ///
+    /// No other plugin is considered if the plugin is available. If it
+    /// fails to decorate on the compositor, libdecor falls back to its
+    /// built-in decorations, see [`ActivePlugin::Builtin`].
+    ///
+    /// # Threads
+    ///
+    /// The plugin is selected through `LIBDECOR_PLUGIN_DIR`, the same
+    /// restrictions as for [`plugin_dir`](#method.plugin_dir) apply.
+    pub fn preferred_plugin<S: Into<String>>(mut self, name: S) -> Self {
+        self.preferred_plugin = Some(name.into());
+        self
+    }
+
+    /// Use an explicitly loaded libdecor, e.g. a copy bundled with the
+    /// application, instead of looking up `libdecor-0.so`.
+    ///
+    /// The library is used for the whole process, building fails if
+    /// another library has already been loaded, see
+    /// [`ffi::set_library`](libdecor_sys::set_library). Requires the
+    /// `dlopen` feature.
+    ///
+    /// ```no_run
+    /// # use libdecor::{ffi, Context, ContextBuilder};
+    /// # use wayland_client::Display;
+    /// #
+    /// # let display = Display::connect_to_env().unwrap();
+    /// let library = unsafe { ffi::load_from_candidates(&["./lib/libdecor-0.so", "libdecor-0.so"]) }
+    ///     .expect("failed to load libdecor");
+    /// let context: Context = ContextBuilder::new()
+    ///     .library(Box::leak(Box::new(library)))
+    ///     .build(display, |_| {})
+    ///     .unwrap();
+    /// ```
+    #[cfg(all(feature = "dlopen", not(feature = "mock")))]
+    pub fn library(mut self, library: &'static Libdecor) -> Self {
+        self.library = Some(library);
+        self
+    }
+
+    /// Create the [`Context`] for the given [`Display`].
+    ///
+    /// See [`Context::try_new`].
+    pub fn build<D: 'static, C>(&self, display: Display, cb: C) -> Result<Context<D>, Error>
+    where
+        C: FnMut(Request) + 'static,
+    {
+        let state = Rc::new(CallbackState::default());
+        Ok(Context {
+            inner: Rc::new(InnerContext::new(display, cb, state.clone(), self)?),
+            slot: Rc::new(DispatchSlot::new(state)),
+        })
     }
 }
 
 /// A libdecor context instance.
-#[derive(Debug, Clone)]
-pub struct Context {
+///
+/// The type parameter `D` is the application data which is passed to the
+/// callbacks of all frames created by this context. It has to be provided
+/// when dispatching the context with [`dispatch`](#method.dispatch) or a frame
+/// with [`Frame::dispatch`].
+pub struct Context<D = ()> {
     inner: Rc<InnerContext>,
+    slot: Rc<DispatchSlot<D>>,
+}
+
+impl<D> Clone for Context<D> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+impl<D> std::fmt::Debug for Context<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Context")
+            .field("inner", &self.inner)
+            .finish()
+    }
 }
 
-impl Context {
+impl<D: 'static> Context<D> {
     /// Create a new libdecor context for the given [`Display`].
+    ///
+    /// The callback may create, dispatch and drop contexts, e.g. to
+    /// reconnect after [`ErrorKind::CompositorIncompatible`]. Errors raised
+    /// while the callback is running are delivered after it has returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context could not be created, see [`try_new`](#method.try_new)
+    /// for a fallible alternative.
     pub fn new<C>(display: Display, cb: C) -> Self
     where
         C: FnMut(Request) + 'static,
     {
-        Self {
-            inner: Rc::new(InnerContext::new(display, cb)),
+        match Self::try_new(display, cb) {
+            Ok(context) => context,
+            Err(err) => panic!("{}", err),
         }
     }
 
+    /// Try to create a new libdecor context for the given [`Display`].
+    ///
+    /// Returns an [`Error`] of kind [`ErrorKind::ContextCreationFailed`]
+    /// if libdecor failed to create the context.
+    pub fn try_new<C>(display: Display, cb: C) -> Result<Self, Error>
+    where
+        C: FnMut(Request) + 'static,
+    {
+        ContextBuilder::new().build(display, cb)
+    }
+
     /// Decorate the given content [`WlSurface`].
     ///
     /// This will create an [`wayland_protocols::xdg_shell::client::xdg_surface::XdgSurface`]
@@ -183,60 +552,152 @@ impl Context {
     ///
     /// The passed [`WlSurface`] should only contain actual application content,
     /// without any window decoration.
-    pub fn decorate<C>(&self, surface: WlSurface, cb: C) -> Option<Frame>
+    pub fn decorate<C>(&self, surface: WlSurface, cb: C) -> Option<Frame<D>>
     where
-        C: FnMut(&FrameRef, &FrameRequest, DispatchData) + 'static,
+        C: FnMut(&FrameRef, &FrameRequest<'_>, &mut D) + 'static,
     {
-        let cb: Box<Box<FrameCallback>> = Box::new(Box::new(cb));
-        let cb = Box::into_raw(cb);
+        self.decorate_with_handler(surface, CallbackHandler(cb))
+    }
+
+    /// Decorate the given content [`WlSurface`] using a [`FrameHandler`].
+    ///
+    /// See [`decorate`](#method.decorate) for details.
+    pub fn decorate_with_handler<H>(&self, surface: WlSurface, handler: H) -> Option<Frame<D>>
+    where
+        H: FrameHandler<D> + 'static,
+    {
+        let state = FrameState {
+            handler: Rc::new(RefCell::new(handler)),
+            slot: self.slot.clone(),
+            #[cfg(feature = "libdecor_0_2")]
+            wm_capabilities: std::cell::Cell::new(None),
+        };
+        let dispatch: Box<Box<dyn FrameDispatch>> = Box::new(Box::new(state));
+        let dispatch = Box::into_raw(dispatch);
 
         let frame = unsafe {
             ffi_dispatch!(
                 LIBDECOR_HANDLE,
                 libdecor_decorate,
-                self.inner.0,
+                self.inner.ptr,
                 surface.as_ref().c_ptr() as *mut _,
                 &LIBDECOR_FRAME_INTERFACE as *const _ as *mut _,
-                cb as *mut _
+                dispatch as *mut _
             )
         };
 
         if frame.is_null() {
+            let _ = unsafe { Box::from_raw(dispatch) };
             None
         } else {
-            Some(Frame {
-                frame_ref: FrameRef(frame),
-                cb,
-                _context: self.clone(),
-            })
+            Some(Frame::new(frame, dispatch, self.clone()))
         }
     }
 
+    /// Decorate the given content [`WlSurface`] and queue the frame events.
+    ///
+    /// Instead of invoking a callback, the events of the created [`Frame`] are
+    /// buffered as owned [`FrameEvent`](crate::FrameEvent)s and can be drained
+    /// from the returned [`FrameEventReceiver`] at any time after dispatching
+    /// the context.
+    ///
+    /// Configurations are committed right away, using the configured content
+    /// size or the last committed size if the configuration does not contain one.
+    /// `initial_size` is used until the first size has been committed.
+    ///
+    /// The commit happens inside of the libdecor callback, before the event
+    /// is queued, as the configuration is only valid during the callback.
+    /// The application can neither pick another size nor delay the commit
+    /// until its content is ready, the size reported by
+    /// [`FrameEvent::Configure`](crate::FrameEvent::Configure) has already
+    /// been committed. Use [`decorate_with_handler`](#method.decorate_with_handler) to commit
+    /// configurations with a different policy.
+    pub fn decorate_with_queue(
+        &self,
+        surface: WlSurface,
+        initial_size: (i32, i32),
+    ) -> Option<(Frame<D>, FrameEventReceiver)> {
+        let queue = EventQueue::new(initial_size);
+        let frame = self.decorate_with_handler(surface, QueueHandler::new(queue.clone()))?;
+        Some((frame, FrameEventReceiver::new(queue)))
+    }
+}
+
+impl<D> Context<D> {
+    pub(crate) fn slot(&self) -> &DispatchSlot<D> {
+        &self.slot
+    }
+
+    /// Returns the plugin drawing the decorations of this context.
+    ///
+    /// libdecor does not expose its choice, so this is the plugin with the
+    /// highest priority loaded in the process when the context was created.
+    /// With several contexts selecting different plugins, e.g. through
+    /// [`ContextBuilder::preferred_plugin`], it may be the plugin of another
+    /// context.
+    pub fn active_plugin(&self) -> &ActivePlugin {
+        &self.inner.plugin
+    }
+
+    /// Set the preferred [`DecorationMode`] for frames decorated afterwards.
+    ///
+    /// Defaults to [`DecorationMode::ClientSide`]. With
+    /// [`DecorationMode::ServerSide`] the compositor is asked to decorate the
+    /// window via `zxdg_decoration_manager_v1`, decorations are only drawn by
+    /// the client if it refuses. The chosen mode is reported as
+    /// [`FrameRequest::DecorationMode`].
+    ///
+    /// This only works with the built-in decorations of the `dlopen` feature,
+    /// see [`uses_fallback`](crate::uses_fallback). libdecor itself always
+    /// negotiates server-side decorations and neither takes a preference nor
+    /// reports the outcome, so [`Unsupported`] is returned when libdecor is in
+    /// use and [`FrameRequest::DecorationMode`] is never emitted with it.
+    pub fn set_decoration_mode(&self, mode: DecorationMode) -> Result<(), Unsupported> {
+        // libdecor has no such function, the name is only used for reporting
+        let unsupported = Unsupported("libdecor_set_decoration_mode");
+        #[cfg(all(feature = "dlopen", not(feature = "mock")))]
+        if crate::fallback::set_decoration_mode(self.inner.ptr, mode) {
+            return Ok(());
+        }
+        #[cfg(not(all(feature = "dlopen", not(feature = "mock"))))]
+        let _ = mode;
+        Err(unsupported)
+    }
+
     /// Get the file descriptor used by libdecor. This is similar to
     /// wl_display_get_fd(), thus should be polled, and when data is available,
     /// [`dispatch`](#method.dispatch) should be called.
     pub fn fd(&self) -> RawFd {
-        unsafe { ffi_dispatch!(LIBDECOR_HANDLE, libdecor_get_fd, self.inner.0) }
+        unsafe { ffi_dispatch!(LIBDECOR_HANDLE, libdecor_get_fd, self.inner.ptr) }
     }
 
     /// Dispatch events. This function should be called when data is available on
     /// the file descriptor returned by [`fd`](#method.fd). If timeout is [`None`], this
     /// function will never block.
-    pub fn dispatch<T: Any>(&self, ddata: &mut T, timeout: Option<Duration>) -> bool {
-        let ddata = unsafe { std::mem::transmute(ddata) };
-        let ddata = DispatchData::wrap::<T>(ddata);
-        let ddata_mut = crate::DispatchDataMut::new(ddata);
-        crate::DISPATCH_METADATA.set(&ddata_mut, || {
-            let result = unsafe {
-                ffi_dispatch!(
-                    LIBDECOR_HANDLE,
-                    libdecor_dispatch,
-                    self.inner.0,
-                    timeout.map(|t| t.as_millis() as c_int).unwrap_or(-1)
-                )
-            };
-
-            result >= 0
-        })
+    ///
+    /// The passed `data` is handed to the callbacks invoked during the dispatch.
+    /// Panics in these callbacks are caught before unwinding into libdecor
+    /// and resumed once the dispatch has returned.
+    ///
+    /// Returns the number of dispatched events.
+    pub fn dispatch(
+        &self,
+        data: &mut D,
+        timeout: Option<Duration>,
+    ) -> Result<usize, DispatchError> {
+        let count = self.slot.set(data, || unsafe {
+            ffi_dispatch!(
+                LIBDECOR_HANDLE,
+                libdecor_dispatch,
+                self.inner.ptr,
+                timeout.map(|t| t.as_millis() as c_int).unwrap_or(-1)
+            )
+        });
+
+        if count < 0 {
+            Err(DispatchError::last_error(&self.inner.display))
+        } else {
+            Ok(count as usize)
+        }
     }
 }
@@ -0,0 +1,216 @@
+//! Integration with the [`calloop`] event loop
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    os::unix::prelude::{AsRawFd, RawFd},
+    rc::Rc,
+};
+
+use calloop::{
+    generic::Generic, EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory,
+};
+use wayland_client::Display;
+
+use crate::{Context, DispatchError, Error, Request};
+
+/// Events generated by a [`ContextSource`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ContextEvent {
+    /// The context has events to process.
+    ///
+    /// The [`Context`] passed as metadata should be dispatched with the
+    /// shared data of the event loop and a zero timeout. The requests for
+    /// its frames are then delivered to their handlers together with the
+    /// shared data.
+    Dispatch,
+    /// A request for the [`Context`]
+    Context(Request),
+}
+
+type PendingRequests = Rc<RefCell<VecDeque<Request>>>;
+
+#[derive(Debug)]
+struct ContextFd(RawFd);
+
+impl AsRawFd for ContextFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// A [`calloop`] event source for a [`Context`].
+///
+/// The source polls the file descriptor of the context and emits
+/// [`ContextEvent::Dispatch`] when it becomes readable. Dispatching the
+/// context with the shared data of the event loop delivers the requests of
+/// its frames to their handlers, see [`Context::decorate`]. Context requests
+/// are emitted as [`ContextEvent::Context`] once the dispatch returned.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use libdecor::{ContextEvent, ContextSource, FrameRequest, State};
+/// # use wayland_client::{protocol::wl_surface::WlSurface, Display};
+/// #
+/// # let display = Display::connect_to_env().unwrap();
+/// # let surface: WlSurface = unimplemented!();
+/// struct App {
+///     running: bool,
+/// }
+///
+/// let mut event_loop = calloop::EventLoop::<App>::try_new().unwrap();
+///
+/// let source = ContextSource::new(display);
+/// let frame = source
+///     .context()
+///     .decorate(surface, |frame, request, app: &mut App| match request {
+///         FrameRequest::Configure(configuration) => {
+///             let (width, height) = configuration
+///                 .content_size(frame)
+///                 .unwrap_or((800, 600));
+///             frame.commit(&State::new(width, height), Some(configuration));
+///         }
+///         FrameRequest::Close => app.running = false,
+///         _ => {}
+///     })
+///     .unwrap();
+///
+/// event_loop
+///     .handle()
+///     .insert_source(source, |event, context, app| match event {
+///         ContextEvent::Dispatch => context.dispatch(app, Some(Duration::ZERO)).map(|_| ()),
+///         ContextEvent::Context(request) => {
+///             // handle the context request
+///             Ok(())
+///         }
+///         _ => Ok(()),
+///     })
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct ContextSource<D = ()> {
+    context: Context<D>,
+    fd: Generic<ContextFd>,
+    pending: PendingRequests,
+}
+
+impl<D: 'static> ContextSource<D> {
+    /// Create a new libdecor context for the given [`Display`] wrapped
+    /// in an event source.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context could not be created, see [`try_new`](#method.try_new)
+    /// for a fallible alternative.
+    pub fn new(display: Display) -> Self {
+        match Self::try_new(display) {
+            Ok(source) => source,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Try to create a new libdecor context for the given [`Display`]
+    /// wrapped in an event source.
+    ///
+    /// See [`Context::try_new`].
+    pub fn try_new(display: Display) -> Result<Self, Error> {
+        let pending: PendingRequests = Rc::new(RefCell::new(VecDeque::new()));
+
+        let context = {
+            let pending = pending.clone();
+            Context::try_new(display, move |request| {
+                pending.borrow_mut().push_back(request)
+            })?
+        };
+
+        let fd = Generic::new(ContextFd(context.fd()), Interest::READ, Mode::Level);
+
+        Ok(Self {
+            context,
+            fd,
+            pending,
+        })
+    }
+
+    /// Access the wrapped [`Context`]
+    ///
+    /// Frames are decorated with the context, their handlers receive the
+    /// shared data of the event loop.
+    pub fn context(&self) -> &Context<D> {
+        &self.context
+    }
+
+    fn flush_pending<F>(&mut self, callback: &mut F) -> Result<(), DispatchError>
+    where
+        F: FnMut(ContextEvent, &mut Context<D>) -> Result<(), DispatchError>,
+    {
+        loop {
+            let request = self.pending.borrow_mut().pop_front();
+            match request {
+                Some(request) => callback(ContextEvent::Context(request), &mut self.context)?,
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+impl<D: 'static> EventSource for ContextSource<D> {
+    type Event = ContextEvent;
+    type Metadata = Context<D>;
+    type Ret = Result<(), DispatchError>;
+    type Error = DispatchError;
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> Result<PostAction, Self::Error>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        let context = &mut self.context;
+        let mut result = Ok(());
+        self.fd
+            .process_events(readiness, token, |_, _| {
+                result = callback(ContextEvent::Dispatch, context);
+                Ok(PostAction::Continue)
+            })
+            .map_err(DispatchError::Io)?;
+        result?;
+
+        // Requests reported while dispatching
+        self.flush_pending(&mut callback)?;
+        Ok(PostAction::Continue)
+    }
+
+    fn register(
+        &mut self,
+        poll: &mut Poll,
+        token_factory: &mut TokenFactory,
+    ) -> calloop::Result<()> {
+        self.fd.register(poll, token_factory)
+    }
+
+    fn reregister(
+        &mut self,
+        poll: &mut Poll,
+        token_factory: &mut TokenFactory,
+    ) -> calloop::Result<()> {
+        self.fd.reregister(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.fd.unregister(poll)
+    }
+
+    fn pre_run<F>(&mut self, mut callback: F) -> calloop::Result<()>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        // Requests reported while dispatching outside of the event loop
+        self.flush_pending(&mut callback)
+            .map_err(|err| calloop::Error::OtherError(Box::new(err)))
+    }
+}
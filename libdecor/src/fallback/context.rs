@@ -0,0 +1,303 @@
+use std::{
+    cell::{Cell, RefCell},
+    ffi::CString,
+    io,
+    os::raw::c_int,
+    rc::{Rc, Weak},
+};
+
+use wayland_client::{
+    global_filter,
+    protocol::{
+        wl_compositor::WlCompositor,
+        wl_pointer::{self, WlPointer},
+        wl_seat::{self, WlSeat},
+        wl_shm::WlShm,
+        wl_subcompositor::WlSubcompositor,
+    },
+    Display, EventQueue, GlobalManager, Main, Proxy,
+};
+use wayland_protocols::{
+    unstable::xdg_decoration::v1::client::zxdg_decoration_manager_v1::ZxdgDecorationManagerV1,
+    xdg_shell::client::xdg_wm_base::{self, XdgWmBase},
+};
+
+use libdecor_sys as ffi;
+
+use crate::DecorationMode;
+
+use super::frame::{self, FallbackFrame, Part};
+
+/// Globals required to decorate a surface
+pub(super) struct Globals {
+    pub(super) compositor: Main<WlCompositor>,
+    pub(super) subcompositor: Main<WlSubcompositor>,
+    pub(super) shm: Main<WlShm>,
+    pub(super) wm_base: Main<XdgWmBase>,
+    pub(super) decoration_manager: Option<Main<ZxdgDecorationManagerV1>>,
+}
+
+type Seats = Rc<RefCell<Vec<Rc<Seat>>>>;
+
+/// A fallback replacement for `struct libdecor`
+///
+/// All objects are created on a private event queue which is dispatched
+/// by [`FallbackContext::dispatch`].
+pub(super) struct FallbackContext {
+    display: Display,
+    iface: *const ffi::libdecor_interface,
+    event_queue: RefCell<EventQueue>,
+    pub(super) globals: Rc<Globals>,
+    /// The preferred mode for frames decorated afterwards
+    pub(super) decoration_mode: Cell<DecorationMode>,
+    seats: Seats,
+    _global_manager: GlobalManager,
+}
+
+impl FallbackContext {
+    /// Returns [`None`] if the compositor lacks a required global.
+    pub(super) fn new(
+        display: *mut ffi::wl_display,
+        iface: *mut ffi::libdecor_interface,
+    ) -> Option<Rc<Self>> {
+        let display = unsafe { Display::from_external_display(display as *mut _) };
+        let mut event_queue = display.create_event_queue();
+        let attached_display = (*display).clone().attach(event_queue.token());
+
+        let seats: Seats = Rc::new(RefCell::new(Vec::new()));
+        let global_manager = GlobalManager::new_with_cb(
+            &attached_display,
+            global_filter!([WlSeat, 5, {
+                let seats = seats.clone();
+                move |seat: Main<WlSeat>, _: wayland_client::DispatchData| {
+                    seats.borrow_mut().push(Seat::new(seat));
+                }
+            }]),
+        );
+        event_queue.sync_roundtrip(&mut (), |_, _, _| {}).ok()?;
+
+        let globals = Globals {
+            compositor: global_manager.instantiate_range(1, 4).ok()?,
+            subcompositor: global_manager.instantiate_exact(1).ok()?,
+            shm: global_manager.instantiate_exact(1).ok()?,
+            wm_base: global_manager.instantiate_exact(1).ok()?,
+            decoration_manager: global_manager.instantiate_exact(1).ok(),
+        };
+        globals.shm.quick_assign(|_, _, _| {});
+        globals.wm_base.quick_assign(|wm_base, event, _| {
+            if let xdg_wm_base::Event::Ping { serial } = event {
+                wm_base.pong(serial);
+            }
+        });
+
+        // Receive the seat capabilities and names
+        event_queue.sync_roundtrip(&mut (), |_, _, _| {}).ok()?;
+
+        Some(Rc::new(FallbackContext {
+            display,
+            iface,
+            event_queue: RefCell::new(event_queue),
+            globals: Rc::new(globals),
+            decoration_mode: Cell::new(DecorationMode::ClientSide),
+            seats,
+            _global_manager: global_manager,
+        }))
+    }
+
+    fn ptr(&self) -> *mut ffi::libdecor {
+        self as *const Self as *mut ffi::libdecor
+    }
+
+    /// Report an error through the interface passed to `libdecor_new`.
+    pub(super) fn notify_error(&self, error: ffi::libdecor_error, message: &str) {
+        let message = CString::new(message).unwrap_or_default();
+        unsafe { ((*self.iface).error)(self.ptr(), error, message.as_ptr()) }
+    }
+
+    pub(super) fn fd(&self) -> c_int {
+        self.display.get_connection_fd()
+    }
+
+    /// Dispatch the private event queue, waiting up to `timeout`
+    /// milliseconds for new events, `-1` waits indefinitely.
+    ///
+    /// Returns the number of dispatched events or `-1` with `errno` set.
+    pub(super) fn dispatch(&self, timeout: c_int) -> c_int {
+        // Nested dispatching from within a callback is a no-op
+        let mut event_queue = match self.event_queue.try_borrow_mut() {
+            Ok(event_queue) => event_queue,
+            Err(_) => return 0,
+        };
+
+        match self.dispatch_queue(&mut event_queue, timeout) {
+            Ok(dispatched) => dispatched as c_int,
+            Err(_) => -1,
+        }
+    }
+
+    fn dispatch_queue(&self, event_queue: &mut EventQueue, timeout: c_int) -> io::Result<u32> {
+        let dispatched = event_queue.dispatch_pending(&mut (), |_, _, _| {})?;
+        if dispatched > 0 {
+            return Ok(dispatched);
+        }
+
+        if let Err(err) = self.display.flush() {
+            if err.kind() != io::ErrorKind::WouldBlock {
+                return Err(err);
+            }
+        }
+
+        if let Some(guard) = event_queue.prepare_read() {
+            let mut fd = libc::pollfd {
+                fd: self.fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            match unsafe { libc::poll(&mut fd, 1, timeout) } {
+                ret if ret < 0 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                0 => {}
+                _ => {
+                    if let Err(err) = guard.read_events() {
+                        if err.kind() != io::ErrorKind::WouldBlock {
+                            return Err(err);
+                        }
+                    }
+                }
+            }
+        }
+
+        event_queue.dispatch_pending(&mut (), |_, _, _| {})
+    }
+}
+
+impl Drop for FallbackContext {
+    fn drop(&mut self) {
+        for seat in self.seats.borrow().iter() {
+            seat.release();
+        }
+        if let Some(decoration_manager) = &self.globals.decoration_manager {
+            decoration_manager.destroy();
+        }
+        self.globals.wm_base.destroy();
+        self.globals.subcompositor.destroy();
+    }
+}
+
+/// Get a [`WlSeat`] passed to libdecor
+pub(super) unsafe fn seat_from_ptr(seat: *mut ffi::wl_seat) -> WlSeat {
+    Proxy::<WlSeat>::from_c_ptr(seat as *mut _).into()
+}
+
+/// Pointer handling of a seat for clicks on the decorations
+struct Seat {
+    seat: Main<WlSeat>,
+    name: RefCell<Option<String>>,
+    pointer: RefCell<Option<Main<WlPointer>>>,
+    focus: RefCell<Option<(Weak<FallbackFrame>, Part)>>,
+    position: Cell<(f64, f64)>,
+}
+
+impl Seat {
+    fn new(seat: Main<WlSeat>) -> Rc<Self> {
+        let state = Rc::new(Seat {
+            seat: seat.clone(),
+            name: RefCell::new(None),
+            pointer: RefCell::new(None),
+            focus: RefCell::new(None),
+            position: Cell::new((0.0, 0.0)),
+        });
+
+        let weak = Rc::downgrade(&state);
+        seat.quick_assign(move |seat, event, _| {
+            let state = match weak.upgrade() {
+                Some(state) => state,
+                None => return,
+            };
+
+            match event {
+                wl_seat::Event::Capabilities { capabilities } => {
+                    let has_pointer = capabilities.contains(wl_seat::Capability::Pointer);
+                    let mut pointer = state.pointer.borrow_mut();
+                    if has_pointer && pointer.is_none() {
+                        let new_pointer = seat.get_pointer();
+                        let weak = Rc::downgrade(&state);
+                        new_pointer.quick_assign(move |_, event, _| {
+                            if let Some(state) = weak.upgrade() {
+                                state.pointer_event(event);
+                            }
+                        });
+                        *pointer = Some(new_pointer);
+                    } else if !has_pointer {
+                        if let Some(pointer) = pointer.take() {
+                            release_pointer(&pointer);
+                        }
+                    }
+                }
+                wl_seat::Event::Name { name } => *state.name.borrow_mut() = Some(name),
+                _ => {}
+            }
+        });
+
+        state
+    }
+
+    fn pointer_event(&self, event: wl_pointer::Event) {
+        match event {
+            wl_pointer::Event::Enter {
+                surface,
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                *self.focus.borrow_mut() = frame::decoration_of(&surface);
+                self.position.set((surface_x, surface_y));
+            }
+            wl_pointer::Event::Leave { .. } => *self.focus.borrow_mut() = None,
+            wl_pointer::Event::Motion {
+                surface_x,
+                surface_y,
+                ..
+            } => self.position.set((surface_x, surface_y)),
+            wl_pointer::Event::Button {
+                serial,
+                button,
+                state: wl_pointer::ButtonState::Pressed,
+                ..
+            } => {
+                let focus = self.focus.borrow().clone();
+                if let Some((frame, part)) = focus.and_then(|(f, p)| Some((f.upgrade()?, p))) {
+                    let name = self.name.borrow().clone();
+                    frame.pointer_button(
+                        &self.seat,
+                        name.as_deref(),
+                        part,
+                        self.position.get(),
+                        button,
+                        serial,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn release(&self) {
+        if let Some(pointer) = self.pointer.borrow_mut().take() {
+            release_pointer(&pointer);
+        }
+        if self.seat.as_ref().version() >= 5 {
+            self.seat.release();
+        }
+    }
+}
+
+fn release_pointer(pointer: &WlPointer) {
+    if pointer.as_ref().version() >= 3 {
+        pointer.release();
+    }
+}
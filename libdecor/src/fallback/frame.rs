@@ -0,0 +1,880 @@
+use std::{
+    cell::{Cell, RefCell},
+    ffi::{c_void, CStr, CString},
+    os::raw::{c_char, c_int},
+    ptr,
+    rc::{Rc, Weak},
+};
+
+use wayland_client::{
+    protocol::{
+        wl_output::WlOutput, wl_seat::WlSeat, wl_subsurface::WlSubsurface, wl_surface::WlSurface,
+    },
+    Main, Proxy,
+};
+use wayland_protocols::{
+    unstable::xdg_decoration::v1::client::zxdg_toplevel_decoration_v1::{
+        self, ZxdgToplevelDecorationV1,
+    },
+    xdg_shell::client::{
+        xdg_surface::{self, XdgSurface},
+        xdg_toplevel::{self, ResizeEdge, XdgToplevel},
+    },
+};
+
+use libdecor_sys::*;
+
+use crate::DecorationMode;
+
+use super::{
+    context::{FallbackContext, Globals},
+    render::{self, Button, BORDER_SIZE, TITLE_HEIGHT},
+};
+
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+
+const ALL_CAPABILITIES: libdecor_capabilities = LIBDECOR_ACTION_MOVE
+    | LIBDECOR_ACTION_RESIZE
+    | LIBDECOR_ACTION_MINIMIZE
+    | LIBDECOR_ACTION_FULLSCREEN
+    | LIBDECOR_ACTION_CLOSE;
+
+const NOT_FLOATING: libdecor_window_state = LIBDECOR_WINDOW_STATE_MAXIMIZED
+    | LIBDECOR_WINDOW_STATE_FULLSCREEN
+    | LIBDECOR_WINDOW_STATE_TILED_LEFT
+    | LIBDECOR_WINDOW_STATE_TILED_RIGHT
+    | LIBDECOR_WINDOW_STATE_TILED_TOP
+    | LIBDECOR_WINDOW_STATE_TILED_BOTTOM;
+
+/// A fallback replacement for `struct libdecor_configuration`
+///
+/// Only lives for the duration of the configure callback.
+pub(super) struct FallbackConfiguration {
+    serial: u32,
+    /// The window geometry size requested by the compositor
+    size: (i32, i32),
+    pub(super) window_state: libdecor_window_state,
+}
+
+/// A part of the decorations, each drawn to its own subsurface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Part {
+    Title,
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Part {
+    const ALL: [Part; 5] = [
+        Part::Title,
+        Part::Top,
+        Part::Bottom,
+        Part::Left,
+        Part::Right,
+    ];
+
+    /// Position and size of the part relative to the content surface
+    fn geometry(self, (width, height): (i32, i32), title_height: i32) -> (i32, i32, i32, i32) {
+        let top = -title_height;
+        match self {
+            Part::Title => (0, top, width, title_height),
+            Part::Top => (
+                -BORDER_SIZE,
+                top - BORDER_SIZE,
+                width + 2 * BORDER_SIZE,
+                BORDER_SIZE,
+            ),
+            Part::Bottom => (-BORDER_SIZE, height, width + 2 * BORDER_SIZE, BORDER_SIZE),
+            Part::Left => (-BORDER_SIZE, top, BORDER_SIZE, height + title_height),
+            Part::Right => (width, top, BORDER_SIZE, height + title_height),
+        }
+    }
+
+    /// The outline drawn along the edge facing the window
+    fn outline(self, width: i32, height: i32) -> (i32, i32, i32, i32) {
+        match self {
+            Part::Title => (0, 0, 0, 0),
+            Part::Top => (
+                BORDER_SIZE - 1,
+                BORDER_SIZE - 1,
+                width - 2 * BORDER_SIZE + 2,
+                1,
+            ),
+            Part::Bottom => (BORDER_SIZE - 1, 0, width - 2 * BORDER_SIZE + 2, 1),
+            Part::Left => (BORDER_SIZE - 1, 0, 1, height),
+            Part::Right => (0, 0, 1, height),
+        }
+    }
+
+    /// The resize edge of a border at the given position
+    fn resize_edge(self, (x, y): (f64, f64), (width, height): (i32, i32)) -> ResizeEdge {
+        let corner = f64::from(2 * BORDER_SIZE);
+        let start_x = x < corner;
+        let end_x = x > f64::from(width) - corner;
+        let start_y = y < corner;
+        let end_y = y > f64::from(height) - corner;
+
+        match self {
+            Part::Title => ResizeEdge::None,
+            Part::Top if start_x => ResizeEdge::TopLeft,
+            Part::Top if end_x => ResizeEdge::TopRight,
+            Part::Top => ResizeEdge::Top,
+            Part::Bottom if start_x => ResizeEdge::BottomLeft,
+            Part::Bottom if end_x => ResizeEdge::BottomRight,
+            Part::Bottom => ResizeEdge::Bottom,
+            Part::Left if start_y => ResizeEdge::TopLeft,
+            Part::Left if end_y => ResizeEdge::BottomLeft,
+            Part::Left => ResizeEdge::Left,
+            Part::Right if start_y => ResizeEdge::TopRight,
+            Part::Right if end_y => ResizeEdge::BottomRight,
+            Part::Right => ResizeEdge::Right,
+        }
+    }
+}
+
+/// Stored in the user data of the decoration surfaces
+struct DecorationData {
+    frame: Weak<FallbackFrame>,
+    part: Part,
+}
+
+/// Look up the frame and part of a decoration surface.
+pub(super) fn decoration_of(surface: &WlSurface) -> Option<(Weak<FallbackFrame>, Part)> {
+    surface
+        .as_ref()
+        .user_data()
+        .get::<DecorationData>()
+        .map(|data| (data.frame.clone(), data.part))
+}
+
+struct Decoration {
+    part: Part,
+    surface: Main<WlSurface>,
+    subsurface: Main<WlSubsurface>,
+}
+
+/// The xdg_shell objects, created when the frame is mapped
+struct Shell {
+    xdg_surface: Main<XdgSurface>,
+    toplevel: Main<XdgToplevel>,
+    /// Only created if server-side decorations are preferred
+    toplevel_decoration: Option<Main<ZxdgToplevelDecorationV1>>,
+    decorations: Vec<Decoration>,
+}
+
+struct FrameState {
+    title: CString,
+    app_id: Option<String>,
+    parent: Option<Weak<FallbackFrame>>,
+    capabilities: libdecor_capabilities,
+    visible: bool,
+    min_content_size: (i32, i32),
+    max_content_size: (i32, i32),
+    content_size: (i32, i32),
+    window_state: libdecor_window_state,
+    pending: ((i32, i32), libdecor_window_state),
+    popup_grabs: Vec<String>,
+    /// The preferred mode until the compositor chose one
+    decoration_mode: DecorationMode,
+    decoration_negotiated: bool,
+    shell: Option<Shell>,
+}
+
+impl FrameState {
+    /// Whether the decorations are drawn by the frame
+    fn decorated(&self) -> bool {
+        self.visible && self.decoration_mode == DecorationMode::ClientSide
+    }
+
+    fn title_height(&self) -> i32 {
+        title_height(self.decorated(), self.window_state)
+    }
+
+    fn buttons(&self) -> Vec<Button> {
+        let mut buttons = Vec::new();
+        if self.capabilities & LIBDECOR_ACTION_CLOSE != 0 {
+            buttons.push(Button::Close);
+        }
+        if self.capabilities & LIBDECOR_ACTION_RESIZE != 0 {
+            buttons.push(Button::Maximize);
+        }
+        if self.capabilities & LIBDECOR_ACTION_MINIMIZE != 0 {
+            buttons.push(Button::Minimize);
+        }
+        buttons
+    }
+}
+
+fn title_height(decorated: bool, window_state: libdecor_window_state) -> i32 {
+    if decorated && window_state & LIBDECOR_WINDOW_STATE_FULLSCREEN == 0 {
+        TITLE_HEIGHT
+    } else {
+        0
+    }
+}
+
+/// Describe why the size limits are invalid, as libdecor does, `0` is unset.
+fn invalid_limits(min: (i32, i32), max: (i32, i32)) -> Option<String> {
+    let exceeds = |min: i32, max: i32| min > 0 && max > 0 && min > max;
+    if exceeds(min.0, max.0) || exceeds(min.1, max.1) {
+        Some(format!(
+            "minimum size ({},{}) must be smaller than maximum size ({},{})",
+            min.0, min.1, max.0, max.1
+        ))
+    } else {
+        None
+    }
+}
+
+/// Translate a content size to a window geometry size, `0` stays unset.
+fn window_size((width, height): (i32, i32), title_height: i32) -> (i32, i32) {
+    (width, if height > 0 { height + title_height } else { 0 })
+}
+
+fn window_state(states: &[u8]) -> libdecor_window_state {
+    states
+        .chunks_exact(4)
+        .filter_map(|state| {
+            xdg_toplevel::State::from_raw(u32::from_ne_bytes([
+                state[0], state[1], state[2], state[3],
+            ]))
+        })
+        .fold(LIBDECOR_WINDOW_STATE_NONE, |window_state, state| {
+            window_state
+                | match state {
+                    xdg_toplevel::State::Activated => LIBDECOR_WINDOW_STATE_ACTIVE,
+                    xdg_toplevel::State::Maximized => LIBDECOR_WINDOW_STATE_MAXIMIZED,
+                    xdg_toplevel::State::Fullscreen => LIBDECOR_WINDOW_STATE_FULLSCREEN,
+                    xdg_toplevel::State::TiledLeft => LIBDECOR_WINDOW_STATE_TILED_LEFT,
+                    xdg_toplevel::State::TiledRight => LIBDECOR_WINDOW_STATE_TILED_RIGHT,
+                    xdg_toplevel::State::TiledTop => LIBDECOR_WINDOW_STATE_TILED_TOP,
+                    xdg_toplevel::State::TiledBottom => LIBDECOR_WINDOW_STATE_TILED_BOTTOM,
+                    _ => LIBDECOR_WINDOW_STATE_NONE,
+                }
+        })
+}
+
+fn resize_edge(edge: libdecor_resize_edge) -> ResizeEdge {
+    match edge {
+        LIBDECOR_RESIZE_EDGE_TOP => ResizeEdge::Top,
+        LIBDECOR_RESIZE_EDGE_BOTTOM => ResizeEdge::Bottom,
+        LIBDECOR_RESIZE_EDGE_LEFT => ResizeEdge::Left,
+        LIBDECOR_RESIZE_EDGE_TOP_LEFT => ResizeEdge::TopLeft,
+        LIBDECOR_RESIZE_EDGE_BOTTOM_LEFT => ResizeEdge::BottomLeft,
+        LIBDECOR_RESIZE_EDGE_RIGHT => ResizeEdge::Right,
+        LIBDECOR_RESIZE_EDGE_TOP_RIGHT => ResizeEdge::TopRight,
+        LIBDECOR_RESIZE_EDGE_BOTTOM_RIGHT => ResizeEdge::BottomRight,
+        _ => ResizeEdge::None,
+    }
+}
+
+enum Action {
+    Close,
+    ToggleMaximized,
+    Minimize,
+    Move,
+    Resize(ResizeEdge),
+    ShowWindowMenu(i32, i32),
+}
+
+/// A fallback replacement for `struct libdecor_frame`
+///
+/// The frame is reference counted like its C counterpart, the [`Rc`]
+/// only keeps it alive while one of its own callbacks is running.
+pub(super) struct FallbackFrame {
+    this: Weak<FallbackFrame>,
+    /// Frames are released before their context, as required by libdecor
+    context: *const FallbackContext,
+    globals: Rc<Globals>,
+    surface: WlSurface,
+    iface: *const libdecor_frame_interface,
+    user_data: *mut c_void,
+    refs: Cell<usize>,
+    state: RefCell<FrameState>,
+}
+
+impl FallbackFrame {
+    pub(super) fn new(
+        context: &FallbackContext,
+        surface: *mut wl_surface,
+        iface: *mut libdecor_frame_interface,
+        user_data: *mut c_void,
+    ) -> Rc<Self> {
+        let surface = unsafe { Proxy::<WlSurface>::from_c_ptr(surface as *mut _) }.into();
+        Rc::new_cyclic(|this| FallbackFrame {
+            this: this.clone(),
+            context,
+            globals: context.globals.clone(),
+            surface,
+            iface,
+            user_data,
+            refs: Cell::new(1),
+            state: RefCell::new(FrameState {
+                title: CString::default(),
+                app_id: None,
+                parent: None,
+                capabilities: ALL_CAPABILITIES,
+                visible: true,
+                min_content_size: (0, 0),
+                max_content_size: (0, 0),
+                content_size: (0, 0),
+                window_state: LIBDECOR_WINDOW_STATE_NONE,
+                pending: ((0, 0), LIBDECOR_WINDOW_STATE_NONE),
+                popup_grabs: Vec::new(),
+                decoration_mode: context.decoration_mode.get(),
+                decoration_negotiated: false,
+                shell: None,
+            }),
+        })
+    }
+
+    fn ptr(&self) -> *mut libdecor_frame {
+        self as *const Self as *mut libdecor_frame
+    }
+
+    pub(super) fn add_ref(&self) {
+        self.refs.set(self.refs.get() + 1);
+    }
+
+    /// Returns [`true`] if the last reference was removed.
+    pub(super) fn remove_ref(&self) -> bool {
+        self.refs.set(self.refs.get() - 1);
+        self.refs.get() == 0
+    }
+
+    /// Destroy all protocol objects of the frame.
+    pub(super) fn destroy(&self) {
+        if let Some(shell) = self.state.borrow_mut().shell.take() {
+            for decoration in shell.decorations {
+                decoration.subsurface.destroy();
+                decoration.surface.destroy();
+            }
+            // Has to be destroyed before the toplevel
+            if let Some(toplevel_decoration) = shell.toplevel_decoration {
+                toplevel_decoration.destroy();
+            }
+            shell.toplevel.destroy();
+            shell.xdg_surface.destroy();
+        }
+    }
+
+    fn configure(&self, serial: u32) {
+        let (size, window_state) = self.state.borrow().pending;
+        let mut configuration = FallbackConfiguration {
+            serial,
+            size,
+            window_state,
+        };
+        unsafe {
+            ((*self.iface).configure)(
+                self.ptr(),
+                &mut configuration as *mut FallbackConfiguration as *mut libdecor_configuration,
+                self.user_data,
+            )
+        }
+    }
+
+    fn request_commit(&self) {
+        unsafe { ((*self.iface).commit)(self.ptr(), self.user_data) }
+    }
+
+    fn dismiss_popup(&self, seat_name: &str) {
+        let seat_name = CString::new(seat_name).unwrap_or_default();
+        unsafe { ((*self.iface).dismiss_popup)(self.ptr(), seat_name.as_ptr(), self.user_data) }
+    }
+
+    pub(super) fn close(&self) {
+        unsafe { ((*self.iface).close)(self.ptr(), self.user_data) }
+    }
+
+    fn report_decoration_mode(&self, mode: DecorationMode) {
+        crate::frame::decoration_mode_callback_trampolin(self.ptr(), mode, self.user_data)
+    }
+
+    /// Create the toplevel decoration if server-side decorations are
+    /// preferred and supported by the compositor.
+    fn create_toplevel_decoration(
+        &self,
+        toplevel: &XdgToplevel,
+        mode: DecorationMode,
+    ) -> Option<Main<ZxdgToplevelDecorationV1>> {
+        if mode != DecorationMode::ServerSide {
+            return None;
+        }
+        let decoration_manager = self.globals.decoration_manager.as_ref()?;
+
+        let toplevel_decoration = decoration_manager.get_toplevel_decoration(toplevel);
+        let this = self.this.clone();
+        toplevel_decoration.quick_assign(move |_, event, _| {
+            if let zxdg_toplevel_decoration_v1::Event::Configure { mode } = event {
+                if let Some(frame) = this.upgrade() {
+                    frame.decoration_configured(mode);
+                }
+            }
+        });
+        toplevel_decoration.set_mode(zxdg_toplevel_decoration_v1::Mode::ServerSide);
+        Some(toplevel_decoration)
+    }
+
+    /// The compositor chose the decoration mode, followed by a configure.
+    fn decoration_configured(&self, mode: zxdg_toplevel_decoration_v1::Mode) {
+        let mode = match mode {
+            zxdg_toplevel_decoration_v1::Mode::ServerSide => DecorationMode::ServerSide,
+            _ => DecorationMode::ClientSide,
+        };
+        {
+            let mut state = self.state.borrow_mut();
+            if state.decoration_negotiated && state.decoration_mode == mode {
+                return;
+            }
+            state.decoration_mode = mode;
+            state.decoration_negotiated = true;
+            self.update_decorations(&state);
+            self.apply_size_limits(&state);
+        }
+        self.report_decoration_mode(mode);
+    }
+
+    pub(super) fn map(&self) {
+        if self.state.borrow().shell.is_some() {
+            return;
+        }
+
+        let parent = self.parent_toplevel();
+        let mut state = self.state.borrow_mut();
+
+        let xdg_surface = self.globals.wm_base.get_xdg_surface(&self.surface);
+        let toplevel = xdg_surface.get_toplevel();
+
+        let this = self.this.clone();
+        xdg_surface.quick_assign(move |_, event, _| {
+            if let xdg_surface::Event::Configure { serial } = event {
+                if let Some(frame) = this.upgrade() {
+                    frame.configure(serial);
+                }
+            }
+        });
+        let this = self.this.clone();
+        toplevel.quick_assign(move |_, event, _| {
+            let frame = match this.upgrade() {
+                Some(frame) => frame,
+                None => return,
+            };
+            match event {
+                xdg_toplevel::Event::Configure {
+                    width,
+                    height,
+                    states,
+                } => frame.state.borrow_mut().pending = ((width, height), window_state(&states)),
+                xdg_toplevel::Event::Close => frame.close(),
+                _ => {}
+            }
+        });
+
+        toplevel.set_title(state.title.to_string_lossy().into_owned());
+        if let Some(app_id) = &state.app_id {
+            toplevel.set_app_id(app_id.clone());
+        }
+        if let Some(parent) = parent {
+            toplevel.set_parent(Some(&parent));
+        }
+
+        let toplevel_decoration = self.create_toplevel_decoration(&toplevel, state.decoration_mode);
+        // Without the decoration manager the frame has to draw the decorations
+        let refused =
+            toplevel_decoration.is_none() && state.decoration_mode == DecorationMode::ServerSide;
+        if refused {
+            state.decoration_mode = DecorationMode::ClientSide;
+        }
+
+        let decorations = Part::ALL
+            .iter()
+            .map(|&part| self.create_decoration(part))
+            .collect();
+
+        state.shell = Some(Shell {
+            xdg_surface,
+            toplevel,
+            toplevel_decoration,
+            decorations,
+        });
+        self.apply_size_limits(&state);
+        drop(state);
+
+        self.surface.commit();
+
+        if refused {
+            self.report_decoration_mode(DecorationMode::ClientSide);
+        }
+    }
+
+    fn create_decoration(&self, part: Part) -> Decoration {
+        let surface = self.globals.compositor.create_surface();
+        surface.quick_assign(|_, _, _| {});
+        let frame = self.this.clone();
+        surface
+            .as_ref()
+            .user_data()
+            .set(move || DecorationData { frame, part });
+
+        let subsurface = self
+            .globals
+            .subcompositor
+            .get_subsurface(&surface, &self.surface);
+
+        Decoration {
+            part,
+            surface,
+            subsurface,
+        }
+    }
+
+    /// Redraw and reposition the decorations for the current state.
+    fn update_decorations(&self, state: &FrameState) {
+        let shell = match &state.shell {
+            Some(shell) => shell,
+            None => return,
+        };
+
+        let content_size = state.content_size;
+        let title_height = state.title_height();
+        let borders = title_height > 0 && state.window_state & NOT_FLOATING == 0;
+        let active = state.window_state & LIBDECOR_WINDOW_STATE_ACTIVE != 0;
+
+        for decoration in &shell.decorations {
+            let shown = match decoration.part {
+                Part::Title => title_height > 0,
+                _ => borders,
+            };
+            let (x, y, width, height) = decoration.part.geometry(content_size, title_height);
+
+            let buffer = if shown && width > 0 && height > 0 {
+                let pixels = match decoration.part {
+                    Part::Title => render::title_bar(
+                        width,
+                        active,
+                        &state.title.to_string_lossy(),
+                        &state.buttons(),
+                    ),
+                    part => render::border(width, height, part.outline(width, height)),
+                };
+                render::buffer(&self.globals.shm, width, height, &pixels)
+                    .ok()
+                    .map(|buffer| buffer.detach())
+            } else {
+                None
+            };
+
+            decoration.subsurface.set_position(x, y);
+            decoration.surface.attach(buffer.as_ref(), 0, 0);
+            if buffer.is_some() {
+                decoration.surface.damage(0, 0, width, height);
+            }
+            decoration.surface.commit();
+        }
+
+        if content_size.0 > 0 && content_size.1 > 0 {
+            shell.xdg_surface.set_window_geometry(
+                0,
+                -title_height,
+                content_size.0,
+                content_size.1 + title_height,
+            );
+        }
+    }
+
+    fn apply_size_limits(&self, state: &FrameState) {
+        if let Some(shell) = &state.shell {
+            let title_height = state.title_height();
+            let (width, height) = window_size(state.min_content_size, title_height);
+            shell.toplevel.set_min_size(width, height);
+            let (width, height) = window_size(state.max_content_size, title_height);
+            shell.toplevel.set_max_size(width, height);
+        }
+    }
+
+    pub(super) fn commit(
+        &self,
+        content_size: (i32, i32),
+        configuration: Option<&FallbackConfiguration>,
+    ) {
+        let error = {
+            let mut state = self.state.borrow_mut();
+            if let Some(configuration) = configuration {
+                state.window_state = configuration.window_state;
+                if let Some(shell) = &state.shell {
+                    shell.xdg_surface.ack_configure(configuration.serial);
+                }
+            }
+            state.content_size = content_size;
+
+            self.update_decorations(&state);
+            self.apply_size_limits(&state);
+            invalid_limits(state.min_content_size, state.max_content_size)
+        };
+
+        // Reported without holding the state, the callback may use the frame
+        if let Some(message) = error {
+            unsafe { &*self.context }
+                .notify_error(LIBDECOR_ERROR_INVALID_FRAME_CONFIGURATION, &message);
+        }
+    }
+
+    pub(super) fn content_size(&self, configuration: &FallbackConfiguration) -> Option<(i32, i32)> {
+        let (width, height) = configuration.size;
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+
+        let decorated = self.state.borrow().decorated();
+        let title_height = title_height(decorated, configuration.window_state);
+        Some((width, height - title_height))
+    }
+
+    pub(super) fn set_visibility(&self, visible: bool) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.visible = visible;
+            self.update_decorations(&state);
+            self.apply_size_limits(&state);
+        }
+        self.request_commit();
+    }
+
+    pub(super) fn is_visible(&self) -> bool {
+        self.state.borrow().visible
+    }
+
+    fn parent_toplevel(&self) -> Option<XdgToplevel> {
+        let parent = self.state.borrow().parent.as_ref()?.upgrade()?;
+        if ptr::eq(&*parent, self) {
+            return None;
+        }
+        let state = parent.state.borrow();
+        state.shell.as_ref().map(|shell| shell.toplevel.detach())
+    }
+
+    pub(super) fn set_parent(&self, parent: Option<&FallbackFrame>) {
+        self.state.borrow_mut().parent = parent.map(|parent| parent.this.clone());
+        let parent = self.parent_toplevel();
+        if let Some(shell) = &self.state.borrow().shell {
+            shell.toplevel.set_parent(parent.as_ref());
+        }
+    }
+
+    pub(super) fn set_title(&self, title: &CStr) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.title = title.to_owned();
+            if let Some(shell) = &state.shell {
+                shell
+                    .toplevel
+                    .set_title(title.to_string_lossy().into_owned());
+            }
+            self.update_decorations(&state);
+        }
+        self.request_commit();
+    }
+
+    pub(super) fn title(&self) -> *const c_char {
+        // The string is owned by the frame and only replaced by `set_title`
+        self.state.borrow().title.as_ptr()
+    }
+
+    pub(super) fn set_app_id(&self, app_id: &str) {
+        let mut state = self.state.borrow_mut();
+        state.app_id = Some(app_id.to_owned());
+        if let Some(shell) = &state.shell {
+            shell.toplevel.set_app_id(app_id.to_owned());
+        }
+    }
+
+    pub(super) fn set_capabilities(&self, capabilities: libdecor_capabilities, set: bool) {
+        {
+            let mut state = self.state.borrow_mut();
+            if set {
+                state.capabilities |= capabilities;
+            } else {
+                state.capabilities &= !capabilities;
+            }
+            self.update_decorations(&state);
+        }
+        self.request_commit();
+    }
+
+    pub(super) fn has_capability(&self, capability: libdecor_capabilities) -> bool {
+        self.state.borrow().capabilities & capability == capability
+    }
+
+    fn with_toplevel(&self, f: impl FnOnce(&XdgToplevel)) {
+        if let Some(shell) = &self.state.borrow().shell {
+            f(&shell.toplevel);
+        }
+    }
+
+    pub(super) fn show_window_menu(&self, seat: &WlSeat, serial: u32, x: c_int, y: c_int) {
+        self.with_toplevel(|toplevel| toplevel.show_window_menu(seat, serial, x, y));
+    }
+
+    pub(super) fn popup_grab(&self, seat_name: &str) {
+        self.state
+            .borrow_mut()
+            .popup_grabs
+            .push(seat_name.to_owned());
+    }
+
+    pub(super) fn popup_ungrab(&self, seat_name: &str) {
+        self.state
+            .borrow_mut()
+            .popup_grabs
+            .retain(|name| name != seat_name);
+    }
+
+    pub(super) fn translate_coordinate(&self, x: c_int, y: c_int) -> (c_int, c_int) {
+        (x, y + self.state.borrow().title_height())
+    }
+
+    pub(super) fn set_min_content_size(&self, width: c_int, height: c_int) {
+        let mut state = self.state.borrow_mut();
+        state.min_content_size = (width, height);
+        self.apply_size_limits(&state);
+    }
+
+    pub(super) fn set_max_content_size(&self, width: c_int, height: c_int) {
+        let mut state = self.state.borrow_mut();
+        state.max_content_size = (width, height);
+        self.apply_size_limits(&state);
+    }
+
+    #[cfg(feature = "libdecor_0_2")]
+    pub(super) fn min_content_size(&self) -> (c_int, c_int) {
+        self.state.borrow().min_content_size
+    }
+
+    #[cfg(feature = "libdecor_0_2")]
+    pub(super) fn max_content_size(&self) -> (c_int, c_int) {
+        self.state.borrow().max_content_size
+    }
+
+    pub(super) fn resize(&self, seat: &WlSeat, serial: u32, edge: libdecor_resize_edge) {
+        self.with_toplevel(|toplevel| toplevel.resize(seat, serial, resize_edge(edge)));
+    }
+
+    pub(super) fn _move(&self, seat: &WlSeat, serial: u32) {
+        self.with_toplevel(|toplevel| toplevel._move(seat, serial));
+    }
+
+    pub(super) fn set_minimized(&self) {
+        self.with_toplevel(|toplevel| toplevel.set_minimized());
+    }
+
+    pub(super) fn set_maximized(&self, maximized: bool) {
+        self.with_toplevel(|toplevel| {
+            if maximized {
+                toplevel.set_maximized()
+            } else {
+                toplevel.unset_maximized()
+            }
+        });
+    }
+
+    pub(super) fn set_fullscreen(&self, output: Option<*mut wl_output>) {
+        self.with_toplevel(|toplevel| match output {
+            Some(output) => {
+                let output: Option<WlOutput> = if output.is_null() {
+                    None
+                } else {
+                    Some(unsafe { Proxy::<WlOutput>::from_c_ptr(output as *mut _) }.into())
+                };
+                toplevel.set_fullscreen(output.as_ref())
+            }
+            None => toplevel.unset_fullscreen(),
+        });
+    }
+
+    pub(super) fn is_floating(&self) -> bool {
+        self.state.borrow().window_state & NOT_FLOATING == 0
+    }
+
+    pub(super) fn xdg_surface_ptr(&self) -> *mut c_void {
+        match &self.state.borrow().shell {
+            Some(shell) => shell.xdg_surface.as_ref().c_ptr() as *mut c_void,
+            None => ptr::null_mut(),
+        }
+    }
+
+    pub(super) fn xdg_toplevel_ptr(&self) -> *mut c_void {
+        match &self.state.borrow().shell {
+            Some(shell) => shell.toplevel.as_ref().c_ptr() as *mut c_void,
+            None => ptr::null_mut(),
+        }
+    }
+
+    /// Handle a button press on one of the decorations.
+    pub(super) fn pointer_button(
+        &self,
+        seat: &WlSeat,
+        seat_name: Option<&str>,
+        part: Part,
+        position: (f64, f64),
+        button: u32,
+        serial: u32,
+    ) {
+        // Clicking the decorations dismisses popups grabbing the seat
+        if let Some(seat_name) = seat_name {
+            let grabbed = self
+                .state
+                .borrow()
+                .popup_grabs
+                .iter()
+                .any(|name| name == seat_name);
+            if grabbed {
+                self.dismiss_popup(seat_name);
+                return;
+            }
+        }
+
+        let action = {
+            let state = self.state.borrow();
+            let title_height = state.title_height();
+            let (_, _, width, height) = part.geometry(state.content_size, title_height);
+
+            match (part, button) {
+                (Part::Title, BTN_LEFT) => {
+                    let slot = (f64::from(width) - position.0) / f64::from(TITLE_HEIGHT);
+                    match state.buttons().get(slot as usize) {
+                        Some(Button::Close) if slot >= 0.0 => Some(Action::Close),
+                        Some(Button::Maximize) if slot >= 0.0 => Some(Action::ToggleMaximized),
+                        Some(Button::Minimize) if slot >= 0.0 => Some(Action::Minimize),
+                        _ if state.capabilities & LIBDECOR_ACTION_MOVE != 0 => Some(Action::Move),
+                        _ => None,
+                    }
+                }
+                (Part::Title, BTN_RIGHT) => {
+                    Some(Action::ShowWindowMenu(position.0 as i32, position.1 as i32))
+                }
+                (_, BTN_LEFT) if state.capabilities & LIBDECOR_ACTION_RESIZE != 0 => {
+                    Some(Action::Resize(part.resize_edge(position, (width, height))))
+                }
+                _ => None,
+            }
+        };
+
+        match action {
+            Some(Action::Close) => self.close(),
+            Some(Action::ToggleMaximized) => self.set_maximized(
+                self.state.borrow().window_state & LIBDECOR_WINDOW_STATE_MAXIMIZED == 0,
+            ),
+            Some(Action::Minimize) => self.set_minimized(),
+            Some(Action::Move) => self._move(seat, serial),
+            Some(Action::Resize(edge)) => {
+                self.with_toplevel(|toplevel| toplevel.resize(seat, serial, edge))
+            }
+            Some(Action::ShowWindowMenu(x, y)) => self.show_window_menu(seat, serial, x, y),
+            None => {}
+        }
+    }
+}
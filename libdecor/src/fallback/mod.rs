@@ -0,0 +1,414 @@
+//! Pure-Rust fallback decorations
+//!
+//! Used with the `dlopen` feature if the libdecor shared library could not
+//! be loaded. The fallback implements the libdecor C API on top of
+//! xdg_shell and draws simple decorations, a title bar with buttons and
+//! invisible resize borders, into `wl_shm` buffers attached to subsurfaces
+//! of the content surface. The title is drawn with a small bitmap font
+//! covering ASCII.
+//!
+//! As the functions are exposed through the same [`LibdecorFunctions`]
+//! table as the loaded library, [`Context`](crate::Context) and
+//! [`Frame`](crate::Frame) work unchanged on top of it.
+
+use std::{
+    ffi::{c_void, CStr},
+    ops::Deref,
+    os::raw::{c_char, c_int},
+    rc::Rc,
+    sync::OnceLock,
+};
+
+use libdecor_sys::*;
+
+use crate::DecorationMode;
+
+mod context;
+mod font;
+mod frame;
+mod render;
+
+use self::{
+    context::FallbackContext,
+    frame::{FallbackConfiguration, FallbackFrame},
+};
+
+/// Replaces [`libdecor_sys::LIBDECOR_HANDLE`] within this crate
+///
+/// Dereferences to the functions of the loaded library, or to the
+/// fallback implementation if the library is not available.
+pub(crate) struct Handle;
+
+pub(crate) static LIBDECOR_HANDLE: Handle = Handle;
+
+impl Deref for Handle {
+    type Target = LibdecorFunctions;
+
+    fn deref(&self) -> &LibdecorFunctions {
+        static FUNCTIONS: OnceLock<LibdecorFunctions> = OnceLock::new();
+        FUNCTIONS.get_or_init(|| match *LIBDECOR_OPTION {
+            Some(lib) => lib.into(),
+            None => FALLBACK_FUNCTIONS,
+        })
+    }
+}
+
+static FALLBACK_FUNCTIONS: LibdecorFunctions = LibdecorFunctions {
+    libdecor_new,
+    libdecor_unref,
+    libdecor_get_fd,
+    libdecor_dispatch,
+    libdecor_decorate,
+    libdecor_frame_ref,
+    libdecor_frame_unref,
+    libdecor_frame_set_visibility,
+    libdecor_frame_is_visible,
+    libdecor_frame_set_parent,
+    libdecor_frame_set_title,
+    libdecor_frame_get_title,
+    libdecor_frame_set_app_id,
+    libdecor_frame_set_capabilities,
+    libdecor_frame_unset_capabilities,
+    libdecor_frame_has_capability,
+    libdecor_frame_show_window_menu,
+    libdecor_frame_popup_grab,
+    libdecor_frame_popup_ungrab,
+    libdecor_frame_translate_coordinate,
+    libdecor_frame_set_max_content_size,
+    libdecor_frame_set_min_content_size,
+    libdecor_frame_resize,
+    libdecor_frame_move,
+    libdecor_frame_commit,
+    libdecor_frame_set_minimized,
+    libdecor_frame_set_maximized,
+    libdecor_frame_unset_maximized,
+    libdecor_frame_set_fullscreen,
+    libdecor_frame_unset_fullscreen,
+    libdecor_frame_is_floating,
+    libdecor_frame_close,
+    libdecor_frame_map,
+    libdecor_frame_get_xdg_surface,
+    libdecor_frame_get_xdg_toplevel,
+    libdecor_state_new,
+    libdecor_state_free,
+    libdecor_configuration_get_content_size,
+    libdecor_configuration_get_window_state,
+    #[cfg(feature = "libdecor_0_2")]
+    libdecor_frame_get_min_content_size: Some(libdecor_frame_get_min_content_size),
+    #[cfg(feature = "libdecor_0_2")]
+    libdecor_frame_get_max_content_size: Some(libdecor_frame_get_max_content_size),
+    #[cfg(feature = "libdecor_0_2")]
+    libdecor_frame_get_wm_capabilities: Some(libdecor_frame_get_wm_capabilities),
+};
+
+// SAFETY (for all functions below): The pointers are only ever created by
+// this module and are passed back by `Context` and `Frame` unchanged.
+
+/// Set the preferred decoration mode of a context.
+///
+/// The system libdecor negotiates the mode on its own, so this only
+/// affects the fallback. Returns whether the fallback is in use.
+pub(crate) fn set_decoration_mode(ctx: *mut libdecor, mode: DecorationMode) -> bool {
+    if LIBDECOR_OPTION.is_some() {
+        return false;
+    }
+    unsafe { context(ctx) }.decoration_mode.set(mode);
+    true
+}
+
+unsafe fn context<'a>(context: *mut libdecor) -> &'a FallbackContext {
+    &*(context as *const FallbackContext)
+}
+
+unsafe fn frame<'a>(frame: *mut libdecor_frame) -> &'a FallbackFrame {
+    &*(frame as *const FallbackFrame)
+}
+
+unsafe fn to_str<'a>(s: *const c_char) -> &'a str {
+    CStr::from_ptr(s).to_str().unwrap_or_default()
+}
+
+unsafe extern "C" fn libdecor_new(
+    display: *mut wl_display,
+    iface: *mut libdecor_interface,
+) -> *mut libdecor {
+    match FallbackContext::new(display, iface) {
+        Some(context) => Rc::into_raw(context) as *mut libdecor,
+        None => std::ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn libdecor_unref(context: *mut libdecor) {
+    drop(Rc::from_raw(context as *const FallbackContext));
+}
+
+unsafe extern "C" fn libdecor_get_fd(ctx: *mut libdecor) -> c_int {
+    context(ctx).fd()
+}
+
+unsafe extern "C" fn libdecor_dispatch(ctx: *mut libdecor, timeout: c_int) -> c_int {
+    context(ctx).dispatch(timeout)
+}
+
+unsafe extern "C" fn libdecor_decorate(
+    ctx: *mut libdecor,
+    surface: *mut wl_surface,
+    iface: *mut libdecor_frame_interface,
+    user_data: *mut c_void,
+) -> *mut libdecor_frame {
+    let frame = FallbackFrame::new(context(ctx), surface, iface, user_data);
+    Rc::into_raw(frame) as *mut libdecor_frame
+}
+
+unsafe extern "C" fn libdecor_frame_ref(ptr: *mut libdecor_frame) {
+    frame(ptr).add_ref();
+}
+
+unsafe extern "C" fn libdecor_frame_unref(ptr: *mut libdecor_frame) {
+    if frame(ptr).remove_ref() {
+        frame(ptr).destroy();
+        drop(Rc::from_raw(ptr as *const FallbackFrame));
+    }
+}
+
+unsafe extern "C" fn libdecor_frame_set_visibility(ptr: *mut libdecor_frame, visible: bool) {
+    frame(ptr).set_visibility(visible);
+}
+
+unsafe extern "C" fn libdecor_frame_is_visible(ptr: *mut libdecor_frame) -> bool {
+    frame(ptr).is_visible()
+}
+
+unsafe extern "C" fn libdecor_frame_set_parent(
+    ptr: *mut libdecor_frame,
+    parent: *mut libdecor_frame,
+) {
+    let parent = if parent.is_null() {
+        None
+    } else {
+        Some(frame(parent))
+    };
+    frame(ptr).set_parent(parent);
+}
+
+unsafe extern "C" fn libdecor_frame_set_title(ptr: *mut libdecor_frame, title: *const c_char) {
+    frame(ptr).set_title(CStr::from_ptr(title));
+}
+
+unsafe extern "C" fn libdecor_frame_get_title(ptr: *mut libdecor_frame) -> *const c_char {
+    frame(ptr).title()
+}
+
+unsafe extern "C" fn libdecor_frame_set_app_id(ptr: *mut libdecor_frame, app_id: *const c_char) {
+    frame(ptr).set_app_id(to_str(app_id));
+}
+
+unsafe extern "C" fn libdecor_frame_set_capabilities(
+    ptr: *mut libdecor_frame,
+    capabilities: libdecor_capabilities,
+) {
+    frame(ptr).set_capabilities(capabilities, true);
+}
+
+unsafe extern "C" fn libdecor_frame_unset_capabilities(
+    ptr: *mut libdecor_frame,
+    capabilities: libdecor_capabilities,
+) {
+    frame(ptr).set_capabilities(capabilities, false);
+}
+
+unsafe extern "C" fn libdecor_frame_has_capability(
+    ptr: *mut libdecor_frame,
+    capability: libdecor_capabilities,
+) -> bool {
+    frame(ptr).has_capability(capability)
+}
+
+unsafe extern "C" fn libdecor_frame_show_window_menu(
+    ptr: *mut libdecor_frame,
+    seat: *mut wl_seat,
+    serial: u32,
+    x: c_int,
+    y: c_int,
+) {
+    frame(ptr).show_window_menu(&context::seat_from_ptr(seat), serial, x, y);
+}
+
+unsafe extern "C" fn libdecor_frame_popup_grab(ptr: *mut libdecor_frame, seat_name: *const c_char) {
+    frame(ptr).popup_grab(to_str(seat_name));
+}
+
+unsafe extern "C" fn libdecor_frame_popup_ungrab(
+    ptr: *mut libdecor_frame,
+    seat_name: *const c_char,
+) {
+    frame(ptr).popup_ungrab(to_str(seat_name));
+}
+
+unsafe extern "C" fn libdecor_frame_translate_coordinate(
+    ptr: *mut libdecor_frame,
+    x: c_int,
+    y: c_int,
+    frame_x: *mut c_int,
+    frame_y: *mut c_int,
+) {
+    let (x, y) = frame(ptr).translate_coordinate(x, y);
+    *frame_x = x;
+    *frame_y = y;
+}
+
+unsafe extern "C" fn libdecor_frame_set_max_content_size(
+    ptr: *mut libdecor_frame,
+    width: c_int,
+    height: c_int,
+) {
+    frame(ptr).set_max_content_size(width, height);
+}
+
+unsafe extern "C" fn libdecor_frame_set_min_content_size(
+    ptr: *mut libdecor_frame,
+    width: c_int,
+    height: c_int,
+) {
+    frame(ptr).set_min_content_size(width, height);
+}
+
+#[cfg(feature = "libdecor_0_2")]
+unsafe extern "C" fn libdecor_frame_get_min_content_size(
+    ptr: *const libdecor_frame,
+    width: *mut c_int,
+    height: *mut c_int,
+) {
+    let (w, h) = frame(ptr as *mut _).min_content_size();
+    *width = w;
+    *height = h;
+}
+
+#[cfg(feature = "libdecor_0_2")]
+unsafe extern "C" fn libdecor_frame_get_max_content_size(
+    ptr: *const libdecor_frame,
+    width: *mut c_int,
+    height: *mut c_int,
+) {
+    let (w, h) = frame(ptr as *mut _).max_content_size();
+    *width = w;
+    *height = h;
+}
+
+/// `xdg_wm_base` is bound below version 5, so like libdecor all
+/// capabilities are assumed to be supported.
+#[cfg(feature = "libdecor_0_2")]
+unsafe extern "C" fn libdecor_frame_get_wm_capabilities(
+    _ptr: *mut libdecor_frame,
+) -> libdecor_wm_capabilities {
+    LIBDECOR_WM_CAPABILITIES_WINDOW_MENU
+        | LIBDECOR_WM_CAPABILITIES_MAXIMIZE
+        | LIBDECOR_WM_CAPABILITIES_FULLSCREEN
+        | LIBDECOR_WM_CAPABILITIES_MINIMIZE
+}
+
+unsafe extern "C" fn libdecor_frame_resize(
+    ptr: *mut libdecor_frame,
+    seat: *mut wl_seat,
+    serial: u32,
+    edge: libdecor_resize_edge,
+) {
+    frame(ptr).resize(&context::seat_from_ptr(seat), serial, edge);
+}
+
+unsafe extern "C" fn libdecor_frame_move(
+    ptr: *mut libdecor_frame,
+    seat: *mut wl_seat,
+    serial: u32,
+) {
+    frame(ptr)._move(&context::seat_from_ptr(seat), serial);
+}
+
+unsafe extern "C" fn libdecor_frame_commit(
+    ptr: *mut libdecor_frame,
+    state: *mut libdecor_state,
+    configuration: *mut libdecor_configuration,
+) {
+    let configuration = (configuration as *const FallbackConfiguration).as_ref();
+    let content_size = *(state as *const (c_int, c_int));
+    frame(ptr).commit(content_size, configuration);
+}
+
+unsafe extern "C" fn libdecor_frame_set_minimized(ptr: *mut libdecor_frame) {
+    frame(ptr).set_minimized();
+}
+
+unsafe extern "C" fn libdecor_frame_set_maximized(ptr: *mut libdecor_frame) {
+    frame(ptr).set_maximized(true);
+}
+
+unsafe extern "C" fn libdecor_frame_unset_maximized(ptr: *mut libdecor_frame) {
+    frame(ptr).set_maximized(false);
+}
+
+unsafe extern "C" fn libdecor_frame_set_fullscreen(
+    ptr: *mut libdecor_frame,
+    output: *mut wl_output,
+) {
+    frame(ptr).set_fullscreen(Some(output));
+}
+
+unsafe extern "C" fn libdecor_frame_unset_fullscreen(ptr: *mut libdecor_frame) {
+    frame(ptr).set_fullscreen(None);
+}
+
+unsafe extern "C" fn libdecor_frame_is_floating(ptr: *mut libdecor_frame) -> bool {
+    frame(ptr).is_floating()
+}
+
+unsafe extern "C" fn libdecor_frame_close(ptr: *mut libdecor_frame) {
+    frame(ptr).close();
+}
+
+unsafe extern "C" fn libdecor_frame_map(ptr: *mut libdecor_frame) {
+    frame(ptr).map();
+}
+
+unsafe extern "C" fn libdecor_frame_get_xdg_surface(ptr: *mut libdecor_frame) -> *mut xdg_surface {
+    frame(ptr).xdg_surface_ptr() as *mut xdg_surface
+}
+
+unsafe extern "C" fn libdecor_frame_get_xdg_toplevel(
+    ptr: *mut libdecor_frame,
+) -> *mut xdg_toplevel {
+    frame(ptr).xdg_toplevel_ptr() as *mut xdg_toplevel
+}
+
+unsafe extern "C" fn libdecor_state_new(width: c_int, height: c_int) -> *mut libdecor_state {
+    Box::into_raw(Box::new((width, height))) as *mut libdecor_state
+}
+
+unsafe extern "C" fn libdecor_state_free(state: *mut libdecor_state) {
+    drop(Box::from_raw(state as *mut (c_int, c_int)));
+}
+
+unsafe extern "C" fn libdecor_configuration_get_content_size(
+    configuration: *mut libdecor_configuration,
+    ptr: *mut libdecor_frame,
+    width: *mut c_int,
+    height: *mut c_int,
+) -> bool {
+    let configuration = &*(configuration as *const FallbackConfiguration);
+    match frame(ptr).content_size(configuration) {
+        Some((w, h)) => {
+            *width = w;
+            *height = h;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn libdecor_configuration_get_window_state(
+    configuration: *mut libdecor_configuration,
+    window_state: *mut libdecor_window_state,
+) -> bool {
+    let configuration = &*(configuration as *const FallbackConfiguration);
+    *window_state = configuration.window_state;
+    true
+}
@@ -0,0 +1,167 @@
+use std::{
+    ffi::CStr,
+    fs::File,
+    io::{self, Write},
+    os::unix::io::{AsRawFd, FromRawFd},
+};
+
+use wayland_client::{
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_shm::{self, WlShm},
+    },
+    Main,
+};
+
+use super::font;
+
+/// Height of the title bar, also the size of its buttons
+pub(super) const TITLE_HEIGHT: i32 = 24;
+/// Width of the invisible resize borders
+pub(super) const BORDER_SIZE: i32 = 8;
+
+const GLYPH_SIZE: i32 = 10;
+/// Size of a font pixel of the title
+const FONT_SCALE: i32 = 2;
+
+const TITLE_ACTIVE: u32 = 0xff30_3030;
+const TITLE_INACTIVE: u32 = 0xff5a_5a5a;
+const FOREGROUND: u32 = 0xffe6_e6e6;
+const OUTLINE: u32 = 0x4000_0000;
+
+/// A title bar button, laid out from right to left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Button {
+    Close,
+    Maximize,
+    Minimize,
+}
+
+struct Canvas {
+    width: i32,
+    height: i32,
+    pixels: Vec<u32>,
+}
+
+impl Canvas {
+    fn new(width: i32, height: i32, color: u32) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: vec![color; (width * height) as usize],
+        }
+    }
+
+    fn fill(&mut self, x: i32, y: i32, width: i32, height: i32, color: u32) {
+        for py in y.max(0)..(y + height).min(self.height) {
+            for px in x.max(0)..(x + width).min(self.width) {
+                self.pixels[(py * self.width + px) as usize] = color;
+            }
+        }
+    }
+}
+
+/// Draw the title bar with the title and its buttons.
+pub(super) fn title_bar(width: i32, active: bool, title: &str, buttons: &[Button]) -> Vec<u32> {
+    let background = if active { TITLE_ACTIVE } else { TITLE_INACTIVE };
+    let mut canvas = Canvas::new(width, TITLE_HEIGHT, background);
+
+    let offset = (TITLE_HEIGHT - GLYPH_SIZE) / 2;
+
+    // The title is cut off at the first button
+    let end = width - buttons.len() as i32 * TITLE_HEIGHT;
+    let advance = (font::GLYPH_WIDTH + 1) * FONT_SCALE;
+    let y = (TITLE_HEIGHT - font::GLYPH_HEIGHT * FONT_SCALE) / 2;
+    let mut x = offset;
+    for c in title.chars() {
+        if x + font::GLYPH_WIDTH * FONT_SCALE > end {
+            break;
+        }
+        for (column, bits) in font::glyph(c).iter().enumerate() {
+            for row in 0..font::GLYPH_HEIGHT {
+                if bits & (1 << row) != 0 {
+                    canvas.fill(
+                        x + column as i32 * FONT_SCALE,
+                        y + row * FONT_SCALE,
+                        FONT_SCALE,
+                        FONT_SCALE,
+                        FOREGROUND,
+                    );
+                }
+            }
+        }
+        x += advance;
+    }
+
+    for (slot, button) in buttons.iter().enumerate() {
+        let x = width - (slot as i32 + 1) * TITLE_HEIGHT + offset;
+        let y = offset;
+        match button {
+            Button::Close => {
+                for i in 0..GLYPH_SIZE {
+                    canvas.fill(x + i, y + i, 2, 1, FOREGROUND);
+                    canvas.fill(x + GLYPH_SIZE - 2 - i, y + i, 2, 1, FOREGROUND);
+                }
+            }
+            Button::Maximize => {
+                canvas.fill(x, y, GLYPH_SIZE, 2, FOREGROUND);
+                canvas.fill(x, y + GLYPH_SIZE - 1, GLYPH_SIZE, 1, FOREGROUND);
+                canvas.fill(x, y, 1, GLYPH_SIZE, FOREGROUND);
+                canvas.fill(x + GLYPH_SIZE - 1, y, 1, GLYPH_SIZE, FOREGROUND);
+            }
+            Button::Minimize => {
+                canvas.fill(x, y + GLYPH_SIZE - 2, GLYPH_SIZE, 2, FOREGROUND);
+            }
+        }
+    }
+
+    canvas.pixels
+}
+
+/// Draw a transparent border with an outline at the given rectangle.
+pub(super) fn border(width: i32, height: i32, outline: (i32, i32, i32, i32)) -> Vec<u32> {
+    let mut canvas = Canvas::new(width, height, 0);
+    canvas.fill(outline.0, outline.1, outline.2, outline.3, OUTLINE);
+    canvas.pixels
+}
+
+fn memfd() -> io::Result<File> {
+    let name = CStr::from_bytes_with_nul(b"libdecor-fallback\0").unwrap();
+    let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(unsafe { File::from_raw_fd(fd) })
+    }
+}
+
+/// Create an ARGB8888 buffer holding the given pixels.
+///
+/// The buffer destroys itself once released by the compositor.
+pub(super) fn buffer(
+    shm: &WlShm,
+    width: i32,
+    height: i32,
+    pixels: &[u32],
+) -> io::Result<Main<WlBuffer>> {
+    let bytes: Vec<u8> = pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_le_bytes())
+        .collect();
+    let mut file = memfd()?;
+    file.write_all(&bytes)?;
+
+    let stride = width * 4;
+    // The file descriptor is duplicated by libwayland and can be closed
+    let pool = shm.create_pool(file.as_raw_fd(), stride * height);
+    let buffer = pool.create_buffer(0, width, height, stride, wl_shm::Format::Argb8888);
+    pool.destroy();
+
+    buffer.quick_assign(|buffer, event, _| {
+        if let wl_buffer::Event::Release = event {
+            buffer.destroy();
+        }
+    });
+
+    Ok(buffer)
+}
@@ -1,13 +1,23 @@
+#[cfg(feature = "libdecor_0_2")]
+use std::cell::Cell;
 use std::{
-    any::Any,
+    cell::RefCell,
     ffi::{c_void, CStr, CString},
+    marker::PhantomData,
     os::raw::c_char,
+    rc::{Rc, Weak},
 };
-use wayland_client::DispatchData;
 use wayland_protocols::xdg_shell::client::{xdg_surface, xdg_toplevel};
 
 use libdecor_sys::*;
 
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
+use crate::fallback::LIBDECOR_HANDLE;
+
+#[cfg(feature = "libdecor_0_2")]
+use crate::Unsupported;
+use crate::{CallbackState, Error};
+
 bitflags::bitflags! {
     /// The possible window states
     pub struct WindowState: libdecor_window_state {
@@ -25,6 +35,11 @@ bitflags::bitflags! {
         const TILED_TOP = LIBDECOR_WINDOW_STATE_TILED_TOP;
         /// Tiled bottom
         const TILED_BOTTOM = LIBDECOR_WINDOW_STATE_TILED_BOTTOM;
+        /// The window is not visible, e.g. minimized or fully occluded
+        ///
+        /// Requires the `libdecor_0_2` feature.
+        #[cfg(feature = "libdecor_0_2")]
+        const SUSPENDED = LIBDECOR_WINDOW_STATE_SUSPENDED;
     }
 
     /// Capabilities of a [`Frame`]
@@ -42,6 +57,37 @@ bitflags::bitflags! {
     }
 }
 
+#[cfg(feature = "libdecor_0_2")]
+bitflags::bitflags! {
+    /// Window management actions supported by the compositor
+    ///
+    /// Contrary to [`Capabilities`], which the application allows, these are
+    /// the actions the compositor is able to perform. Requires the
+    /// `libdecor_0_2` feature.
+    pub struct WmCapabilities: libdecor_wm_capabilities {
+        /// The compositor can show a window menu
+        const WINDOW_MENU = LIBDECOR_WM_CAPABILITIES_WINDOW_MENU;
+        /// The window can be maximized
+        const MAXIMIZE = LIBDECOR_WM_CAPABILITIES_MAXIMIZE;
+        /// The window can be made fullscreen
+        const FULLSCREEN = LIBDECOR_WM_CAPABILITIES_FULLSCREEN;
+        /// The window can be minimized
+        const MINIMIZE = LIBDECOR_WM_CAPABILITIES_MINIMIZE;
+    }
+}
+
+/// The mode used for decorating a window
+///
+/// Only negotiated by the built-in decorations of the `dlopen` feature, see
+/// [`Context::set_decoration_mode`](crate::Context::set_decoration_mode).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationMode {
+    /// The decorations are drawn by the client
+    ClientSide,
+    /// The decorations are drawn by the compositor
+    ServerSide,
+}
+
 /// Defines the edge for an interactive [`FrameRef::resize`]
 #[derive(Debug)]
 pub enum ResizeEdge {
@@ -63,6 +109,8 @@ pub enum ResizeEdge {
     TopRight,
     /// Bottom right corner of the window
     BottomRight,
+    /// An edge unknown to this crate
+    Unknown(u32),
 }
 
 impl From<libdecor_resize_edge> for ResizeEdge {
@@ -77,7 +125,7 @@ impl From<libdecor_resize_edge> for ResizeEdge {
             LIBDECOR_RESIZE_EDGE_RIGHT => Self::Right,
             LIBDECOR_RESIZE_EDGE_TOP_RIGHT => Self::TopRight,
             LIBDECOR_RESIZE_EDGE_BOTTOM_RIGHT => Self::BottomRight,
-            _ => unreachable!(),
+            edge => Self::Unknown(edge),
         }
     }
 }
@@ -94,17 +142,156 @@ impl From<ResizeEdge> for libdecor_resize_edge {
             ResizeEdge::Right => LIBDECOR_RESIZE_EDGE_RIGHT,
             ResizeEdge::TopRight => LIBDECOR_RESIZE_EDGE_TOP_RIGHT,
             ResizeEdge::BottomRight => LIBDECOR_RESIZE_EDGE_BOTTOM_RIGHT,
+            ResizeEdge::Unknown(edge) => edge,
         }
     }
 }
 
-pub(crate) type FrameCallback = dyn FnMut(&FrameRef, &FrameRequest, DispatchData);
+/// A handler for the requests of a [`Frame`].
+///
+/// This is an alternative to the callback passed to [`Context::decorate`](crate::Context::decorate)
+/// which allows to split the handling into separate methods. Use it with
+/// [`Context::decorate_with_handler`](crate::Context::decorate_with_handler).
+///
+/// All methods have a default implementation that ignores the request.
+pub trait FrameHandler<D> {
+    /// A new configuration was received. An application should respond to
+    /// this by creating a suitable [`State`], and apply it using
+    /// [`FrameRef::commit`].
+    fn configure(&mut self, frame: &FrameRef, configuration: &Configuration<'_>, data: &mut D) {
+        let _ = (frame, configuration, data);
+    }
+
+    /// The window was requested to be closed by the compositor.
+    fn close(&mut self, frame: &FrameRef, data: &mut D) {
+        let _ = (frame, data);
+    }
+
+    /// The window decoration asked to have the main surface to be
+    /// committed. This is required when the decoration is implemented using
+    /// synchronous subsurfaces.
+    fn commit(&mut self, frame: &FrameRef, data: &mut D) {
+        let _ = (frame, data);
+    }
+
+    /// Any mapped popup that has a grab on the given seat should be
+    /// dismissed.
+    fn dismiss_popup(&mut self, frame: &FrameRef, seat_name: &str, data: &mut D) {
+        let _ = (frame, seat_name, data);
+    }
+
+    /// An error attributed to this frame was reported by libdecor, e.g.
+    /// an invalid frame configuration on commit.
+    ///
+    /// The error is also passed to the callback of the [`Context`](crate::Context).
+    fn error(&mut self, frame: &FrameRef, error: &Error, data: &mut D) {
+        let _ = (frame, error, data);
+    }
+
+    /// The decoration mode of the window was negotiated with the compositor.
+    ///
+    /// Only reported by the built-in decorations of the `dlopen` feature,
+    /// never when libdecor is in use, see
+    /// [`Context::set_decoration_mode`](crate::Context::set_decoration_mode).
+    fn decoration_mode(&mut self, frame: &FrameRef, mode: DecorationMode, data: &mut D) {
+        let _ = (frame, mode, data);
+    }
+
+    /// The actions supported by the compositor changed.
+    ///
+    /// Called before the configuration the change was received with, and
+    /// before the first configuration. Requires the `libdecor_0_2` feature.
+    #[cfg(feature = "libdecor_0_2")]
+    fn wm_capabilities(&mut self, frame: &FrameRef, capabilities: WmCapabilities, data: &mut D) {
+        let _ = (frame, capabilities, data);
+    }
+}
+
+/// Adapter for using a callback as a [`FrameHandler`]
+pub(crate) struct CallbackHandler<C>(pub(crate) C);
+
+impl<C, D> FrameHandler<D> for CallbackHandler<C>
+where
+    C: FnMut(&FrameRef, &FrameRequest<'_>, &mut D),
+{
+    fn configure(&mut self, frame: &FrameRef, configuration: &Configuration<'_>, data: &mut D) {
+        let request = FrameRequest::Configure(configuration.reborrow());
+        (self.0)(frame, &request, data)
+    }
+
+    fn close(&mut self, frame: &FrameRef, data: &mut D) {
+        (self.0)(frame, &FrameRequest::Close, data)
+    }
+
+    fn commit(&mut self, frame: &FrameRef, data: &mut D) {
+        (self.0)(frame, &FrameRequest::Commit, data)
+    }
+
+    fn dismiss_popup(&mut self, frame: &FrameRef, seat_name: &str, data: &mut D) {
+        let request = FrameRequest::DismissPopup {
+            seat_name: seat_name.to_owned(),
+        };
+        (self.0)(frame, &request, data)
+    }
+
+    fn error(&mut self, frame: &FrameRef, error: &Error, data: &mut D) {
+        (self.0)(frame, &FrameRequest::Error(error.clone()), data)
+    }
+
+    fn decoration_mode(&mut self, frame: &FrameRef, mode: DecorationMode, data: &mut D) {
+        (self.0)(frame, &FrameRequest::DecorationMode(mode), data)
+    }
+
+    #[cfg(feature = "libdecor_0_2")]
+    fn wm_capabilities(&mut self, frame: &FrameRef, capabilities: WmCapabilities, data: &mut D) {
+        (self.0)(frame, &FrameRequest::WmCapabilities(capabilities), data)
+    }
+}
 
 /// An object representing a toplevel window configuration.
+///
+/// The configuration is owned by libdecor and only valid for the duration of
+/// the configure callback it was passed to. Use [`snapshot`](#method.snapshot)
+/// to keep the configured values around for later use.
 #[derive(Debug)]
-pub struct Configuration(*mut libdecor_configuration);
+pub struct Configuration<'a> {
+    pub(crate) ptr: *mut libdecor_configuration,
+    _marker: PhantomData<&'a libdecor_configuration>,
+}
+
+impl<'a> Configuration<'a> {
+    /// Wrap a configuration received in a configure callback.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` has to be valid for the lifetime `'a`.
+    pub(crate) unsafe fn from_ptr(ptr: *mut libdecor_configuration) -> Self {
+        Self {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+
+    fn reborrow(&self) -> Configuration<'_> {
+        Configuration {
+            ptr: self.ptr,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Take an owned snapshot of this configuration for the given frame.
+    ///
+    /// The snapshot can be kept after the configure callback has returned,
+    /// but can not be committed. The configuration itself has to be committed
+    /// with [`FrameRef::commit`] inside of the callback.
+    pub fn snapshot(&self, frame: &FrameRef) -> ConfigurationSnapshot {
+        ConfigurationSnapshot {
+            frame: frame.id(),
+            content_size: self.content_size(frame),
+            window_state: self.window_state(),
+        }
+    }
 
-impl Configuration {
     /// Get the expected size of the content for this configuration.
     ///
     /// If the configuration doesn't contain a size, [`None`] is returned.
@@ -116,7 +303,7 @@ impl Configuration {
             ffi_dispatch!(
                 LIBDECOR_HANDLE,
                 libdecor_configuration_get_content_size,
-                self.0,
+                self.ptr,
                 frame.0,
                 &mut width,
                 &mut height
@@ -142,19 +329,47 @@ impl Configuration {
             ffi_dispatch!(
                 LIBDECOR_HANDLE,
                 libdecor_configuration_get_window_state,
-                self.0,
+                self.ptr,
                 &mut window_state
             )
         };
 
         if has_window_state && window_state != LIBDECOR_WINDOW_STATE_NONE {
-            Some(WindowState::from_bits(window_state).unwrap())
+            // libdecor 0.2 reports states unknown without the `libdecor_0_2` feature
+            Some(WindowState::from_bits_truncate(window_state))
         } else {
             None
         }
     }
 }
 
+/// An owned snapshot of a [`Configuration`]
+///
+/// Created by [`Configuration::snapshot`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigurationSnapshot {
+    frame: FrameId,
+    content_size: Option<(i32, i32)>,
+    window_state: Option<WindowState>,
+}
+
+impl ConfigurationSnapshot {
+    /// The identifier of the frame the configuration belongs to
+    pub fn frame(&self) -> FrameId {
+        self.frame
+    }
+
+    /// The expected size of the content, see [`Configuration::content_size`]
+    pub fn content_size(&self) -> Option<(i32, i32)> {
+        self.content_size
+    }
+
+    /// The window state, see [`Configuration::window_state`]
+    pub fn window_state(&self) -> Option<WindowState> {
+        self.window_state
+    }
+}
+
 /// An object corresponding to a configured content state.
 #[derive(Debug)]
 pub struct State(*mut libdecor_state);
@@ -173,17 +388,113 @@ impl Drop for State {
     }
 }
 
-fn invoke_frame_callback(
-    frame: *mut libdecor_frame,
-    user_data: *mut c_void,
-    request: FrameRequest,
-) {
-    assert!(crate::DISPATCH_METADATA.is_set());
+/// Type erased access to the [`FrameHandler`] of a [`Frame`] for the trampolines
+pub(crate) trait FrameDispatch {
+    fn configure(&self, frame: &FrameRef, configuration: &Configuration<'_>);
+    fn close(&self, frame: &FrameRef);
+    fn commit(&self, frame: &FrameRef);
+    fn dismiss_popup(&self, frame: &FrameRef, seat_name: &str);
+    fn error(&self, frame: &FrameRef, error: &Error);
+    #[cfg(all(feature = "dlopen", not(feature = "mock")))]
+    fn decoration_mode(&self, frame: &FrameRef, mode: DecorationMode);
+    fn callback_state(&self) -> &CallbackState;
+}
+
+pub(crate) type SharedFrameHandler<D> = Rc<RefCell<dyn FrameHandler<D>>>;
+
+pub(crate) struct FrameState<D> {
+    pub(crate) handler: SharedFrameHandler<D>,
+    pub(crate) slot: Rc<crate::DispatchSlot<D>>,
+    /// Last reported to the handler
+    #[cfg(feature = "libdecor_0_2")]
+    pub(crate) wm_capabilities: Cell<Option<WmCapabilities>>,
+}
+
+impl<D: 'static> FrameState<D> {
+    /// Invoke the handler, deferring the invocation if another callback
+    /// is currently running.
+    fn invoke<F>(&self, frame: &FrameRef, f: F)
+    where
+        F: FnOnce(&mut dyn FrameHandler<D>, &FrameRef, &mut D) + 'static,
+    {
+        let handler: Weak<RefCell<dyn FrameHandler<D>>> = Rc::downgrade(&self.handler);
+        let frame = frame.0;
+        self.slot.invoke(move |data| {
+            // The frame has been dropped in the meantime
+            if let Some(handler) = handler.upgrade() {
+                f(&mut *handler.borrow_mut(), &FrameRef(frame), data)
+            }
+        })
+    }
+}
+
+impl<D: 'static> FrameDispatch for FrameState<D> {
+    fn configure(&self, frame: &FrameRef, configuration: &Configuration<'_>) {
+        // The configuration can not be deferred, it is only valid for the
+        // duration of the callback.
+        let _ = self.slot.with(|data| {
+            let mut handler = self.handler.borrow_mut();
+
+            // libdecor updates the capabilities when receiving them,
+            // they are only applied with the following configuration
+            #[cfg(feature = "libdecor_0_2")]
+            if let Ok(wm_capabilities) = frame.wm_capabilities() {
+                if self.wm_capabilities.replace(Some(wm_capabilities)) != Some(wm_capabilities) {
+                    handler.wm_capabilities(frame, wm_capabilities, data);
+                }
+            }
+
+            handler.configure(frame, configuration, data)
+        });
+    }
+
+    fn close(&self, frame: &FrameRef) {
+        self.invoke(frame, |handler, frame, data| handler.close(frame, data))
+    }
+
+    fn commit(&self, frame: &FrameRef) {
+        self.invoke(frame, |handler, frame, data| handler.commit(frame, data))
+    }
+
+    fn dismiss_popup(&self, frame: &FrameRef, seat_name: &str) {
+        let seat_name = seat_name.to_owned();
+        self.invoke(frame, move |handler, frame, data| {
+            handler.dismiss_popup(frame, &seat_name, data)
+        })
+    }
+
+    fn error(&self, frame: &FrameRef, error: &Error) {
+        let error = error.clone();
+        self.invoke(frame, move |handler, frame, data| {
+            handler.error(frame, &error, data)
+        })
+    }
 
-    let callback = unsafe { &mut *(user_data as *mut std::boxed::Box<FrameCallback>) };
+    #[cfg(all(feature = "dlopen", not(feature = "mock")))]
+    fn decoration_mode(&self, frame: &FrameRef, mode: DecorationMode) {
+        self.invoke(frame, move |handler, frame, data| {
+            handler.decoration_mode(frame, mode, data)
+        })
+    }
+
+    fn callback_state(&self) -> &CallbackState {
+        &self.slot.state
+    }
+}
+
+fn invoke_frame_dispatch<F>(frame: *mut libdecor_frame, user_data: *mut c_void, f: F)
+where
+    F: FnOnce(&dyn FrameDispatch, &FrameRef),
+{
+    let dispatch: &dyn FrameDispatch = unsafe { &**(user_data as *const Box<dyn FrameDispatch>) };
     let frame_ref = FrameRef(frame);
 
-    crate::DISPATCH_METADATA.with(|ddata| callback(&frame_ref, &request, ddata.get().reborrow()));
+    // Unwinding into libdecor is undefined behavior, the panic is
+    // resumed once the dispatch returns
+    let state = dispatch.callback_state();
+    state
+        .panic
+        .catch(|| state.with_frame(frame, dispatch, || f(dispatch, &frame_ref)))
 }
 
 extern "C" fn configure_callback_trampolin(
@@ -191,19 +502,19 @@ extern "C" fn configure_callback_trampolin(
     configuration: *mut libdecor_configuration,
     user_data: *mut c_void,
 ) {
-    invoke_frame_callback(
-        frame,
-        user_data,
-        FrameRequest::Configure(Configuration(configuration)),
-    )
+    // SAFETY: The configuration is valid until the callback returns
+    let configuration = unsafe { Configuration::from_ptr(configuration) };
+    invoke_frame_dispatch(frame, user_data, |dispatch, frame| {
+        dispatch.configure(frame, &configuration)
+    })
 }
 
 extern "C" fn close_callback_trampolin(frame: *mut libdecor_frame, user_data: *mut c_void) {
-    invoke_frame_callback(frame, user_data, FrameRequest::Close)
+    invoke_frame_dispatch(frame, user_data, |dispatch, frame| dispatch.close(frame))
 }
 
 extern "C" fn commit_callback_trampolin(frame: *mut libdecor_frame, user_data: *mut c_void) {
-    invoke_frame_callback(frame, user_data, FrameRequest::Commit)
+    invoke_frame_dispatch(frame, user_data, |dispatch, frame| dispatch.commit(frame))
 }
 
 extern "C" fn dismiss_popup_callback_trampolin(
@@ -213,23 +524,31 @@ extern "C" fn dismiss_popup_callback_trampolin(
 ) {
     let seat_name = unsafe { CStr::from_ptr(seat_name) };
 
-    invoke_frame_callback(
-        frame,
-        user_data,
-        FrameRequest::DismissPopup {
-            seat_name: seat_name.to_str().unwrap().to_owned(),
-        },
-    )
+    invoke_frame_dispatch(frame, user_data, |dispatch, frame| {
+        dispatch.dismiss_popup(frame, &seat_name.to_string_lossy())
+    })
+}
+
+/// Called by the fallback decorations, libdecor has no equivalent callback
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
+pub(crate) fn decoration_mode_callback_trampolin(
+    frame: *mut libdecor_frame,
+    mode: DecorationMode,
+    user_data: *mut c_void,
+) {
+    invoke_frame_dispatch(frame, user_data, |dispatch, frame| {
+        dispatch.decoration_mode(frame, mode)
+    })
 }
 
 /// Possible variants for the [`Frame`] callback
 #[derive(Debug)]
 #[non_exhaustive]
-pub enum FrameRequest {
+pub enum FrameRequest<'a> {
     /// A new configuration was received. An application should respond to
     /// this by creating a suitable [`State`], and apply it using
     /// [`FrameRef::commit`].
-    Configure(Configuration),
+    Configure(Configuration<'a>),
     /// The window was requested to be closed by the compositor.
     Close,
     /// The window decoration asked to have the main surface to be
@@ -242,6 +561,21 @@ pub enum FrameRequest {
         /// The name of the seat
         seat_name: String,
     },
+    /// An error attributed to this frame was reported by libdecor.
+    ///
+    /// The error is also passed to the callback of the [`Context`](crate::Context).
+    Error(Error),
+    /// The decoration mode of the window was negotiated with the compositor.
+    ///
+    /// Only reported by the built-in decorations of the `dlopen` feature,
+    /// never when libdecor is in use, see
+    /// [`Context::set_decoration_mode`](crate::Context::set_decoration_mode).
+    DecorationMode(DecorationMode),
+    /// The actions supported by the compositor changed.
+    ///
+    /// See [`FrameHandler::wm_capabilities`].
+    #[cfg(feature = "libdecor_0_2")]
+    WmCapabilities(WmCapabilities),
 }
 
 pub(crate) static LIBDECOR_FRAME_INTERFACE: libdecor_frame_interface = libdecor_frame_interface {
@@ -262,10 +596,19 @@ pub(crate) static LIBDECOR_FRAME_INTERFACE: libdecor_frame_interface = libdecor_
 };
 
 /// A reference to a [`Frame`] used for decorating a Wayland surface.
+///
+/// A [`FrameRef`] is only ever borrowed for the duration of a callback or
+/// [`Frame::dispatch`]. Use a [`FrameHandle`] or [`WeakFrameHandle`] to
+/// keep a reference to a frame around.
 #[derive(Debug)]
 pub struct FrameRef(pub(crate) *mut libdecor_frame);
 
 impl FrameRef {
+    /// The identifier of the referenced frame
+    pub fn id(&self) -> FrameId {
+        FrameId(self.0 as usize)
+    }
+
     /// Close the window.
     ///
     /// Roughly translates to [`xdg_toplevel::Event::Close`].
@@ -476,6 +819,63 @@ impl FrameRef {
         }
     }
 
+    /// Get the window management actions supported by the compositor.
+    ///
+    /// Only known once the frame has been configured, all actions are
+    /// reported as supported before. Requires the `libdecor_0_2` feature.
+    ///
+    /// Returns [`Unsupported`] if the loaded libdecor is older than 0.2.
+    #[cfg(feature = "libdecor_0_2")]
+    pub fn wm_capabilities(&self) -> Result<WmCapabilities, Unsupported> {
+        let capabilities = unsafe {
+            ffi_dispatch_optional!(LIBDECOR_HANDLE, libdecor_frame_get_wm_capabilities, self.0)
+        }
+        .ok_or(Unsupported("libdecor_frame_get_wm_capabilities"))?;
+        Ok(WmCapabilities::from_bits_truncate(capabilities))
+    }
+
+    /// Get the min content size.
+    ///
+    /// Requires the `libdecor_0_2` feature.
+    ///
+    /// Returns [`Unsupported`] if the loaded libdecor is older than 0.2.
+    #[cfg(feature = "libdecor_0_2")]
+    pub fn min_content_size(&self) -> Result<(i32, i32), Unsupported> {
+        let (mut width, mut height) = (0, 0);
+        unsafe {
+            ffi_dispatch_optional!(
+                LIBDECOR_HANDLE,
+                libdecor_frame_get_min_content_size,
+                self.0,
+                &mut width,
+                &mut height
+            )
+        }
+        .ok_or(Unsupported("libdecor_frame_get_min_content_size"))?;
+        Ok((width, height))
+    }
+
+    /// Get the max content size.
+    ///
+    /// A dimension of `0` is unbounded. Requires the `libdecor_0_2` feature.
+    ///
+    /// Returns [`Unsupported`] if the loaded libdecor is older than 0.2.
+    #[cfg(feature = "libdecor_0_2")]
+    pub fn max_content_size(&self) -> Result<(i32, i32), Unsupported> {
+        let (mut width, mut height) = (0, 0);
+        unsafe {
+            ffi_dispatch_optional!(
+                LIBDECOR_HANDLE,
+                libdecor_frame_get_max_content_size,
+                self.0,
+                &mut width,
+                &mut height
+            )
+        }
+        .ok_or(Unsupported("libdecor_frame_get_max_content_size"))?;
+        Ok((width, height))
+    }
+
     /// Initiate an interactive resize.
     ///
     /// This roughly translates to [`xdg_toplevel::XdgToplevel::resize`].
@@ -515,9 +915,9 @@ impl FrameRef {
     /// Commit a new window state. This can be called on application driven resizes
     /// when the window is floating, or in response to received configurations, i.e.
     /// from e.g. interactive resizes or state changes.
-    pub fn commit(&self, state: &State, configuration: Option<&Configuration>) {
+    pub fn commit(&self, state: &State, configuration: Option<&Configuration<'_>>) {
         let configuration = configuration
-            .map(|c| c.0)
+            .map(|c| c.ptr)
             .unwrap_or_else(std::ptr::null_mut);
         unsafe {
             ffi_dispatch!(
@@ -630,34 +1030,209 @@ impl FrameRef {
     }
 }
 
+/// An opaque identifier of a frame.
+///
+/// Can be used to associate owned events, e.g. a [`ConfigurationSnapshot`],
+/// with the [`Frame`] they belong to. An identifier may be reused once the
+/// frame it belonged to has been destroyed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameId(usize);
+
+/// State shared by a [`Frame`] and all of its handles
+struct FrameShared<D> {
+    dispatch: *mut Box<dyn FrameDispatch>,
+    context: crate::Context<D>,
+}
+
+impl<D> Drop for FrameShared<D> {
+    fn drop(&mut self) {
+        // Only reached after the last reference of the frame has been
+        // released, so libdecor can no longer invoke any callbacks.
+        let _ = unsafe { Box::from_raw(self.dispatch) };
+    }
+}
+
 /// A frame used for decorating a Wayland surface.
-#[derive(Debug)]
-pub struct Frame {
-    pub(crate) frame_ref: FrameRef,
-    pub(crate) cb: *mut Box<FrameCallback>,
-    pub(crate) _context: crate::Context,
+///
+/// The type parameter `D` is the application data passed to the
+/// callbacks of the frame, see [`Context`](crate::Context).
+///
+/// Dropping the frame destroys the window, unless there are still
+/// [`FrameHandle`]s referencing it. In that case the window is destroyed
+/// once the last handle has been dropped.
+pub struct Frame<D = ()> {
+    handle: FrameHandle<D>,
+    alive: Rc<()>,
 }
 
-impl Frame {
+impl<D> std::fmt::Debug for Frame<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Frame")
+            .field("frame_ref", &self.handle.frame_ref)
+            .field("context", &self.handle.shared.context)
+            .finish()
+    }
+}
+
+impl<D> Frame<D> {
+    /// Take ownership of a frame returned by `libdecor_decorate`.
+    pub(crate) fn new(
+        frame: *mut libdecor_frame,
+        dispatch: *mut Box<dyn FrameDispatch>,
+        context: crate::Context<D>,
+    ) -> Self {
+        Frame {
+            handle: FrameHandle {
+                frame_ref: FrameRef(frame),
+                shared: Rc::new(FrameShared { dispatch, context }),
+            },
+            alive: Rc::new(()),
+        }
+    }
+
     /// Dispatch a call to a [`FrameRef`]
     ///
     /// This is necessary because invoking a method on the [`FrameRef`] can invoke
-    /// the callback synchronously which needs access to the dispatch data.
-    pub fn dispatch<T, F, R>(&self, ddata: &mut T, f: F) -> R
+    /// the callback synchronously which needs access to the application data.
+    ///
+    /// A panic in a callback invoked synchronously is resumed once `f` has
+    /// returned.
+    pub fn dispatch<F, R>(&self, data: &mut D, f: F) -> R
     where
-        T: Any,
         F: FnOnce(&FrameRef) -> R,
     {
-        let ddata = unsafe { std::mem::transmute(ddata) };
-        let ddata = DispatchData::wrap::<T>(ddata);
-        let ddata_mut = crate::DispatchDataMut::new(ddata);
-        crate::DISPATCH_METADATA.set(&ddata_mut, || f(&self.frame_ref))
+        self.handle.dispatch(data, f)
+    }
+
+    /// The identifier of this frame
+    pub fn id(&self) -> FrameId {
+        self.handle.id()
+    }
+
+    /// Create a new strong [`FrameHandle`] keeping this frame alive.
+    pub fn handle(&self) -> FrameHandle<D> {
+        self.handle.clone()
+    }
+
+    /// Create a new [`WeakFrameHandle`] to this frame.
+    ///
+    /// The handle can only be upgraded as long as this [`Frame`] is alive.
+    pub fn downgrade(&self) -> WeakFrameHandle<D> {
+        WeakFrameHandle {
+            frame: self.handle.frame_ref.0,
+            alive: Rc::downgrade(&self.alive),
+            shared: Rc::downgrade(&self.handle.shared),
+        }
+    }
+}
+
+/// A reference counted handle to a [`Frame`].
+///
+/// Created by [`Frame::handle`] or [`WeakFrameHandle::upgrade`]. Every handle
+/// holds a libdecor reference of the frame, so the frame stays valid for as
+/// long as the handle exists, even after the [`Frame`] has been dropped.
+pub struct FrameHandle<D = ()> {
+    frame_ref: FrameRef,
+    shared: Rc<FrameShared<D>>,
+}
+
+impl<D> std::fmt::Debug for FrameHandle<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameHandle")
+            .field("frame_ref", &self.frame_ref)
+            .finish()
     }
 }
 
-impl Drop for Frame {
+impl<D> FrameHandle<D> {
+    /// Dispatch a call to a [`FrameRef`], see [`Frame::dispatch`].
+    pub fn dispatch<F, R>(&self, data: &mut D, f: F) -> R
+    where
+        F: FnOnce(&FrameRef) -> R,
+    {
+        let slot = self.shared.context.slot();
+        // SAFETY: The dispatch is only freed together with `shared`
+        let dispatch: &(dyn FrameDispatch + 'static) = unsafe { &**self.shared.dispatch };
+        slot.set(data, || {
+            slot.state
+                .with_frame(self.frame_ref.0, dispatch, || f(&self.frame_ref))
+        })
+    }
+
+    /// The identifier of the referenced frame
+    pub fn id(&self) -> FrameId {
+        self.frame_ref.id()
+    }
+}
+
+impl<D> Clone for FrameHandle<D> {
+    fn clone(&self) -> Self {
+        unsafe { ffi_dispatch!(LIBDECOR_HANDLE, libdecor_frame_ref, self.frame_ref.0) }
+        FrameHandle {
+            frame_ref: FrameRef(self.frame_ref.0),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<D> Drop for FrameHandle<D> {
     fn drop(&mut self) {
         unsafe { ffi_dispatch!(LIBDECOR_HANDLE, libdecor_frame_unref, self.frame_ref.0) }
-        let _ = unsafe { Box::from_raw(self.cb) };
+    }
+}
+
+/// A weak handle to a [`Frame`].
+///
+/// Created by [`Frame::downgrade`]. The handle does not keep the frame alive
+/// and can be stored e.g. in the state of another window to refer to this
+/// frame without creating a reference cycle.
+pub struct WeakFrameHandle<D = ()> {
+    frame: *mut libdecor_frame,
+    alive: Weak<()>,
+    shared: Weak<FrameShared<D>>,
+}
+
+impl<D> std::fmt::Debug for WeakFrameHandle<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeakFrameHandle")
+            .field("frame", &self.frame)
+            .field("alive", &self.is_alive())
+            .finish()
+    }
+}
+
+impl<D> Clone for WeakFrameHandle<D> {
+    fn clone(&self) -> Self {
+        WeakFrameHandle {
+            frame: self.frame,
+            alive: self.alive.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<D> WeakFrameHandle<D> {
+    /// Try to upgrade to a strong [`FrameHandle`].
+    ///
+    /// Returns [`None`] if the [`Frame`] has already been dropped.
+    pub fn upgrade(&self) -> Option<FrameHandle<D>> {
+        let _alive = self.alive.upgrade()?;
+        let shared = self.shared.upgrade()?;
+
+        unsafe { ffi_dispatch!(LIBDECOR_HANDLE, libdecor_frame_ref, self.frame) }
+        Some(FrameHandle {
+            frame_ref: FrameRef(self.frame),
+            shared,
+        })
+    }
+
+    /// Returns [`true`] if the [`Frame`] has not been dropped yet.
+    pub fn is_alive(&self) -> bool {
+        self.alive.strong_count() > 0
+    }
+
+    /// The identifier of the referenced frame
+    pub fn id(&self) -> FrameId {
+        FrameId(self.frame as usize)
     }
 }
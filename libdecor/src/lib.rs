@@ -98,6 +98,47 @@
 //! #     .unwrap();
 //! ```
 //!
+//! ## Queue frame events
+//!
+//! Instead of handling the frame requests in a callback, the events can be queued
+//! as owned [`FrameEvent`]s and drained at any later point in time.
+//!
+//! ```no_run
+//! # use libdecor::{Context, FrameEvent, Request};
+//! # use wayland_client::{protocol::wl_compositor, Display, GlobalManager};
+//! #
+//! # let display = Display::connect_to_env().unwrap();
+//! # let mut event_queue = display.create_event_queue();
+//! # let attached_display = (*display).clone().attach(event_queue.token());
+//! # let globals = GlobalManager::new(&attached_display);
+//! # event_queue
+//! #     .sync_roundtrip(&mut (), |_, _, _| unreachable!())
+//! #     .unwrap();
+//! # let compositor = globals
+//! #     .instantiate_exact::<wl_compositor::WlCompositor>(4)
+//! #     .expect("Failed to instantiate wl_compositor");
+//! # let content_surface = compositor.create_surface();
+//! # let context = Context::new(display, |_| {});
+//! #
+//! let (frame, events) = context
+//!     .decorate_with_queue(content_surface.detach(), (800, 600))
+//!     .expect("Failed to create frame");
+//!
+//! frame.dispatch(&mut (), |f| f.map());
+//!
+//! while context.dispatch(&mut (), None).is_ok() {
+//!     for event in events.try_iter() {
+//!         match event {
+//!             FrameEvent::Configure { content_size, window_state } => {
+//!                 // Draw surface content
+//!             }
+//!             FrameEvent::Close => return,
+//!             _ => {}
+//!         }
+//!     }
+//! }
+//! ```
+//!
 //! # Example
 //!
 //! For a more complete example see [demo.rs](https://github.com/cmeissl/libdecor-rs/blob/main/libdecor/examples/demo.rs)
@@ -198,7 +239,7 @@
 //!     f.map();
 //! });
 //!
-//! while context.dispatch(&mut (), Some(Duration::from_millis(16))) {
+//! while context.dispatch(&mut (), Some(Duration::from_millis(16))).is_ok() {
 //!     event_queue
 //!         .dispatch(&mut (), |_, _, _| unreachable!())
 //!         .unwrap();
@@ -208,38 +249,224 @@
 
 #![warn(missing_docs, missing_debug_implementations)]
 
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    panic::{self, AssertUnwindSafe},
+    ptr::NonNull,
+    rc::Rc,
+};
+
 pub use libdecor_sys as ffi;
+pub use libdecor_sys::LibdecorVersion;
 
+#[cfg(any(feature = "tokio", feature = "async-io"))]
+mod async_context;
 mod context;
+#[cfg(feature = "calloop")]
+mod event_source;
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
+mod fallback;
 mod frame;
+#[cfg(feature = "plugin")]
+pub mod plugin;
+mod queue;
+mod selection;
 
+#[cfg(any(feature = "tokio", feature = "async-io"))]
+pub use async_context::*;
 pub use context::*;
+#[cfg(feature = "calloop")]
+pub use event_source::*;
 pub use frame::*;
-use wayland_client::DispatchData;
+pub use queue::{FrameEvent, FrameEventReceiver, TryIter, TryRecvError};
+pub use selection::{ActivePlugin, PluginInfo};
+
+type DeferredCallback<D> = Box<dyn FnOnce(&mut D)>;
+
+/// Holds a panic caught in a callback invoked by libdecor.
+///
+/// Unwinding through the C stack of libdecor is undefined behavior, so
+/// panics are caught in the trampolines and resumed once control is back
+/// in Rust, see [`DispatchSlot::set`].
+#[derive(Default)]
+pub(crate) struct PanicSlot(Cell<Option<Box<dyn Any + Send>>>);
 
-scoped_tls::scoped_thread_local!(pub(crate) static DISPATCH_METADATA: DispatchDataMut);
+impl PanicSlot {
+    /// Invoke `f`, catching a panic. Only the first caught panic is kept.
+    pub(crate) fn catch<F>(&self, f: F)
+    where
+        F: FnOnce(),
+    {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+            let previous = self.0.take();
+            self.0.set(previous.or(Some(payload)));
+        }
+    }
 
-struct DispatchDataMut<'a> {
-    ddata: *mut DispatchData<'a>,
+    /// Resume a previously caught panic.
+    pub(crate) fn resume(&self) {
+        if let Some(payload) = self.0.take() {
+            panic::resume_unwind(payload)
+        }
+    }
 }
 
-impl<'a> DispatchDataMut<'a> {
-    fn new(ddata: DispatchData<'a>) -> Self {
-        Self {
-            ddata: Box::into_raw(Box::new(ddata)),
+impl std::fmt::Debug for PanicSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PanicSlot").finish()
+    }
+}
+
+/// A frame currently in use, see [`CallbackState::with_frame`]
+type TrackedFrame = (*mut ffi::libdecor_frame, *const dyn frame::FrameDispatch);
+
+/// State shared between a [`Context`] and the trampolines of its callbacks
+#[derive(Debug, Default)]
+pub(crate) struct CallbackState {
+    pub(crate) panic: PanicSlot,
+    frames: RefCell<Vec<TrackedFrame>>,
+}
+
+impl CallbackState {
+    /// Track `frame` as being in use for the duration of `f`.
+    ///
+    /// Errors reported by libdecor while a frame is in use, e.g. an invalid
+    /// frame configuration on commit, are attributed to this frame.
+    pub(crate) fn with_frame<F, R>(
+        &self,
+        frame: *mut ffi::libdecor_frame,
+        dispatch: &(dyn frame::FrameDispatch + 'static),
+        f: F,
+    ) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        struct Untrack<'a>(&'a RefCell<Vec<TrackedFrame>>);
+
+        impl<'a> Drop for Untrack<'a> {
+            fn drop(&mut self) {
+                self.0.borrow_mut().pop();
+            }
         }
+
+        self.frames.borrow_mut().push((frame, dispatch));
+        let _untrack = Untrack(&self.frames);
+        f()
     }
 
-    #[allow(clippy::mut_from_ref)]
-    fn get(&self) -> &mut DispatchData<'a> {
-        unsafe { &mut *(self.ddata) }
+    /// The innermost frame currently in use
+    pub(crate) fn current_frame(&self) -> Option<TrackedFrame> {
+        self.frames.borrow().last().copied()
     }
 }
 
-impl<'a> Drop for DispatchDataMut<'a> {
+/// Holds the application data while a [`Context`] or [`Frame`] is dispatched.
+///
+/// While a callback is running the data is taken out of the slot. Callbacks
+/// that are invoked re-entrant, e.g. a commit request triggered by calling
+/// [`FrameRef::commit`] inside the configure callback, are deferred until
+/// the running callback has returned.
+pub(crate) struct DispatchSlot<D> {
+    data: Cell<Option<NonNull<D>>>,
+    depth: Cell<usize>,
+    deferred: RefCell<VecDeque<DeferredCallback<D>>>,
+    pub(crate) state: Rc<CallbackState>,
+}
+
+/// Restores the previous state of a [`DispatchSlot`], also when unwinding
+struct Restore<'a, D> {
+    slot: &'a DispatchSlot<D>,
+    data: Option<NonNull<D>>,
+    depth: usize,
+}
+
+impl<'a, D> Drop for Restore<'a, D> {
     fn drop(&mut self) {
-        unsafe {
-            let _ = Box::from_raw(self.ddata);
+        self.slot.depth.set(self.depth);
+        self.slot.data.set(self.data);
+    }
+}
+
+impl<D> DispatchSlot<D> {
+    pub(crate) fn new(state: Rc<CallbackState>) -> Self {
+        Self {
+            data: Cell::new(None),
+            depth: Cell::new(0),
+            deferred: RefCell::new(VecDeque::new()),
+            state,
+        }
+    }
+
+    /// Make the data available to callbacks for the duration of `f`.
+    ///
+    /// A panic caught in any callback invoked during `f` is resumed after
+    /// `f` has returned.
+    pub(crate) fn set<F, R>(&self, data: &mut D, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let result = {
+            let _restore = Restore {
+                slot: self,
+                data: self.data.replace(Some(NonNull::from(data))),
+                depth: self.depth.replace(self.depth.get() + 1),
+            };
+            f()
+        };
+        self.state.panic.resume();
+        result
+    }
+
+    /// Invoke `f` with the data if it is currently available.
+    ///
+    /// Returns [`None`] if another callback is currently running.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slot is not dispatched.
+    pub(crate) fn with<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut D) -> R,
+    {
+        assert!(
+            self.depth.get() > 0,
+            "libdecor callback invoked outside of dispatch"
+        );
+
+        let mut data = self.data.take()?;
+        let _restore = Restore {
+            slot: self,
+            data: Some(data),
+            depth: self.depth.get(),
+        };
+
+        // SAFETY: The pointer was created from a mutable reference in `set`
+        // which outlives the dispatch. It has been taken out of the slot, so
+        // no other reference can be created until it is restored.
+        let result = f(unsafe { data.as_mut() });
+
+        loop {
+            let deferred = self.deferred.borrow_mut().pop_front();
+            match deferred {
+                Some(deferred) => deferred(unsafe { data.as_mut() }),
+                None => break,
+            }
+        }
+
+        Some(result)
+    }
+
+    /// Invoke `f` with the data, or defer it until the currently
+    /// running callback has returned.
+    pub(crate) fn invoke<F>(&self, f: F)
+    where
+        F: FnOnce(&mut D) + 'static,
+    {
+        let mut f = Some(f);
+        if self.with(|data| (f.take().unwrap())(data)).is_none() {
+            self.deferred.borrow_mut().push_back(Box::new(f.unwrap()));
         }
     }
 }
@@ -253,3 +480,45 @@ impl<'a> Drop for DispatchDataMut<'a> {
 pub fn is_lib_available() -> bool {
     ffi::is_lib_available()
 }
+
+/// Check if the built-in fallback decorations are used.
+///
+/// With the `dlopen` feature contexts fall back to simple decorations
+/// drawn by this crate if the `libdecor` library is not available.
+#[cfg(feature = "dlopen")]
+pub fn uses_fallback() -> bool {
+    !ffi::is_lib_available()
+}
+
+/// Returns the version of the libdecor API provided by the library.
+///
+/// With the `dlopen` feature the version of the loaded library is probed
+/// at runtime and [`None`] is returned if the library is not available.
+/// Without it this is the version the crate has been built for.
+pub fn libdecor_version() -> Option<LibdecorVersion> {
+    ffi::libdecor_version()
+}
+
+/// The loaded libdecor lacks a function
+///
+/// Returned with the `dlopen` feature by functions requiring a newer
+/// libdecor than available at runtime, see [`libdecor_version`], and by
+/// functions only backed by the built-in decorations like
+/// [`Context::set_decoration_mode`] when libdecor is in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unsupported(pub(crate) &'static str);
+
+impl Unsupported {
+    /// Returns the name of the missing libdecor function.
+    pub fn function(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is not supported by the loaded libdecor", self.0)
+    }
+}
+
+impl std::error::Error for Unsupported {}
@@ -0,0 +1,800 @@
+//! Decoration plugins written in Rust
+//!
+//! libdecor draws the decorations with plugins loaded from its plugin
+//! directory, e.g. `libdecor-cairo.so`. A plugin is a shared library
+//! exporting a `libdecor_plugin_description`. Implement [`Plugin`] and
+//! export it with [`libdecor_plugin!`](crate::libdecor_plugin) from a crate
+//! built as `cdylib`:
+//!
+//! ```no_run
+//! use std::{cell::Cell, io, os::unix::io::RawFd, time::Duration};
+//!
+//! use libdecor::{
+//!     plugin::{BorderSize, Plugin, PluginContext, PluginFrameRef, StateRef, WeakPluginFrame},
+//!     Configuration,
+//! };
+//!
+//! struct Branded {
+//!     context: PluginContext,
+//!     ready: Cell<bool>,
+//! }
+//!
+//! impl Plugin for Branded {
+//!     type Frame = ();
+//!
+//!     fn new(context: PluginContext) -> Option<Self> {
+//!         // Bind the globals on a private event queue
+//!         Some(Branded {
+//!             context,
+//!             ready: Cell::new(false),
+//!         })
+//!     }
+//!
+//!     fn fd(&self) -> RawFd {
+//!         self.context.display().get_connection_fd()
+//!     }
+//!
+//!     fn dispatch(&self, timeout: Option<Duration>) -> io::Result<usize> {
+//!         // Dispatch the event queue of the plugin, ready once the
+//!         // globals have been received
+//!         if !self.ready.replace(true) {
+//!             self.context.notify_ready();
+//!         }
+//!         Ok(0)
+//!     }
+//!
+//!     fn frame_new(&self, _: WeakPluginFrame) -> Self::Frame {}
+//!
+//!     fn frame_commit(
+//!         &self,
+//!         frame: &PluginFrameRef,
+//!         _: &Self::Frame,
+//!         state: &StateRef<'_>,
+//!         _: Option<&Configuration<'_>>,
+//!     ) {
+//!         // Draw the decorations around the content
+//!         let (width, height) = state.content_size();
+//!         frame.set_window_geometry(0, 0, width, height);
+//!     }
+//!
+//!     fn frame_border_size(
+//!         &self,
+//!         _: &PluginFrameRef,
+//!         _: &Self::Frame,
+//!         _: Option<&Configuration<'_>>,
+//!     ) -> Option<BorderSize> {
+//!         Some(BorderSize::default())
+//!     }
+//! }
+//!
+//! libdecor::libdecor_plugin! {
+//!     plugin: Branded,
+//!     description: "Branded decorations",
+//!     priorities: {
+//!         "GNOME" => libdecor::plugin::PRIORITY_HIGH,
+//!         _ => libdecor::plugin::PRIORITY_MEDIUM,
+//!     },
+//! }
+//! ```
+//!
+//! libdecor may call back into the plugin while the plugin calls libdecor,
+//! e.g. committing the application surface from a click on the decorations
+//! results in a [`Plugin::frame_commit`]. The methods of the plugin thus only
+//! get shared references and state has to be kept in [`Cell`](std::cell::Cell)s
+//! or [`RefCell`](std::cell::RefCell)s not borrowed while calling libdecor.
+
+use std::{
+    cell::Cell,
+    ffi::{c_void, CStr},
+    io, mem,
+    ops::Deref,
+    os::{
+        raw::{c_char, c_int},
+        unix::io::RawFd,
+    },
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    rc::Rc,
+    time::Duration,
+};
+
+use wayland_client::{protocol::wl_surface::WlSurface, Display};
+
+use libdecor_sys::{plugin::*, *};
+
+use crate::{Capabilities, Configuration, ErrorKind, FrameRef, WindowState};
+
+/// Priority of the plugin preferred on a desktop
+pub const PRIORITY_HIGH: c_int = LIBDECOR_PLUGIN_PRIORITY_HIGH;
+/// Priority of a plugin working well on a desktop
+pub const PRIORITY_MEDIUM: c_int = LIBDECOR_PLUGIN_PRIORITY_MEDIUM;
+/// Priority of a plugin only used if no other is available
+pub const PRIORITY_LOW: c_int = LIBDECOR_PLUGIN_PRIORITY_LOW;
+
+/// A decoration plugin
+///
+/// Exported by [`libdecor_plugin!`](crate::libdecor_plugin). Panics can not
+/// unwind into libdecor and abort the process.
+pub trait Plugin: Sized + 'static {
+    /// Data of the plugin for each frame
+    type Frame: 'static;
+
+    /// Create the plugin for a context.
+    ///
+    /// Returning [`None`] lets libdecor try the next plugin. Once ready, e.g.
+    /// after receiving the required globals, the plugin has to call
+    /// [`PluginContext::notify_ready`].
+    fn new(context: PluginContext) -> Option<Self>;
+
+    /// Get the file descriptor to poll for events of the plugin.
+    fn fd(&self) -> RawFd;
+
+    /// Dispatch the events of the plugin.
+    ///
+    /// Waits up to `timeout` for new events, if timeout is [`None`] this
+    /// function waits indefinitely. Returns the number of dispatched events.
+    fn dispatch(&self, timeout: Option<Duration>) -> io::Result<usize>;
+
+    /// Whether the plugin sets the cursor over the application surface.
+    ///
+    /// Set by the application, the plugin only sets the cursor over the
+    /// decorations by default.
+    #[cfg(feature = "libdecor_0_2")]
+    fn set_handle_application_cursor(&self, handle_cursor: bool) {
+        let _ = handle_cursor;
+    }
+
+    /// Create the data for a new frame.
+    ///
+    /// The passed [`WeakPluginFrame`] allows to access the frame outside of
+    /// the methods of the plugin, e.g. when handling input on the decorations.
+    fn frame_new(&self, frame: WeakPluginFrame) -> Self::Frame;
+
+    /// A frame is about to be destroyed.
+    fn frame_free(&self, frame: &PluginFrameRef, data: Self::Frame) {
+        let _ = (frame, data);
+    }
+
+    /// The application committed a new state, the decorations should be
+    /// updated for it.
+    fn frame_commit(
+        &self,
+        frame: &PluginFrameRef,
+        data: &Self::Frame,
+        state: &StateRef<'_>,
+        configuration: Option<&Configuration<'_>>,
+    );
+
+    /// A property of the frame like the title or the capabilities changed.
+    fn frame_property_changed(&self, frame: &PluginFrameRef, data: &Self::Frame) {
+        let _ = (frame, data);
+    }
+
+    /// The application grabbed the seat with a popup.
+    ///
+    /// Clicks on the decorations should dismiss the popup with
+    /// [`PluginFrameRef::dismiss_popup`] until the grab is released.
+    fn frame_popup_grab(&self, frame: &PluginFrameRef, data: &Self::Frame, seat_name: &str) {
+        let _ = (frame, data, seat_name);
+    }
+
+    /// The application released the grab of a seat.
+    fn frame_popup_ungrab(&self, frame: &PluginFrameRef, data: &Self::Frame, seat_name: &str) {
+        let _ = (frame, data, seat_name);
+    }
+
+    /// Get the size of the decorations around the content.
+    ///
+    /// Used to translate between the window geometry and the content size.
+    /// The configuration, if any, is the one the size is requested for.
+    /// Returns [`None`] if the size is unknown.
+    fn frame_border_size(
+        &self,
+        frame: &PluginFrameRef,
+        data: &Self::Frame,
+        configuration: Option<&Configuration<'_>>,
+    ) -> Option<BorderSize>;
+}
+
+/// The size of the decorations on each side of the content
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BorderSize {
+    /// Size left of the content
+    pub left: i32,
+    /// Size right of the content
+    pub right: i32,
+    /// Size above the content
+    pub top: i32,
+    /// Size below the content
+    pub bottom: i32,
+}
+
+/// The libdecor context a plugin was created for
+///
+/// Valid for the lifetime of the plugin.
+#[derive(Debug, Clone, Copy)]
+pub struct PluginContext(*mut libdecor);
+
+impl PluginContext {
+    /// Get the [`Display`] of the context.
+    pub fn display(&self) -> Display {
+        unsafe {
+            let display = ffi_dispatch!(LIBDECOR_PLUGIN_HANDLE, libdecor_get_wl_display, self.0);
+            Display::from_external_display(display as *mut _)
+        }
+    }
+
+    /// Notify libdecor that the plugin is ready to decorate frames.
+    pub fn notify_ready(&self) {
+        unsafe { ffi_dispatch!(LIBDECOR_PLUGIN_HANDLE, libdecor_notify_plugin_ready, self.0) }
+    }
+
+    /// Report an error to the application.
+    ///
+    /// [`ErrorKind::ContextCreationFailed`] has no counterpart in libdecor
+    /// and is reported as [`ErrorKind::CompositorIncompatible`].
+    pub fn notify_error<S: AsRef<str>>(&self, kind: ErrorKind, message: S) {
+        let error = match kind {
+            ErrorKind::InvalidFrameConfiguration => LIBDECOR_ERROR_INVALID_FRAME_CONFIGURATION,
+            ErrorKind::CompositorIncompatible | ErrorKind::ContextCreationFailed => {
+                LIBDECOR_ERROR_COMPOSITOR_INCOMPATIBLE
+            }
+            ErrorKind::Unknown(error) => error,
+        };
+        let message = std::ffi::CString::new(message.as_ref()).unwrap();
+        unsafe {
+            ffi_dispatch!(
+                LIBDECOR_PLUGIN_HANDLE,
+                libdecor_notify_plugin_error,
+                self.0,
+                error,
+                b"%s\0".as_ptr() as *const c_char,
+                message.as_ptr()
+            )
+        }
+    }
+}
+
+/// A frame decorated by a plugin
+///
+/// Dereferences to a [`FrameRef`] for the functions also available to
+/// applications.
+#[derive(Debug)]
+pub struct PluginFrameRef(FrameRef);
+
+impl Deref for PluginFrameRef {
+    type Target = FrameRef;
+
+    fn deref(&self) -> &FrameRef {
+        &self.0
+    }
+}
+
+impl PluginFrameRef {
+    /// Get the content [`WlSurface`] of the frame.
+    pub fn wl_surface(&self) -> WlSurface {
+        unsafe {
+            let surface = ffi_dispatch!(
+                LIBDECOR_PLUGIN_HANDLE,
+                libdecor_frame_get_wl_surface,
+                self.0 .0
+            );
+            wayland_client::Proxy::from_c_ptr(surface as *mut _).into()
+        }
+    }
+
+    /// Get the last committed size of the content.
+    pub fn content_size(&self) -> (i32, i32) {
+        unsafe {
+            (
+                ffi_dispatch!(
+                    LIBDECOR_PLUGIN_HANDLE,
+                    libdecor_frame_get_content_width,
+                    self.0 .0
+                ),
+                ffi_dispatch!(
+                    LIBDECOR_PLUGIN_HANDLE,
+                    libdecor_frame_get_content_height,
+                    self.0 .0
+                ),
+            )
+        }
+    }
+
+    /// Get the last committed [`WindowState`].
+    pub fn window_state(&self) -> WindowState {
+        let window_state = unsafe {
+            ffi_dispatch!(
+                LIBDECOR_PLUGIN_HANDLE,
+                libdecor_frame_get_window_state,
+                self.0 .0
+            )
+        };
+        WindowState::from_bits_truncate(window_state)
+    }
+
+    /// Get the [`Capabilities`] set by the application.
+    pub fn capabilities(&self) -> Capabilities {
+        let capabilities = unsafe {
+            ffi_dispatch!(
+                LIBDECOR_PLUGIN_HANDLE,
+                libdecor_frame_get_capabilities,
+                self.0 .0
+            )
+        };
+        Capabilities::from_bits_truncate(capabilities)
+    }
+
+    /// Set the window geometry, relative to the content surface.
+    pub fn set_window_geometry(&self, x: i32, y: i32, width: i32, height: i32) {
+        unsafe {
+            ffi_dispatch!(
+                LIBDECOR_PLUGIN_HANDLE,
+                libdecor_frame_set_window_geometry,
+                self.0 .0,
+                x,
+                y,
+                width,
+                height
+            )
+        }
+    }
+
+    /// Ask the application to commit the content surface.
+    ///
+    /// Required when the decorations are drawn to synchronized subsurfaces.
+    pub fn toplevel_commit(&self) {
+        unsafe {
+            ffi_dispatch!(
+                LIBDECOR_PLUGIN_HANDLE,
+                libdecor_frame_toplevel_commit,
+                self.0 .0
+            )
+        }
+    }
+
+    /// Ask the application to dismiss the popups grabbing the given seat.
+    pub fn dismiss_popup<S: AsRef<str>>(&self, seat_name: S) {
+        let seat_name = std::ffi::CString::new(seat_name.as_ref()).unwrap();
+        unsafe {
+            ffi_dispatch!(
+                LIBDECOR_PLUGIN_HANDLE,
+                libdecor_frame_dismiss_popup,
+                self.0 .0,
+                seat_name.as_ptr()
+            )
+        }
+    }
+}
+
+/// A weak reference to a frame decorated by a plugin
+///
+/// Can be upgraded once [`Plugin::frame_new`] has returned and until the
+/// frame has been passed to [`Plugin::frame_free`].
+#[derive(Debug, Clone)]
+pub struct WeakPluginFrame(Rc<Cell<*mut libdecor_frame>>);
+
+impl WeakPluginFrame {
+    /// Try to get a [`PluginFrameRef`] of the frame.
+    ///
+    /// Returns [`None`] if the frame has been freed.
+    pub fn upgrade(&self) -> Option<PluginFrameRef> {
+        let frame = self.0.get();
+        if frame.is_null() {
+            None
+        } else {
+            Some(PluginFrameRef(FrameRef(frame)))
+        }
+    }
+
+    /// Returns [`true`] if the frame has not been freed yet.
+    pub fn is_alive(&self) -> bool {
+        !self.0.get().is_null()
+    }
+}
+
+/// A state committed by the application
+///
+/// Only valid during [`Plugin::frame_commit`].
+#[derive(Debug)]
+pub struct StateRef<'a> {
+    ptr: *mut libdecor_state,
+    _marker: std::marker::PhantomData<&'a libdecor_state>,
+}
+
+impl<'a> StateRef<'a> {
+    /// Get the committed size of the content.
+    pub fn content_size(&self) -> (i32, i32) {
+        unsafe {
+            (
+                ffi_dispatch!(
+                    LIBDECOR_PLUGIN_HANDLE,
+                    libdecor_state_get_content_width,
+                    self.ptr
+                ),
+                ffi_dispatch!(
+                    LIBDECOR_PLUGIN_HANDLE,
+                    libdecor_state_get_content_height,
+                    self.ptr
+                ),
+            )
+        }
+    }
+
+    /// Get the committed [`WindowState`].
+    pub fn window_state(&self) -> WindowState {
+        let window_state = unsafe {
+            ffi_dispatch!(
+                LIBDECOR_PLUGIN_HANDLE,
+                libdecor_state_get_window_state,
+                self.ptr
+            )
+        };
+        WindowState::from_bits_truncate(window_state)
+    }
+}
+
+/// Makes the description exported by [`libdecor_plugin!`](crate::libdecor_plugin) shareable
+#[doc(hidden)]
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct Export<T>(pub T);
+
+// SAFETY: Only used for immutable statics pointing to other statics
+unsafe impl<T> Sync for Export<T> {}
+
+/// Build the description exported by [`libdecor_plugin!`](crate::libdecor_plugin).
+///
+/// `conflicting_symbols` is terminated by a null pointer in the description.
+#[doc(hidden)]
+pub const fn description(
+    description: *const u8,
+    priorities: *const libdecor_plugin_priority,
+    constructor: libdecor_plugin_constructor,
+    conflicting_symbols: &[*const u8],
+) -> libdecor_plugin_description {
+    assert!(
+        conflicting_symbols.len() < LIBDECOR_PLUGIN_CONFLICTING_SYMBOLS_MAX,
+        "too many conflicting symbols"
+    );
+    let mut conflicts = [ptr::null_mut(); LIBDECOR_PLUGIN_CONFLICTING_SYMBOLS_MAX];
+    let mut i = 0;
+    while i < conflicting_symbols.len() {
+        conflicts[i] = conflicting_symbols[i] as *mut c_char;
+        i += 1;
+    }
+
+    libdecor_plugin_description {
+        api_version: LIBDECOR_PLUGIN_API_VERSION,
+        #[cfg(feature = "libdecor_0_2")]
+        capabilities: LIBDECOR_PLUGIN_CAPABILITY_BASE,
+        description: description as *mut c_char,
+        priorities,
+        constructor,
+        conflicting_symbols: conflicts,
+    }
+}
+
+/// The plugin instance handed to libdecor
+#[repr(C)]
+struct PluginInstance<P> {
+    base: libdecor_plugin,
+    iface: libdecor_plugin_interface,
+    plugin: P,
+}
+
+/// A frame allocated by the plugin, freed by libdecor
+#[repr(C)]
+struct FrameInstance<F> {
+    base: libdecor_frame_base,
+    weak: Rc<Cell<*mut libdecor_frame>>,
+    data: F,
+}
+
+unsafe fn plugin<'a, P>(plugin: *mut libdecor_plugin) -> &'a P {
+    &(*(plugin as *const PluginInstance<P>)).plugin
+}
+
+unsafe fn frame_data<'a, F>(frame: *mut libdecor_frame) -> &'a F {
+    &(*(frame as *const FrameInstance<F>)).data
+}
+
+/// Run `f` without unwinding into libdecor, returns `failure` if it panicked.
+///
+/// There is no application to resume the panic in, it is only reported by
+/// the panic hook.
+fn guard<R>(failure: R, f: impl FnOnce() -> R) -> R {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(failure)
+}
+
+/// The constructor exported by [`libdecor_plugin!`](crate::libdecor_plugin)
+#[doc(hidden)]
+pub unsafe extern "C" fn construct<P: Plugin>(context: *mut libdecor) -> *mut libdecor_plugin {
+    guard(ptr::null_mut(), || {
+        let plugin = match P::new(PluginContext(context)) {
+            Some(plugin) => plugin,
+            None => return ptr::null_mut(),
+        };
+
+        let instance = Box::into_raw(Box::new(PluginInstance {
+            base: libdecor_plugin {
+                priv_: ptr::null_mut(),
+            },
+            iface: interface::<P>(),
+            plugin,
+        }));
+
+        let ret = ffi_dispatch!(
+            LIBDECOR_PLUGIN_HANDLE,
+            libdecor_plugin_init,
+            &mut (*instance).base,
+            context,
+            &mut (*instance).iface
+        );
+        if ret < 0 {
+            drop(Box::from_raw(instance));
+            return ptr::null_mut();
+        }
+
+        instance as *mut libdecor_plugin
+    })
+}
+
+fn interface<P: Plugin>() -> libdecor_plugin_interface {
+    libdecor_plugin_interface {
+        destroy: destroy::<P>,
+        get_fd: get_fd::<P>,
+        dispatch: dispatch::<P>,
+        #[cfg(feature = "libdecor_0_2")]
+        set_handle_application_cursor: set_handle_application_cursor::<P>,
+        frame_new: frame_new::<P>,
+        frame_free: frame_free::<P>,
+        frame_commit: frame_commit::<P>,
+        frame_property_changed: frame_property_changed::<P>,
+        frame_popup_grab: frame_popup_grab::<P>,
+        frame_popup_ungrab: frame_popup_ungrab::<P>,
+        frame_get_border_size: frame_get_border_size::<P>,
+        reserved0: None,
+        reserved1: None,
+        reserved2: None,
+        reserved3: None,
+        reserved4: None,
+        reserved5: None,
+        reserved6: None,
+        reserved7: None,
+        reserved8: None,
+        reserved9: None,
+    }
+}
+
+unsafe extern "C" fn destroy<P: Plugin>(plugin: *mut libdecor_plugin) {
+    let mut instance = Box::from_raw(plugin as *mut PluginInstance<P>);
+    ffi_dispatch!(
+        LIBDECOR_PLUGIN_HANDLE,
+        libdecor_plugin_release,
+        &mut instance.base
+    );
+    guard((), || drop(instance))
+}
+
+unsafe extern "C" fn get_fd<P: Plugin>(ptr: *mut libdecor_plugin) -> c_int {
+    guard(-1, || plugin::<P>(ptr).fd())
+}
+
+unsafe extern "C" fn dispatch<P: Plugin>(ptr: *mut libdecor_plugin, timeout: c_int) -> c_int {
+    let timeout = if timeout < 0 {
+        None
+    } else {
+        Some(Duration::from_millis(timeout as u64))
+    };
+    guard(-1, || match plugin::<P>(ptr).dispatch(timeout) {
+        Ok(dispatched) => dispatched as c_int,
+        Err(_) => -1,
+    })
+}
+
+#[cfg(feature = "libdecor_0_2")]
+unsafe extern "C" fn set_handle_application_cursor<P: Plugin>(
+    ptr: *mut libdecor_plugin,
+    handle_cursor: bool,
+) {
+    guard((), || {
+        plugin::<P>(ptr).set_handle_application_cursor(handle_cursor)
+    })
+}
+
+unsafe extern "C" fn frame_new<P: Plugin>(ptr: *mut libdecor_plugin) -> *mut libdecor_frame {
+    guard(ptr::null_mut(), || {
+        assert!(mem::align_of::<FrameInstance<P::Frame>>() <= mem::align_of::<libc::max_align_t>());
+
+        let frame = libc::calloc(1, mem::size_of::<FrameInstance<P::Frame>>())
+            as *mut FrameInstance<P::Frame>;
+        if frame.is_null() {
+            return ptr::null_mut();
+        }
+
+        let weak = Rc::new(Cell::new(ptr::null_mut()));
+        let data = match guard(None, || {
+            Some(plugin::<P>(ptr).frame_new(WeakPluginFrame(weak.clone())))
+        }) {
+            Some(data) => data,
+            None => {
+                libc::free(frame as *mut c_void);
+                return ptr::null_mut();
+            }
+        };
+        weak.set(frame as *mut libdecor_frame);
+        ptr::addr_of_mut!((*frame).weak).write(weak);
+        ptr::addr_of_mut!((*frame).data).write(data);
+        frame as *mut libdecor_frame
+    })
+}
+
+unsafe extern "C" fn frame_free<P: Plugin>(ptr: *mut libdecor_plugin, frame: *mut libdecor_frame) {
+    let instance = frame as *mut FrameInstance<P::Frame>;
+    let weak = ptr::addr_of!((*instance).weak).read();
+    let data = ptr::addr_of!((*instance).data).read();
+    guard((), || {
+        plugin::<P>(ptr).frame_free(&PluginFrameRef(FrameRef(frame)), data)
+    });
+    weak.set(ptr::null_mut());
+}
+
+unsafe extern "C" fn frame_commit<P: Plugin>(
+    ptr: *mut libdecor_plugin,
+    frame: *mut libdecor_frame,
+    state: *mut libdecor_state,
+    configuration: *mut libdecor_configuration,
+) {
+    let state = StateRef {
+        ptr: state,
+        _marker: std::marker::PhantomData,
+    };
+    let configuration = if configuration.is_null() {
+        None
+    } else {
+        Some(Configuration::from_ptr(configuration))
+    };
+    guard((), || {
+        plugin::<P>(ptr).frame_commit(
+            &PluginFrameRef(FrameRef(frame)),
+            frame_data(frame),
+            &state,
+            configuration.as_ref(),
+        )
+    })
+}
+
+unsafe extern "C" fn frame_property_changed<P: Plugin>(
+    ptr: *mut libdecor_plugin,
+    frame: *mut libdecor_frame,
+) {
+    guard((), || {
+        plugin::<P>(ptr).frame_property_changed(&PluginFrameRef(FrameRef(frame)), frame_data(frame))
+    })
+}
+
+unsafe extern "C" fn frame_popup_grab<P: Plugin>(
+    ptr: *mut libdecor_plugin,
+    frame: *mut libdecor_frame,
+    seat_name: *const c_char,
+) {
+    let seat_name = CStr::from_ptr(seat_name).to_string_lossy();
+    guard((), || {
+        plugin::<P>(ptr).frame_popup_grab(
+            &PluginFrameRef(FrameRef(frame)),
+            frame_data(frame),
+            &seat_name,
+        )
+    })
+}
+
+unsafe extern "C" fn frame_popup_ungrab<P: Plugin>(
+    ptr: *mut libdecor_plugin,
+    frame: *mut libdecor_frame,
+    seat_name: *const c_char,
+) {
+    let seat_name = CStr::from_ptr(seat_name).to_string_lossy();
+    guard((), || {
+        plugin::<P>(ptr).frame_popup_ungrab(
+            &PluginFrameRef(FrameRef(frame)),
+            frame_data(frame),
+            &seat_name,
+        )
+    })
+}
+
+unsafe extern "C" fn frame_get_border_size<P: Plugin>(
+    ptr: *mut libdecor_plugin,
+    frame: *mut libdecor_frame,
+    configuration: *mut libdecor_configuration,
+    left: *mut c_int,
+    right: *mut c_int,
+    top: *mut c_int,
+    bottom: *mut c_int,
+) -> bool {
+    let configuration = if configuration.is_null() {
+        None
+    } else {
+        Some(Configuration::from_ptr(configuration))
+    };
+    let border_size = guard(None, || {
+        plugin::<P>(ptr).frame_border_size(
+            &PluginFrameRef(FrameRef(frame)),
+            frame_data(frame),
+            configuration.as_ref(),
+        )
+    });
+
+    match border_size {
+        Some(border_size) => {
+            *left = border_size.left;
+            *right = border_size.right;
+            *top = border_size.top;
+            *bottom = border_size.bottom;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Export a [`Plugin`] from a `cdylib` crate.
+///
+/// Emits the `libdecor_plugin_description` symbol libdecor looks up when
+/// loading the plugin. libdecor picks the plugin with the highest priority
+/// for the desktop in `XDG_CURRENT_DESKTOP`, the priority after `_` is used on
+/// all other desktops. The plugin is not loaded if one of the symbols in the
+/// optional `conflicts_with` is already present in the process, e.g. from a
+/// different version of a toolkit used by the plugin.
+///
+/// ```ignore
+/// libdecor::libdecor_plugin! {
+///     plugin: Branded,
+///     description: "Branded decorations",
+///     conflicts_with: ["gtk_widget_show_all"],
+///     priorities: {
+///         "GNOME" => libdecor::plugin::PRIORITY_HIGH,
+///         _ => libdecor::plugin::PRIORITY_MEDIUM,
+///     },
+/// }
+/// ```
+#[macro_export]
+macro_rules! libdecor_plugin {
+    (
+        plugin: $plugin:ty,
+        description: $description:literal,
+        $(conflicts_with: [$($conflict:literal),* $(,)?],)?
+        priorities: {
+            $($desktop:literal => $priority:expr,)*
+            _ => $default:expr $(,)?
+        } $(,)?
+    ) => {
+        #[allow(non_upper_case_globals)]
+        #[no_mangle]
+        pub static libdecor_plugin_description: $crate::plugin::Export<
+            $crate::ffi::plugin::libdecor_plugin_description,
+        > = $crate::plugin::Export($crate::plugin::description(
+            concat!($description, "\0").as_ptr(),
+            {
+                static PRIORITIES: $crate::plugin::Export<
+                    [$crate::ffi::plugin::libdecor_plugin_priority; {
+                        let desktops: &[&str] = &[$($desktop),*];
+                        desktops.len() + 1
+                    }],
+                > = $crate::plugin::Export([
+                    $($crate::ffi::plugin::libdecor_plugin_priority {
+                        desktop: concat!($desktop, "\0").as_ptr() as *const _,
+                        priority: $priority,
+                    },)*
+                    $crate::ffi::plugin::libdecor_plugin_priority {
+                        desktop: ::std::ptr::null(),
+                        priority: $default,
+                    },
+                ]);
+                &PRIORITIES as *const _ as *const $crate::ffi::plugin::libdecor_plugin_priority
+            },
+            $crate::plugin::construct::<$plugin>,
+            &[$($(concat!($conflict, "\0").as_ptr()),*)?],
+        ));
+    };
+}
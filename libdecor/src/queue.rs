@@ -0,0 +1,225 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc, task::Waker};
+
+#[cfg(feature = "libdecor_0_2")]
+use crate::WmCapabilities;
+use crate::{Configuration, DecorationMode, Error, FrameHandler, FrameRef, State, WindowState};
+
+/// An owned event of a [`Frame`](crate::Frame).
+///
+/// Contrary to [`FrameRequest`](crate::FrameRequest) the event does not borrow anything
+/// from libdecor and can be handled at any later point in time.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum FrameEvent {
+    /// A new configuration was received and has already been committed.
+    ///
+    /// The application should redraw the content surface with the
+    /// given size.
+    Configure {
+        /// The committed size of the content surface
+        content_size: (i32, i32),
+        /// The window state of the configuration, [`None`] if the
+        /// window state remains unchanged
+        window_state: Option<WindowState>,
+    },
+    /// The window was requested to be closed by the compositor.
+    Close,
+    /// The window decoration asked to have the main surface to be
+    /// committed.
+    Commit,
+    /// Any mapped popup that has a grab on the given seat should be
+    /// dismissed.
+    DismissPopup {
+        /// The name of the seat
+        seat_name: String,
+    },
+    /// An error attributed to this frame was reported by libdecor.
+    Error(Error),
+    /// The decoration mode of the window was negotiated with the compositor.
+    ///
+    /// Only emitted by the built-in decorations of the `dlopen` feature, see
+    /// [`Context::set_decoration_mode`](crate::Context::set_decoration_mode).
+    DecorationMode(DecorationMode),
+    /// The actions supported by the compositor changed, queued before the
+    /// configuration they were received with.
+    #[cfg(feature = "libdecor_0_2")]
+    WmCapabilities(WmCapabilities),
+}
+
+#[derive(Debug)]
+pub(crate) struct EventQueue {
+    pub(crate) events: VecDeque<FrameEvent>,
+    pub(crate) waker: Option<Waker>,
+    pub(crate) closed: bool,
+    content_size: (i32, i32),
+}
+
+pub(crate) type SharedEventQueue = Rc<RefCell<EventQueue>>;
+
+impl EventQueue {
+    pub(crate) fn new(content_size: (i32, i32)) -> SharedEventQueue {
+        Rc::new(RefCell::new(EventQueue {
+            events: VecDeque::new(),
+            waker: None,
+            closed: false,
+            content_size,
+        }))
+    }
+
+    fn push(&mut self, event: FrameEvent) {
+        self.events.push_back(event);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A [`FrameHandler`] feeding an [`EventQueue`]
+pub(crate) struct QueueHandler(SharedEventQueue);
+
+impl QueueHandler {
+    pub(crate) fn new(queue: SharedEventQueue) -> Self {
+        QueueHandler(queue)
+    }
+
+    fn push(&self, event: FrameEvent) {
+        self.0.borrow_mut().push(event);
+    }
+}
+
+impl<D> FrameHandler<D> for QueueHandler {
+    fn configure(&mut self, frame: &FrameRef, configuration: &Configuration<'_>, _: &mut D) {
+        let content_size = configuration
+            .content_size(frame)
+            .unwrap_or_else(|| self.0.borrow().content_size);
+
+        // The configuration is only valid for the duration of the
+        // callback, so it has to be acknowledged right away.
+        let state = State::new(content_size.0, content_size.1);
+        frame.commit(&state, Some(configuration));
+
+        self.0.borrow_mut().content_size = content_size;
+
+        self.push(FrameEvent::Configure {
+            content_size,
+            window_state: configuration.window_state(),
+        });
+    }
+
+    fn close(&mut self, _: &FrameRef, _: &mut D) {
+        self.push(FrameEvent::Close);
+    }
+
+    fn commit(&mut self, _: &FrameRef, _: &mut D) {
+        self.push(FrameEvent::Commit);
+    }
+
+    fn dismiss_popup(&mut self, _: &FrameRef, seat_name: &str, _: &mut D) {
+        self.push(FrameEvent::DismissPopup {
+            seat_name: seat_name.to_owned(),
+        });
+    }
+
+    fn error(&mut self, _: &FrameRef, error: &Error, _: &mut D) {
+        self.push(FrameEvent::Error(error.clone()));
+    }
+
+    fn decoration_mode(&mut self, _: &FrameRef, mode: DecorationMode, _: &mut D) {
+        self.push(FrameEvent::DecorationMode(mode));
+    }
+
+    #[cfg(feature = "libdecor_0_2")]
+    fn wm_capabilities(&mut self, _: &FrameRef, capabilities: WmCapabilities, _: &mut D) {
+        self.push(FrameEvent::WmCapabilities(capabilities));
+    }
+}
+
+impl Drop for QueueHandler {
+    fn drop(&mut self) {
+        let mut queue = self.0.borrow_mut();
+        queue.closed = true;
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Error returned by [`FrameEventReceiver::try_recv`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// There are currently no queued events
+    Empty,
+    /// The [`Frame`](crate::Frame) has been dropped and all queued
+    /// events have been received
+    Disconnected,
+}
+
+impl std::fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("receiving on an empty frame event queue"),
+            TryRecvError::Disconnected => {
+                f.write_str("receiving on a frame event queue of a dropped frame")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TryRecvError {}
+
+/// The receiving side of a per-frame event queue
+///
+/// Created by [`Context::decorate_with_queue`](crate::Context::decorate_with_queue).
+/// The queue is filled while the [`Context`](crate::Context) is dispatched
+/// and can be drained at any later point in time.
+#[derive(Debug)]
+pub struct FrameEventReceiver {
+    queue: SharedEventQueue,
+}
+
+impl FrameEventReceiver {
+    pub(crate) fn new(queue: SharedEventQueue) -> Self {
+        Self { queue }
+    }
+
+    /// Try to receive the next queued event without blocking.
+    pub fn try_recv(&self) -> Result<FrameEvent, TryRecvError> {
+        let mut queue = self.queue.borrow_mut();
+        match queue.events.pop_front() {
+            Some(event) => Ok(event),
+            None if queue.closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Returns an iterator draining all currently queued events.
+    pub fn try_iter(&self) -> TryIter<'_> {
+        TryIter { receiver: self }
+    }
+
+    /// Returns the number of currently queued events.
+    pub fn len(&self) -> usize {
+        self.queue.borrow().events.len()
+    }
+
+    /// Returns [`true`] if there are no queued events.
+    pub fn is_empty(&self) -> bool {
+        self.queue.borrow().events.is_empty()
+    }
+}
+
+/// Iterator over the queued events of a [`FrameEventReceiver`]
+///
+/// Created by [`FrameEventReceiver::try_iter`].
+#[derive(Debug)]
+pub struct TryIter<'a> {
+    receiver: &'a FrameEventReceiver,
+}
+
+impl<'a> Iterator for TryIter<'a> {
+    type Item = FrameEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.try_recv().ok()
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    env,
+    ffi::{c_void, CStr, CString, OsStr, OsString},
+    fmt, fs, io, mem,
+    os::{
+        raw::{c_char, c_int},
+        unix::ffi::OsStrExt,
+    },
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use libdecor_sys::plugin::{
+    libdecor_plugin_description, libdecor_plugin_priority, LIBDECOR_PLUGIN_API_VERSION,
+};
+
+/// Environment variable libdecor reads the plugin directory from
+const PLUGIN_DIR_VAR: &str = "LIBDECOR_PLUGIN_DIR";
+/// Symbol exported by every plugin
+const DESCRIPTION_SYMBOL: &[u8] = b"libdecor_plugin_description\0";
+
+/// A plugin loaded by libdecor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginInfo {
+    name: String,
+    description: String,
+    priority: i32,
+    path: PathBuf,
+}
+
+impl PluginInfo {
+    /// Returns the file name of the plugin without extension, e.g. `libdecor-cairo`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the human readable description of the plugin.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Returns the priority of the plugin on the current desktop.
+    ///
+    /// See [`LIBDECOR_PLUGIN_PRIORITY_HIGH`](libdecor_sys::plugin::LIBDECOR_PLUGIN_PRIORITY_HIGH)
+    /// and friends for the common values.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Returns the path the plugin has been loaded from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// The decorations used by a [`Context`](crate::Context)
+///
+/// Returned by [`Context::active_plugin`](crate::Context::active_plugin).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ActivePlugin {
+    /// A plugin loaded from the plugin directory
+    Plugin(PluginInfo),
+    /// The minimal decorations built into libdecor, used if no plugin
+    /// could be loaded
+    Builtin,
+    /// The decorations of this crate, used with the `dlopen` feature if
+    /// libdecor could not be loaded
+    Fallback,
+}
+
+impl ActivePlugin {
+    /// Find the plugin chosen by libdecor.
+    ///
+    /// libdecor unloads all other plugins after choosing one, so this
+    /// looks for a loaded object exporting a plugin description. The
+    /// objects are process wide, the plugins of other contexts are
+    /// considered as well.
+    pub(crate) fn detect() -> Self {
+        if !libdecor_sys::is_lib_available() {
+            return ActivePlugin::Fallback;
+        }
+
+        loaded_plugins()
+            .into_iter()
+            .max_by_key(|plugin| plugin.priority)
+            .map(ActivePlugin::Plugin)
+            .unwrap_or(ActivePlugin::Builtin)
+    }
+}
+
+impl fmt::Display for ActivePlugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActivePlugin::Plugin(plugin) => {
+                write!(f, "{} (priority {})", plugin.name, plugin.priority)
+            }
+            ActivePlugin::Builtin => f.write_str("libdecor built-in decorations"),
+            ActivePlugin::Fallback => f.write_str("fallback decorations"),
+        }
+    }
+}
+
+fn loaded_plugins() -> Vec<PluginInfo> {
+    unsafe extern "C" fn collect(
+        info: *mut libc::dl_phdr_info,
+        _: libc::size_t,
+        data: *mut c_void,
+    ) -> c_int {
+        let paths = &mut *(data as *mut Vec<PathBuf>);
+        let name = (*info).dlpi_name;
+        // The executable itself has an empty name
+        if !name.is_null() && *name != 0 {
+            let name = CStr::from_ptr(name);
+            paths.push(OsStr::from_bytes(name.to_bytes()).into());
+        }
+        0
+    }
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    unsafe { libc::dl_iterate_phdr(Some(collect), &mut paths as *mut _ as *mut c_void) };
+    paths.iter().filter_map(|path| plugin_info(path)).collect()
+}
+
+fn plugin_info(path: &Path) -> Option<PluginInfo> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    unsafe {
+        // Only takes another reference to an already loaded object
+        let handle = libc::dlopen(c_path.as_ptr(), libc::RTLD_LAZY | libc::RTLD_NOLOAD);
+        if handle.is_null() {
+            return None;
+        }
+
+        let symbol = libc::dlsym(handle, DESCRIPTION_SYMBOL.as_ptr() as *const c_char);
+        let info = (symbol as *const libdecor_plugin_description)
+            .as_ref()
+            .filter(|description| {
+                description.api_version == LIBDECOR_PLUGIN_API_VERSION
+                    && !description.description.is_null()
+            })
+            .map(|description| PluginInfo {
+                name: path
+                    .file_stem()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                description: CStr::from_ptr(description.description)
+                    .to_string_lossy()
+                    .into_owned(),
+                priority: priority(description.priorities),
+                path: path.to_owned(),
+            });
+
+        libc::dlclose(handle);
+        info
+    }
+}
+
+/// Calculate the priority on the current desktop the same way as libdecor.
+///
+/// The priorities are terminated by an entry without a desktop which
+/// applies to all other desktops.
+unsafe fn priority(mut priorities: *const libdecor_plugin_priority) -> i32 {
+    if priorities.is_null() {
+        return -1;
+    }
+
+    let current_desktop = env::var("XDG_CURRENT_DESKTOP").ok();
+    loop {
+        let priority = &*priorities;
+        if priority.desktop.is_null() {
+            return priority.priority;
+        }
+
+        if let Some(current_desktop) = &current_desktop {
+            let desktop = CStr::from_ptr(priority.desktop).to_string_lossy();
+            if current_desktop
+                .split(':')
+                .filter(|token| !token.is_empty())
+                .any(|token| desktop.contains(token))
+            {
+                return priority.priority;
+            }
+        }
+
+        priorities = priorities.add(1);
+    }
+}
+
+/// Get the address of a function of the loaded libdecor.
+#[cfg(all(feature = "dlopen", not(feature = "mock")))]
+fn libdecor_address() -> Option<*const c_void> {
+    libdecor_sys::LIBDECOR_OPTION.map(|lib| lib.libdecor_new as *const c_void)
+}
+
+#[cfg(not(any(feature = "dlopen", feature = "mock")))]
+fn libdecor_address() -> Option<*const c_void> {
+    Some(libdecor_sys::libdecor_new as *const c_void)
+}
+
+#[cfg(feature = "mock")]
+fn libdecor_address() -> Option<*const c_void> {
+    None
+}
+
+/// The directory libdecor loads the plugins from by default.
+///
+/// Plugins are installed to `libdecor/plugins-1` next to the library.
+fn default_plugin_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os(PLUGIN_DIR_VAR) {
+        return Some(dir.into());
+    }
+
+    let mut info = mem::MaybeUninit::<libc::Dl_info>::zeroed();
+    if unsafe { libc::dladdr(libdecor_address()?, info.as_mut_ptr()) } == 0 {
+        return None;
+    }
+    let info = unsafe { info.assume_init() };
+    if info.dli_fname.is_null() {
+        return None;
+    }
+
+    let library = unsafe { CStr::from_ptr(info.dli_fname) };
+    let library = Path::new(OsStr::from_bytes(library.to_bytes()));
+    Some(
+        library
+            .parent()?
+            .join("libdecor")
+            .join(format!("plugins-{}", LIBDECOR_PLUGIN_API_VERSION)),
+    )
+}
+
+/// Points libdecor to the selected plugins while a context is created
+///
+/// libdecor only reads the plugin directory from the environment, so the
+/// variable is overridden until the selection is dropped. A preferred
+/// plugin is linked into a temporary directory containing only that plugin.
+#[derive(Debug)]
+pub(crate) struct PluginSelection {
+    previous: Option<Option<OsString>>,
+    staging: Option<PathBuf>,
+}
+
+impl PluginSelection {
+    pub(crate) fn apply(plugin_dir: Option<&Path>, preferred: Option<&str>) -> io::Result<Self> {
+        let mut selection = PluginSelection {
+            previous: None,
+            staging: None,
+        };
+
+        let preferred = preferred.and_then(|name| {
+            let dir = plugin_dir.map(Path::to_owned).or_else(default_plugin_dir)?;
+            let file_name = format!("{}.so", name);
+            let path = dir.join(&file_name);
+            // An unavailable plugin is not preferred
+            if path.is_file() {
+                Some((path, file_name))
+            } else {
+                None
+            }
+        });
+
+        let dir = match preferred {
+            Some((path, file_name)) => {
+                static COUNTER: AtomicUsize = AtomicUsize::new(0);
+                let staging = env::temp_dir().join(format!(
+                    "libdecor-rs-{}-{}",
+                    std::process::id(),
+                    COUNTER.fetch_add(1, Ordering::Relaxed)
+                ));
+                fs::create_dir(&staging)?;
+                selection.staging = Some(staging.clone());
+                std::os::unix::fs::symlink(path, staging.join(file_name))?;
+                staging
+            }
+            None => match plugin_dir {
+                Some(plugin_dir) => plugin_dir.to_owned(),
+                None => return Ok(selection),
+            },
+        };
+
+        selection.previous = Some(env::var_os(PLUGIN_DIR_VAR));
+        env::set_var(PLUGIN_DIR_VAR, dir);
+        Ok(selection)
+    }
+}
+
+impl Drop for PluginSelection {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(Some(previous)) => env::set_var(PLUGIN_DIR_VAR, previous),
+            Some(None) => env::remove_var(PLUGIN_DIR_VAR),
+            None => {}
+        }
+        // The plugin stays loaded after the link has been removed
+        if let Some(staging) = self.staging.take() {
+            let _ = fs::remove_dir_all(staging);
+        }
+    }
+}
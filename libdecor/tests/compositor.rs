@@ -0,0 +1,329 @@
+//! Tests against the headless test compositor
+//!
+//! These tests need the system libdecor and libwayland-client.
+#![cfg(not(feature = "mock"))]
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use libdecor::{
+    ActivePlugin, Context, ContextBuilder, ErrorKind, FrameEvent, FrameRequest, Request, State,
+};
+use libdecor_test_compositor::{Config, DecorationMode, TestCompositor, ToplevelState};
+use wayland_client::{
+    protocol::{wl_compositor::WlCompositor, wl_surface::WlSurface},
+    Display, GlobalManager,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Client {
+    display: Display,
+    surface: WlSurface,
+}
+
+impl Client {
+    fn connect(compositor: &TestCompositor) -> Self {
+        let display = Display::connect_to_name(compositor.socket()).unwrap();
+        let mut event_queue = display.create_event_queue();
+        let attached_display = (*display).clone().attach(event_queue.token());
+        let globals = GlobalManager::new(&attached_display);
+        event_queue
+            .sync_roundtrip(&mut (), |_, _, _| unreachable!())
+            .unwrap();
+
+        let surface = globals
+            .instantiate_exact::<WlCompositor>(4)
+            .unwrap()
+            .create_surface()
+            .detach();
+
+        Client { display, surface }
+    }
+}
+
+fn dispatch_until<D>(context: &Context<D>, data: &mut D, mut f: impl FnMut(&mut D) -> bool) {
+    for _ in 0..100 {
+        if f(data) {
+            return;
+        }
+        context
+            .dispatch(data, Some(Duration::from_millis(50)))
+            .unwrap();
+    }
+    panic!("condition not reached in time");
+}
+
+#[test]
+fn initial_configure_is_acked() {
+    let compositor = TestCompositor::spawn(Config {
+        initial_size: (640, 480),
+        ..Config::default()
+    })
+    .unwrap();
+    let client = Client::connect(&compositor);
+    let context = Context::new(client.display.clone(), |_| {});
+
+    let frame = context
+        .decorate(
+            client.surface.clone(),
+            |frame, request, sizes: &mut Vec<_>| {
+                if let FrameRequest::Configure(configuration) = request {
+                    let size = configuration.content_size(frame).unwrap_or((320, 240));
+                    frame.commit(&State::new(size.0, size.1), Some(configuration));
+                    sizes.push(size);
+                }
+            },
+        )
+        .unwrap();
+    frame.dispatch(&mut Vec::new(), |f| {
+        f.set_title("test");
+        f.set_app_id("libdecor.test");
+        f.map();
+    });
+
+    let mut sizes = Vec::new();
+    dispatch_until(&context, &mut sizes, |sizes| !sizes.is_empty());
+    assert_eq!(sizes.len(), 1);
+    // The commit from within the callback is only flushed by the next dispatch
+    client.display.flush().unwrap();
+
+    assert!(compositor.wait_for(TIMEOUT, |records| {
+        records.toplevels.first().is_some_and(|toplevel| {
+            !toplevel.acked.is_empty() && toplevel.window_geometry.is_some()
+        })
+    }));
+    let toplevel = &compositor.records().toplevels[0];
+    assert_eq!(toplevel.title.as_deref(), Some("test"));
+    assert_eq!(toplevel.app_id.as_deref(), Some("libdecor.test"));
+    assert_eq!(toplevel.acked, toplevel.configured);
+    // The configured size includes the decorations, which are subtracted
+    // from the content size depending on the plugin
+    let (_, _, width, height) = toplevel.window_geometry.unwrap();
+    assert_eq!((width, height), (640, 480));
+}
+
+#[test]
+fn scripted_configures_are_delivered() {
+    let compositor = TestCompositor::spawn(Config::default()).unwrap();
+    let client = Client::connect(&compositor);
+    let context = Context::new(client.display.clone(), |_| {});
+
+    let (frame, events) = context
+        .decorate_with_queue(client.surface.clone(), (320, 240))
+        .unwrap();
+    frame.dispatch(&mut (), |f| f.map());
+
+    dispatch_until(&context, &mut (), |_| !events.is_empty());
+    // The capabilities are announced before the first configure
+    #[cfg(feature = "libdecor_0_2")]
+    assert!(matches!(
+        events.try_recv(),
+        Ok(FrameEvent::WmCapabilities(_))
+    ));
+    assert!(matches!(
+        events.try_recv(),
+        Ok(FrameEvent::Configure {
+            content_size: (320, 240),
+            ..
+        })
+    ));
+
+    compositor.configure(0, (800, 600), vec![ToplevelState::Activated]);
+    dispatch_until(&context, &mut (), |_| !events.is_empty());
+    assert!(matches!(
+        events.try_recv(),
+        Ok(FrameEvent::Configure { .. })
+    ));
+
+    compositor.close(0);
+    dispatch_until(&context, &mut (), |_| !events.is_empty());
+    assert_eq!(events.try_recv(), Ok(FrameEvent::Close));
+
+    assert!(compositor.wait_for(TIMEOUT, |records| {
+        let toplevel = &records.toplevels[0];
+        toplevel.acked.len() == 2
+            && toplevel
+                .window_geometry
+                .is_some_and(|(_, _, width, height)| (width, height) == (800, 600))
+    }));
+}
+
+#[test]
+fn invalid_size_limits_are_reported() {
+    let compositor = TestCompositor::spawn(Config::default()).unwrap();
+    let client = Client::connect(&compositor);
+    let errors = Rc::new(RefCell::new(Vec::new()));
+    let context = {
+        let errors = errors.clone();
+        Context::new(client.display.clone(), move |request| {
+            if let Request::Error(error) = request {
+                errors.borrow_mut().push(error)
+            }
+        })
+    };
+
+    let (frame, events) = context
+        .decorate_with_queue(client.surface.clone(), (320, 240))
+        .unwrap();
+    frame.dispatch(&mut (), |f| f.map());
+    dispatch_until(&context, &mut (), |_| !events.is_empty());
+
+    frame.dispatch(&mut (), |f| {
+        f.set_min_content_size(400, 400);
+        f.set_max_content_size(200, 200);
+        f.commit(&State::new(320, 240), None);
+    });
+
+    let errors = errors.borrow();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind(), ErrorKind::InvalidFrameConfiguration);
+    assert_eq!(errors[0].frame(), Some(frame.id()));
+}
+
+#[test]
+fn server_side_decorations_are_negotiated() {
+    let compositor = TestCompositor::spawn(Config {
+        xdg_decoration: true,
+        decoration_mode: Some(DecorationMode::ServerSide),
+        ..Config::default()
+    })
+    .unwrap();
+    let client = Client::connect(&compositor);
+
+    // Without plugins libdecor uses its built-in decorations, which always
+    // request server-side decorations
+    let plugin_dir =
+        std::env::temp_dir().join(format!("libdecor-rs-no-plugins-{}", std::process::id()));
+    std::fs::create_dir_all(&plugin_dir).unwrap();
+    let context: Context = ContextBuilder::new()
+        .plugin_dir(&plugin_dir)
+        .build(client.display.clone(), |_| {})
+        .unwrap();
+    // Only the fallback of the `dlopen` feature takes a preference
+    #[cfg(feature = "dlopen")]
+    let fallback = libdecor::uses_fallback();
+    #[cfg(not(feature = "dlopen"))]
+    let fallback = false;
+    let reported = context
+        .set_decoration_mode(libdecor::DecorationMode::ServerSide)
+        .is_ok();
+    assert_eq!(reported, fallback);
+    let expected = if fallback {
+        ActivePlugin::Fallback
+    } else {
+        ActivePlugin::Builtin
+    };
+    assert_eq!(context.active_plugin(), &expected);
+
+    let (frame, events) = context
+        .decorate_with_queue(client.surface.clone(), (320, 240))
+        .unwrap();
+    frame.dispatch(&mut (), |f| f.map());
+    dispatch_until(&context, &mut (), |_| !events.is_empty());
+    let _ = std::fs::remove_dir(&plugin_dir);
+
+    let records = compositor.records();
+    assert_eq!(
+        records.toplevels[0].decoration_mode,
+        Some(DecorationMode::ServerSide)
+    );
+    if reported {
+        assert!(
+            events
+                .try_iter()
+                .any(|event| event
+                    == FrameEvent::DecorationMode(libdecor::DecorationMode::ServerSide))
+        );
+    }
+}
+
+#[test]
+fn dispatch_reports_the_lost_connection() {
+    use libdecor::DispatchError;
+
+    let compositor = TestCompositor::spawn(Config::default()).unwrap();
+    let client = Client::connect(&compositor);
+    let context = Context::<()>::try_new(client.display.clone(), |_| {}).unwrap();
+    let frame = context
+        .decorate(client.surface.clone(), |_, _, _| {})
+        .unwrap();
+    frame.dispatch(&mut (), |f| f.map());
+    drop(compositor);
+
+    let error = (0..100)
+        .find_map(|_| context.dispatch(&mut (), Some(TIMEOUT / 100)).err())
+        .expect("the lost connection was not reported");
+    assert!(
+        matches!(error, DispatchError::Disconnected(_)),
+        "unexpected error: {:?}",
+        error
+    );
+    assert!(error.raw_os_error().is_some());
+}
+
+#[cfg(feature = "async-io")]
+#[test]
+fn async_events_are_delivered_after_mapping() {
+    use futures_util::{
+        future::{self, Either},
+        StreamExt,
+    };
+    use libdecor::AsyncContext;
+
+    let compositor = TestCompositor::spawn(Config::default()).unwrap();
+    let client = Client::connect(&compositor);
+    let context = AsyncContext::new_async_io(client.display.clone(), |_| {}).unwrap();
+
+    let (frame, mut events) = context
+        .decorate(client.surface.clone(), (320, 240))
+        .unwrap();
+    frame.dispatch(&mut (), |f| f.map());
+
+    // The map request has to be flushed before waiting for the configure
+    let configure = async_io::block_on(async {
+        loop {
+            match future::select(events.next(), async_io::Timer::after(TIMEOUT)).await {
+                Either::Left((Some(FrameEvent::Configure { content_size, .. }), _)) => {
+                    return Some(content_size)
+                }
+                Either::Left((Some(_), _)) => continue,
+                _ => return None,
+            }
+        }
+    });
+    assert_eq!(configure, Some((320, 240)));
+}
+
+#[cfg(feature = "async-io")]
+#[test]
+fn async_events_end_with_the_dispatch_error() {
+    use futures_util::{
+        future::{self, Either},
+        StreamExt,
+    };
+    use libdecor::{AsyncContext, DispatchError};
+
+    let compositor = TestCompositor::spawn(Config::default()).unwrap();
+    let client = Client::connect(&compositor);
+    let context = AsyncContext::new_async_io(client.display.clone(), |_| {}).unwrap();
+
+    let (_frame, mut events) = context
+        .decorate(client.surface.clone(), (320, 240))
+        .unwrap();
+    drop(compositor);
+
+    let ended = async_io::block_on(async {
+        loop {
+            match future::select(events.next(), async_io::Timer::after(TIMEOUT)).await {
+                Either::Left((Some(_), _)) => continue,
+                Either::Left((None, _)) => return true,
+                Either::Right(_) => return false,
+            }
+        }
+    });
+    assert!(ended);
+    assert!(matches!(
+        events.take_error(),
+        Some(DispatchError::Disconnected(_))
+    ));
+}
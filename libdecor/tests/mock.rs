@@ -0,0 +1,1159 @@
+//! Tests against the mock implementation of libdecor
+//!
+//! Run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use std::{
+    cell::RefCell,
+    os::unix::{io::IntoRawFd, net::UnixStream},
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+};
+
+use libdecor::{
+    ffi::{self, mock},
+    Context, DecorationMode, ErrorKind, FrameEvent, FrameRequest, Request, State, WeakFrameHandle,
+    WindowState,
+};
+use wayland_client::{
+    protocol::{wl_compositor::WlCompositor, wl_surface::WlSurface},
+    Display, EventQueue, Main,
+};
+
+/// A display connected to a socket nobody listens on, which is
+/// sufficient as the mock never talks to the compositor.
+struct Connection {
+    display: Display,
+    _event_queue: EventQueue,
+    _server: UnixStream,
+    compositor: Main<WlCompositor>,
+}
+
+impl Connection {
+    fn new() -> Self {
+        let (client, server) = UnixStream::pair().unwrap();
+        let display = unsafe { Display::from_fd(client.into_raw_fd()) }.unwrap();
+        let event_queue = display.create_event_queue();
+        let attached_display = (*display).clone().attach(event_queue.token());
+        let registry = attached_display.get_registry();
+        let compositor = registry.bind::<WlCompositor>(4, 1);
+
+        Connection {
+            display,
+            _event_queue: event_queue,
+            _server: server,
+            compositor,
+        }
+    }
+
+    fn surface(&self) -> WlSurface {
+        self.compositor.create_surface().detach()
+    }
+}
+
+fn last_frame() -> *mut ffi::libdecor_frame {
+    *mock::frames().last().unwrap()
+}
+
+fn configure(content_size: Option<(i32, i32)>) -> mock::Event {
+    mock::Event::Configure {
+        content_size,
+        window_state: Some(ffi::LIBDECOR_WINDOW_STATE_ACTIVE),
+    }
+}
+
+#[test]
+fn configure_is_committed() {
+    let connection = Connection::new();
+    let context = Context::new(connection.display.clone(), |_| {});
+
+    let frame = context
+        .decorate(
+            connection.surface(),
+            |frame, request, sizes: &mut Vec<_>| {
+                if let FrameRequest::Configure(configuration) = request {
+                    let size = configuration.content_size(frame).unwrap();
+                    assert_eq!(configuration.window_state(), Some(WindowState::ACTIVE));
+                    frame.commit(&State::new(size.0, size.1), Some(configuration));
+                    sizes.push(size);
+                }
+            },
+        )
+        .unwrap();
+    frame.dispatch(&mut Vec::new(), |f| f.map());
+
+    mock::inject(last_frame(), configure(Some((800, 600))));
+
+    let mut sizes = Vec::new();
+    assert_eq!(context.dispatch(&mut sizes, None).unwrap(), 1);
+    assert_eq!(sizes, vec![(800, 600)]);
+
+    let info = mock::frame_info(last_frame()).unwrap();
+    assert!(info.mapped);
+    assert_eq!(info.committed, vec![(800, 600)]);
+    assert_eq!(info.acked_configurations, 1);
+}
+
+#[test]
+fn handlers_receive_typed_requests() {
+    use libdecor::{Configuration, FrameHandler, FrameRef};
+
+    /// Commits the configured size and leaves close to the default method
+    struct Window {
+        size: (i32, i32),
+    }
+
+    impl FrameHandler<Vec<String>> for Window {
+        fn configure(
+            &mut self,
+            frame: &FrameRef,
+            configuration: &Configuration<'_>,
+            log: &mut Vec<String>,
+        ) {
+            self.size = configuration.content_size(frame).unwrap_or(self.size);
+            frame.commit(&State::new(self.size.0, self.size.1), Some(configuration));
+            log.push(format!("configure {:?}", self.size));
+        }
+
+        fn dismiss_popup(&mut self, _: &FrameRef, seat_name: &str, log: &mut Vec<String>) {
+            log.push(format!("dismiss popup {}", seat_name));
+        }
+    }
+
+    let connection = Connection::new();
+    let context = Context::new(connection.display.clone(), |_| {});
+    let _frame = context
+        .decorate_with_handler(connection.surface(), Window { size: (800, 600) })
+        .unwrap();
+
+    mock::inject(last_frame(), configure(None));
+    mock::inject(last_frame(), mock::Event::Close);
+    mock::inject(
+        last_frame(),
+        mock::Event::DismissPopup {
+            seat_name: "seat0".into(),
+        },
+    );
+    mock::inject(last_frame(), configure(Some((640, 480))));
+
+    let mut log = Vec::new();
+    assert_eq!(context.dispatch(&mut log, None).unwrap(), 4);
+    assert_eq!(
+        log,
+        vec![
+            "configure (800, 600)",
+            "dismiss popup seat0",
+            "configure (640, 480)"
+        ]
+    );
+    assert_eq!(
+        mock::frame_info(last_frame()).unwrap().committed,
+        vec![(800, 600), (640, 480)]
+    );
+}
+
+#[test]
+fn callbacks_receive_the_dispatched_data() {
+    #[derive(Default)]
+    struct App {
+        closed: usize,
+        configured: usize,
+    }
+
+    let connection = Connection::new();
+    let context = Context::<App>::new(connection.display.clone(), |_| {});
+    let frame = context
+        .decorate(
+            connection.surface(),
+            |_, request, app: &mut App| match request {
+                FrameRequest::Configure(_) => app.configured += 1,
+                FrameRequest::Close => app.closed += 1,
+                _ => {}
+            },
+        )
+        .unwrap();
+
+    // Callbacks invoked synchronously get the data passed to the frame
+    let mut app = App::default();
+    frame.dispatch(&mut app, |f| f.close());
+    assert_eq!((app.configured, app.closed), (0, 1));
+
+    mock::inject(last_frame(), configure(None));
+    mock::inject(last_frame(), mock::Event::Close);
+    let mut other = App::default();
+    context.dispatch(&mut other, None).unwrap();
+    assert_eq!((app.configured, app.closed), (0, 1));
+    assert_eq!((other.configured, other.closed), (1, 1));
+}
+
+#[test]
+fn configuration_snapshots_outlive_the_callback() {
+    use libdecor::ConfigurationSnapshot;
+
+    let connection = Connection::new();
+    let context = Context::new(connection.display.clone(), |_| {});
+    let frame = context
+        .decorate(
+            connection.surface(),
+            |frame, request, snapshots: &mut Vec<ConfigurationSnapshot>| {
+                if let FrameRequest::Configure(configuration) = request {
+                    snapshots.push(configuration.snapshot(frame));
+                }
+            },
+        )
+        .unwrap();
+
+    mock::inject(last_frame(), configure(Some((800, 600))));
+    mock::inject(
+        last_frame(),
+        mock::Event::Configure {
+            content_size: None,
+            window_state: None,
+        },
+    );
+    let mut snapshots = Vec::new();
+    context.dispatch(&mut snapshots, None).unwrap();
+
+    assert_eq!(snapshots.len(), 2);
+    assert!(snapshots.iter().all(|s| s.frame() == frame.id()));
+    assert_eq!(snapshots[0].content_size(), Some((800, 600)));
+    assert_eq!(snapshots[0].window_state(), Some(WindowState::ACTIVE));
+    assert_eq!(snapshots[1].content_size(), None);
+    assert_eq!(snapshots[1].window_state(), None);
+    // Nothing has been committed by taking the snapshots
+    assert_eq!(
+        mock::frame_info(last_frame()).unwrap().acked_configurations,
+        0
+    );
+}
+
+#[test]
+fn frame_properties() {
+    let connection = Connection::new();
+    let context = Context::new(connection.display.clone(), |_| {});
+    let frame = context
+        .decorate(connection.surface(), |_, _, _: &mut ()| {})
+        .unwrap();
+
+    let title = frame.dispatch(&mut (), |f| {
+        f.set_title("title");
+        f.set_app_id("app_id");
+        f.set_min_content_size(100, 100);
+        f.title()
+    });
+
+    assert_eq!(title, "title");
+    let info = mock::frame_info(last_frame()).unwrap();
+    assert_eq!(info.app_id.as_deref(), Some("app_id"));
+    assert_eq!(info.min_content_size, (100, 100));
+}
+
+#[test]
+fn frame_handles() {
+    let connection = Connection::new();
+    let context = Context::new(connection.display.clone(), |_| {});
+    let frame = context
+        .decorate(connection.surface(), |_, _, _: &mut ()| {})
+        .unwrap();
+    let ptr = last_frame();
+
+    let weak = frame.downgrade();
+    let handle = frame.handle();
+    assert_eq!(mock::frame_info(ptr).unwrap().ref_count, 2);
+    assert_eq!(weak.upgrade().map(|h| h.id()), Some(frame.id()));
+
+    drop(frame);
+    assert!(weak.upgrade().is_none());
+    // The strong handle keeps the frame alive
+    assert_eq!(mock::frame_info(ptr).unwrap().ref_count, 1);
+    handle.dispatch(&mut (), |f| f.set_title("still alive"));
+
+    drop(handle);
+    assert!(mock::frame_info(ptr).is_none());
+}
+
+#[test]
+fn weak_handles_refer_to_other_windows() {
+    let connection = Connection::new();
+    let context = Context::new(connection.display.clone(), |_| {});
+    let child = context
+        .decorate(connection.surface(), |_, _, _: &mut Vec<bool>| {})
+        .unwrap();
+    let child_ptr = last_frame();
+    let weak_child = child.downgrade();
+
+    let parent = {
+        let weak_child = weak_child.clone();
+        context
+            .decorate(
+                connection.surface(),
+                move |_, request, found: &mut Vec<_>| {
+                    if let FrameRequest::Close = request {
+                        let child = weak_child.upgrade();
+                        if let Some(child) = &child {
+                            child.dispatch(&mut Vec::new(), |f| f.set_title("orphan"));
+                        }
+                        found.push(child.is_some());
+                    }
+                },
+            )
+            .unwrap()
+    };
+    let parent_ptr = last_frame();
+
+    let mut found = Vec::new();
+    mock::inject(parent_ptr, mock::Event::Close);
+    context.dispatch(&mut found, None).unwrap();
+    assert_eq!(
+        mock::frame_info(child_ptr).unwrap().title.as_deref(),
+        Some("orphan")
+    );
+    // Upgraded handles have been released again
+    assert_eq!(mock::frame_info(child_ptr).unwrap().ref_count, 1);
+
+    let child_id = child.id();
+    drop(child);
+    assert!(mock::frame_info(child_ptr).is_none());
+    assert!(!weak_child.is_alive());
+    assert_eq!(weak_child.id(), child_id);
+
+    mock::inject(parent_ptr, mock::Event::Close);
+    context.dispatch(&mut found, None).unwrap();
+    assert_eq!(found, vec![true, false]);
+    drop(parent);
+}
+
+#[test]
+fn context_creation_failure() {
+    let connection = Connection::new();
+
+    mock::fail_next_new();
+    let err = Context::<()>::try_new(connection.display.clone(), |_| {}).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ContextCreationFailed);
+}
+
+#[test]
+fn decoration_mode_requires_the_builtin_decorations() {
+    let connection = Connection::new();
+    let context = Context::<()>::new(connection.display.clone(), |_| {});
+
+    let err = context
+        .set_decoration_mode(DecorationMode::ServerSide)
+        .unwrap_err();
+    assert_eq!(err.function(), "libdecor_set_decoration_mode");
+}
+
+#[test]
+fn errors_are_attributed_to_frames() {
+    let connection = Connection::new();
+    let errors = Rc::new(RefCell::new(Vec::new()));
+    let context = {
+        let errors = errors.clone();
+        Context::new(connection.display.clone(), move |request| {
+            if let Request::Error(error) = request {
+                errors.borrow_mut().push(error)
+            }
+        })
+    };
+    let (frame, events) = context
+        .decorate_with_queue(connection.surface(), (800, 600))
+        .unwrap();
+
+    frame.dispatch(&mut (), |f| {
+        f.set_min_content_size(400, 400);
+        f.set_max_content_size(200, 200);
+        f.commit(&State::new(300, 300), None);
+    });
+
+    let errors = errors.borrow();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind(), ErrorKind::InvalidFrameConfiguration);
+    assert_eq!(errors[0].frame(), Some(frame.id()));
+    assert_eq!(events.try_recv(), Ok(FrameEvent::Error(errors[0].clone())));
+}
+
+#[test]
+fn errors_are_only_reported_to_the_frame_in_use() {
+    let connection = Connection::new();
+    let context_errors = Rc::new(RefCell::new(Vec::new()));
+    let context = {
+        let errors = context_errors.clone();
+        Context::new(connection.display.clone(), move |request| {
+            if let Request::Error(error) = request {
+                errors.borrow_mut().push(error.frame())
+            }
+        })
+    };
+    let context_ptr = *mock::contexts().last().unwrap();
+    let handler = |frame: &libdecor::FrameRef, request: &FrameRequest<'_>, log: &mut Vec<_>| {
+        if let FrameRequest::Error(error) = request {
+            assert_eq!(error.frame(), Some(frame.id()));
+            log.push(frame.id());
+        }
+    };
+    let first = context.decorate(connection.surface(), handler).unwrap();
+    let second = context.decorate(connection.surface(), handler).unwrap();
+
+    let mut log = Vec::new();
+    second.dispatch(&mut log, |f| {
+        f.set_min_content_size(400, 400);
+        f.set_max_content_size(200, 200);
+        f.commit(&State::new(300, 300), None);
+    });
+    // Not raised while a frame is in use
+    mock::inject_error(context_ptr, ffi::LIBDECOR_ERROR_COMPOSITOR_INCOMPATIBLE, "");
+    context.dispatch(&mut log, None).unwrap();
+
+    assert_eq!(log, vec![second.id()]);
+    assert_eq!(*context_errors.borrow(), vec![Some(second.id()), None]);
+    drop(first);
+}
+
+#[test]
+fn unknown_errors_are_reported() {
+    let connection = Connection::new();
+    let errors = Rc::new(RefCell::new(Vec::new()));
+    let context: Context = {
+        let errors = errors.clone();
+        Context::new(connection.display.clone(), move |request| {
+            if let Request::Error(error) = request {
+                errors.borrow_mut().push(error.kind())
+            }
+        })
+    };
+
+    mock::inject_error(*mock::contexts().last().unwrap(), 42, "from the future");
+    context.dispatch(&mut (), None).unwrap();
+    assert_eq!(*errors.borrow(), vec![ErrorKind::Unknown(42)]);
+}
+
+fn error_counting_context(connection: &Connection) -> (Context, Rc<RefCell<usize>>) {
+    let errors = Rc::new(RefCell::new(0));
+    let context = {
+        let errors = errors.clone();
+        Context::new(connection.display.clone(), move |_| {
+            *errors.borrow_mut() += 1
+        })
+    };
+    (context, errors)
+}
+
+/// Returns the errors received by the first and second context.
+fn contexts_receive_their_errors() -> (usize, usize) {
+    let connection = Connection::new();
+    let (first, first_errors) = error_counting_context(&connection);
+    let (second, second_errors) = error_counting_context(&connection);
+
+    let frame = second.decorate(connection.surface(), |_, _, _| {}).unwrap();
+    frame.dispatch(&mut (), |f| {
+        f.set_min_content_size(400, 400);
+        f.set_max_content_size(200, 200);
+        f.commit(&State::new(300, 300), None);
+    });
+    drop(frame);
+    drop(second);
+
+    drop(first);
+    let errors = (*first_errors.borrow(), *second_errors.borrow());
+    errors
+}
+
+#[test]
+fn multiple_contexts() {
+    assert_eq!(contexts_receive_their_errors(), (0, 1));
+    let spawned = std::thread::spawn(contexts_receive_their_errors).join();
+    assert_eq!(spawned.ok(), Some((0, 1)));
+}
+
+#[test]
+fn contexts_can_be_used_in_error_callbacks() {
+    let connection = Connection::new();
+    let replacement: Rc<RefCell<Option<Context>>> = Rc::default();
+    let frame: Rc<RefCell<Option<WeakFrameHandle>>> = Rc::default();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let context = {
+        let display = connection.display.clone();
+        let replacement = replacement.clone();
+        let frame = frame.clone();
+        let log = log.clone();
+        Context::new(connection.display.clone(), move |request| {
+            let error = match request {
+                Request::Error(error) => error,
+                _ => return,
+            };
+            log.borrow_mut().push(error.kind());
+
+            if error.kind() == ErrorKind::CompositorIncompatible {
+                // Reconnect, dropping the previous replacement
+                let context = Context::new(display.clone(), |_| {});
+                context.dispatch(&mut (), None).unwrap();
+                *replacement.borrow_mut() = Some(context);
+
+                // Raises another error while this one is delivered
+                let frame = frame.borrow().as_ref().and_then(|f| f.upgrade());
+                if let Some(frame) = frame {
+                    frame.dispatch(&mut (), |f| f.commit(&State::new(300, 300), None));
+                }
+            }
+        })
+    };
+    let decorated = context
+        .decorate(connection.surface(), |_, _, _: &mut ()| {})
+        .unwrap();
+    decorated.dispatch(&mut (), |f| {
+        f.set_min_content_size(400, 400);
+        f.set_max_content_size(200, 200);
+    });
+    *frame.borrow_mut() = Some(decorated.downgrade());
+
+    let context_ptr = mock::contexts()[0];
+    for _ in 0..2 {
+        mock::inject_error(
+            context_ptr,
+            ffi::LIBDECOR_ERROR_COMPOSITOR_INCOMPATIBLE,
+            "incompatible",
+        );
+    }
+    context.dispatch(&mut (), None).unwrap();
+
+    assert_eq!(
+        *log.borrow(),
+        vec![
+            ErrorKind::CompositorIncompatible,
+            ErrorKind::InvalidFrameConfiguration,
+            ErrorKind::CompositorIncompatible,
+            ErrorKind::InvalidFrameConfiguration,
+        ]
+    );
+    // The first replacement has been dropped by the second error
+    assert_eq!(mock::contexts().len(), 2);
+    assert!(replacement.borrow().is_some());
+}
+
+#[test]
+fn queued_events() {
+    let connection = Connection::new();
+    let context = Context::new(connection.display.clone(), |_| {});
+    let (frame, events) = context
+        .decorate_with_queue(connection.surface(), (800, 600))
+        .unwrap();
+
+    mock::inject(last_frame(), configure(None));
+    mock::inject(last_frame(), mock::Event::Close);
+    context.dispatch(&mut (), None).unwrap();
+
+    // The capabilities are announced before the first configure
+    #[cfg(feature = "libdecor_0_2")]
+    assert!(matches!(
+        events.try_recv(),
+        Ok(FrameEvent::WmCapabilities(_))
+    ));
+    assert_eq!(
+        events.try_iter().collect::<Vec<_>>(),
+        vec![
+            FrameEvent::Configure {
+                content_size: (800, 600),
+                window_state: Some(WindowState::ACTIVE),
+            },
+            FrameEvent::Close,
+        ]
+    );
+    assert_eq!(
+        mock::frame_info(last_frame()).unwrap().committed,
+        vec![(800, 600)]
+    );
+
+    drop(frame);
+    assert_eq!(events.try_recv(), Err(libdecor::TryRecvError::Disconnected));
+}
+
+#[test]
+fn queued_events_are_owned() {
+    let connection = Connection::new();
+    let context = Context::new(connection.display.clone(), |_| {});
+    let (frame, events) = context
+        .decorate_with_queue(connection.surface(), (800, 600))
+        .unwrap();
+
+    mock::inject(last_frame(), configure(Some((640, 480))));
+    mock::inject(last_frame(), mock::Event::Commit);
+    mock::inject(
+        last_frame(),
+        mock::Event::DismissPopup {
+            seat_name: "seat0".into(),
+        },
+    );
+    // Keeps the last committed size
+    mock::inject(last_frame(), configure(None));
+    context.dispatch(&mut (), None).unwrap();
+
+    // The events can be kept after the frame is gone
+    drop(frame);
+    #[cfg(feature = "libdecor_0_2")]
+    assert!(matches!(
+        events.try_recv(),
+        Ok(FrameEvent::WmCapabilities(_))
+    ));
+    let events = events.try_iter().collect::<Vec<_>>();
+    let configure = FrameEvent::Configure {
+        content_size: (640, 480),
+        window_state: Some(WindowState::ACTIVE),
+    };
+    assert_eq!(
+        events,
+        vec![
+            configure.clone(),
+            FrameEvent::Commit,
+            FrameEvent::DismissPopup {
+                seat_name: "seat0".into(),
+            },
+            configure,
+        ]
+    );
+}
+
+#[test]
+fn reentrant_callbacks_are_deferred() {
+    let connection = Connection::new();
+    let context = Context::new(connection.display.clone(), |_| {});
+    let frame = context
+        .decorate(connection.surface(), |frame, request, log: &mut Vec<_>| {
+            match request {
+                FrameRequest::Configure(_) => {
+                    log.push("configure");
+                    // Invokes the close callback synchronously
+                    frame.close();
+                    log.push("configure done");
+                }
+                FrameRequest::Close => log.push("close"),
+                _ => {}
+            }
+        })
+        .unwrap();
+
+    mock::inject(last_frame(), configure(None));
+
+    let mut log = Vec::new();
+    context.dispatch(&mut log, None).unwrap();
+    assert_eq!(log, vec!["configure", "configure done", "close"]);
+    drop(frame);
+}
+
+#[test]
+fn panics_are_resumed_after_dispatch() {
+    let connection = Connection::new();
+    let context = Context::new(connection.display.clone(), |_| {});
+    let _frame = context
+        .decorate(connection.surface(), |_, request, _: &mut ()| {
+            if let FrameRequest::Close = request {
+                panic!("close");
+            }
+        })
+        .unwrap();
+
+    mock::inject(last_frame(), mock::Event::Close);
+    mock::inject(last_frame(), mock::Event::Commit);
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| context.dispatch(&mut (), None)));
+    let payload = result.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"close"));
+
+    // All queued events have been delivered before the panic was resumed
+    assert_eq!(context.dispatch(&mut (), None).unwrap(), 0);
+}
+
+#[test]
+fn context_callback_panics_are_resumed_after_dispatch() {
+    let connection = Connection::new();
+    let context = Context::<()>::new(connection.display.clone(), |request| {
+        if let Request::Error(_) = request {
+            panic!("error");
+        }
+    });
+    let context_ptr = *mock::contexts().last().unwrap();
+
+    mock::inject_error(context_ptr, ffi::LIBDECOR_ERROR_COMPOSITOR_INCOMPATIBLE, "");
+    let result = panic::catch_unwind(AssertUnwindSafe(|| context.dispatch(&mut (), None)));
+    let payload = result.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"error"));
+
+    // The context is still usable
+    assert_eq!(context.dispatch(&mut (), None).unwrap(), 0);
+}
+
+#[test]
+fn synchronous_panics_are_resumed_after_frame_dispatch() {
+    let connection = Connection::new();
+    let context = Context::new(connection.display.clone(), |_| {});
+    let frame = context
+        .decorate(connection.surface(), |_, request, _: &mut ()| {
+            if let FrameRequest::Close = request {
+                panic!("close");
+            }
+        })
+        .unwrap();
+
+    let mut returned = false;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        frame.dispatch(&mut (), |f| {
+            f.close();
+            returned = true;
+        })
+    }));
+    assert!(returned);
+    let payload = result.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"close"));
+
+    frame.dispatch(&mut (), |f| f.set_title("still usable"));
+    assert_eq!(
+        mock::frame_info(last_frame()).unwrap().title.as_deref(),
+        Some("still usable")
+    );
+}
+
+#[cfg(feature = "calloop")]
+#[test]
+fn event_source_dispatches_with_the_loop_data() {
+    use libdecor::{ContextEvent, ContextSource};
+
+    #[derive(Default)]
+    struct Data {
+        configured: Vec<(i32, i32)>,
+        errors: Vec<ErrorKind>,
+    }
+
+    let connection = Connection::new();
+    let source = ContextSource::<Data>::new(connection.display.clone());
+    let frame = source
+        .context()
+        .decorate(connection.surface(), |frame, request, data: &mut Data| {
+            if let FrameRequest::Configure(configuration) = request {
+                let size = configuration.content_size(frame).unwrap();
+                frame.commit(&State::new(size.0, size.1), Some(configuration));
+                data.configured.push(size);
+            }
+        })
+        .unwrap();
+    frame.dispatch(&mut Data::default(), |f| f.map());
+    let context_ptr = *mock::contexts().last().unwrap();
+
+    let mut event_loop = calloop::EventLoop::<Data>::try_new().unwrap();
+    event_loop
+        .handle()
+        .insert_source(source, |event, context, data| match event {
+            ContextEvent::Dispatch => context
+                .dispatch(data, Some(std::time::Duration::ZERO))
+                .map(|_| ()),
+            ContextEvent::Context(Request::Error(error)) => {
+                data.errors.push(error.kind());
+                Ok(())
+            }
+            _ => Ok(()),
+        })
+        .unwrap();
+
+    mock::inject(last_frame(), configure(Some((800, 600))));
+    mock::inject_error(
+        context_ptr,
+        ffi::LIBDECOR_ERROR_COMPOSITOR_INCOMPATIBLE,
+        "incompatible",
+    );
+    let mut data = Data::default();
+    event_loop
+        .dispatch(Some(std::time::Duration::ZERO), &mut data)
+        .unwrap();
+
+    assert_eq!(data.configured, vec![(800, 600)]);
+    assert_eq!(data.errors, vec![ErrorKind::CompositorIncompatible]);
+    let info = mock::frame_info(last_frame()).unwrap();
+    assert_eq!(info.committed, vec![(800, 600)]);
+}
+
+#[cfg(feature = "calloop")]
+#[test]
+fn event_source_delivers_requests_raised_outside_of_the_loop() {
+    use libdecor::{ContextEvent, ContextSource};
+
+    let connection = Connection::new();
+    let source = ContextSource::<Vec<ErrorKind>>::new(connection.display.clone());
+    let frame = source
+        .context()
+        .decorate(connection.surface(), |_, _, _: &mut Vec<_>| {})
+        .unwrap();
+    frame.dispatch(&mut Vec::new(), |f| {
+        f.set_min_content_size(400, 400);
+        f.set_max_content_size(200, 200);
+        f.commit(&State::new(300, 300), None);
+    });
+
+    let mut event_loop = calloop::EventLoop::<Vec<ErrorKind>>::try_new().unwrap();
+    event_loop
+        .handle()
+        .insert_source(source, |event, context, errors| match event {
+            ContextEvent::Dispatch => context
+                .dispatch(errors, Some(std::time::Duration::ZERO))
+                .map(|_| ()),
+            ContextEvent::Context(Request::Error(error)) => {
+                errors.push(error.kind());
+                Ok(())
+            }
+            _ => Ok(()),
+        })
+        .unwrap();
+
+    // Nothing is readable, the error is delivered before polling
+    let mut errors = Vec::new();
+    event_loop
+        .dispatch(Some(std::time::Duration::ZERO), &mut errors)
+        .unwrap();
+    assert_eq!(errors, vec![ErrorKind::InvalidFrameConfiguration]);
+}
+
+/// Dispatches a configure and a close, returns the events of the stream.
+#[cfg(any(feature = "async-io", feature = "tokio"))]
+async fn async_events(
+    context: &libdecor::AsyncContext,
+    connection: &Connection,
+) -> Vec<FrameEvent> {
+    use futures_util::StreamExt;
+
+    let (frame, events) = context.decorate(connection.surface(), (800, 600)).unwrap();
+    mock::inject(last_frame(), configure(Some((640, 480))));
+    mock::inject(last_frame(), mock::Event::Close);
+
+    assert_eq!(context.dispatch_ready().await.unwrap(), 2);
+    assert_eq!(
+        mock::frame_info(last_frame()).unwrap().committed,
+        vec![(640, 480)]
+    );
+    drop(frame);
+    events.collect().await
+}
+
+#[cfg(any(feature = "async-io", feature = "tokio"))]
+fn expected_async_events() -> Vec<FrameEvent> {
+    let mut expected = Vec::new();
+    #[cfg(feature = "libdecor_0_2")]
+    expected.push(FrameEvent::WmCapabilities(libdecor::WmCapabilities::all()));
+    expected.push(FrameEvent::Configure {
+        content_size: (640, 480),
+        window_state: Some(WindowState::ACTIVE),
+    });
+    expected.push(FrameEvent::Close);
+    expected
+}
+
+#[cfg(feature = "async-io")]
+#[test]
+fn async_io_dispatches_ready_events() {
+    let connection = Connection::new();
+    let context = libdecor::AsyncContext::new_async_io(connection.display.clone(), |_| {}).unwrap();
+
+    let events = async_io::block_on(async_events(&context, &connection));
+    assert_eq!(events, expected_async_events());
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn tokio_dispatches_ready_events() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()
+        .unwrap();
+    let connection = Connection::new();
+
+    let events = runtime.block_on(async {
+        let context =
+            libdecor::AsyncContext::new_tokio(connection.display.clone(), |_| {}).unwrap();
+        async_events(&context, &connection).await
+    });
+    assert_eq!(events, expected_async_events());
+}
+
+#[cfg(feature = "libdecor_0_2")]
+#[test]
+fn wm_capabilities_changes_are_queued() {
+    use libdecor::WmCapabilities;
+
+    let connection = Connection::new();
+    let context = Context::new(connection.display.clone(), |_| {});
+    let (frame, events) = context
+        .decorate_with_queue(connection.surface(), (800, 600))
+        .unwrap();
+
+    mock::inject(last_frame(), configure(None));
+    context.dispatch(&mut (), None).unwrap();
+    mock::set_wm_capabilities(last_frame(), ffi::LIBDECOR_WM_CAPABILITIES_WINDOW_MENU);
+    mock::inject(last_frame(), configure(None));
+    mock::inject(last_frame(), configure(None));
+    context.dispatch(&mut (), None).unwrap();
+
+    let configure = FrameEvent::Configure {
+        content_size: (800, 600),
+        window_state: Some(WindowState::ACTIVE),
+    };
+    assert_eq!(
+        events.try_iter().collect::<Vec<_>>(),
+        vec![
+            FrameEvent::WmCapabilities(WmCapabilities::all()),
+            configure.clone(),
+            FrameEvent::WmCapabilities(WmCapabilities::WINDOW_MENU),
+            configure.clone(),
+            configure,
+        ]
+    );
+    assert_eq!(
+        frame.dispatch(&mut (), |f| f.wm_capabilities()),
+        Ok(WmCapabilities::WINDOW_MENU)
+    );
+    assert_eq!(
+        libdecor::libdecor_version(),
+        Some(libdecor::LibdecorVersion::V0_2)
+    );
+}
+
+#[cfg(feature = "plugin")]
+#[test]
+fn plugins_are_constructed_for_a_context() {
+    use std::{io, os::unix::io::RawFd, time::Duration};
+
+    use libdecor::{
+        plugin::{BorderSize, Plugin, PluginContext, PluginFrameRef, StateRef, WeakPluginFrame},
+        Configuration,
+    };
+
+    struct Minimal;
+
+    impl Plugin for Minimal {
+        type Frame = ();
+
+        fn new(context: PluginContext) -> Option<Self> {
+            context.notify_error(ErrorKind::CompositorIncompatible, "not supported");
+            context.notify_ready();
+            Some(Minimal)
+        }
+
+        fn fd(&self) -> RawFd {
+            -1
+        }
+
+        fn dispatch(&self, _: Option<Duration>) -> io::Result<usize> {
+            Ok(0)
+        }
+
+        fn frame_new(&self, _: WeakPluginFrame) -> Self::Frame {}
+
+        fn frame_commit(
+            &self,
+            _: &PluginFrameRef,
+            _: &Self::Frame,
+            _: &StateRef<'_>,
+            _: Option<&Configuration<'_>>,
+        ) {
+        }
+
+        fn frame_border_size(
+            &self,
+            _: &PluginFrameRef,
+            _: &Self::Frame,
+            _: Option<&Configuration<'_>>,
+        ) -> Option<BorderSize> {
+            None
+        }
+    }
+
+    libdecor::libdecor_plugin! {
+        plugin: Minimal,
+        description: "Minimal decorations",
+        priorities: {
+            _ => libdecor::plugin::PRIORITY_LOW,
+        },
+    }
+
+    let connection = Connection::new();
+    let errors = Rc::new(RefCell::new(Vec::new()));
+    let _context: Context = {
+        let errors = errors.clone();
+        Context::new(connection.display.clone(), move |request| {
+            if let Request::Error(error) = request {
+                errors.borrow_mut().push(error)
+            }
+        })
+    };
+
+    let plugin = unsafe { (libdecor_plugin_description.0.constructor)(mock::contexts()[0]) };
+    assert!(!plugin.is_null());
+    assert_eq!(mock::plugins().len(), 1);
+    assert!(mock::calls()
+        .iter()
+        .any(|call| call.function == "libdecor_notify_plugin_ready"));
+    {
+        let errors = errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind(), ErrorKind::CompositorIncompatible);
+        assert_eq!(errors[0].to_string(), "not supported");
+    }
+
+    let (_, interface) = mock::plugins()[0];
+    unsafe { ((*interface).destroy)(plugin) };
+    assert!(mock::plugins().is_empty());
+}
+
+#[test]
+fn plugins_are_selected_through_the_plugin_dir() {
+    use libdecor::{ActivePlugin, ContextBuilder};
+    use std::{env, fs, path::PathBuf};
+
+    let plugin_dir = env::temp_dir().join(format!("libdecor-rs-plugins-{}", std::process::id()));
+    fs::create_dir_all(&plugin_dir).unwrap();
+    for plugin in ["libdecor-cairo.so", "libdecor-gtk.so"] {
+        fs::write(plugin_dir.join(plugin), []).unwrap();
+    }
+    let previous = env::var_os("LIBDECOR_PLUGIN_DIR");
+
+    let connection = Connection::new();
+    let build = |builder: ContextBuilder| {
+        let context: Context = builder.build(connection.display.clone(), |_| {}).unwrap();
+        let selected = mock::plugin_dir(*mock::contexts().last().unwrap()).unwrap();
+        // The variable is only overridden while creating the context
+        assert_eq!(env::var_os("LIBDECOR_PLUGIN_DIR"), previous);
+        (context, selected)
+    };
+
+    let (_context, selected) = build(ContextBuilder::new().plugin_dir(&plugin_dir));
+    assert_eq!(selected.path, Some(plugin_dir.clone()));
+    assert_eq!(selected.files, vec!["libdecor-cairo.so", "libdecor-gtk.so"]);
+
+    // Only the preferred plugin is visible to libdecor
+    let (_context, selected) = build(
+        ContextBuilder::new()
+            .plugin_dir(&plugin_dir)
+            .preferred_plugin("libdecor-gtk"),
+    );
+    let staging: PathBuf = selected.path.unwrap();
+    assert_ne!(staging, plugin_dir);
+    assert_eq!(selected.files, vec!["libdecor-gtk.so"]);
+    assert!(!staging.exists());
+
+    // An unavailable plugin is not preferred
+    let (context, selected) = build(
+        ContextBuilder::new()
+            .plugin_dir(&plugin_dir)
+            .preferred_plugin("libdecor-missing"),
+    );
+    assert_eq!(selected.path, Some(plugin_dir.clone()));
+    assert_eq!(selected.files.len(), 2);
+
+    // The mock does not load any plugin
+    assert_eq!(context.active_plugin(), &ActivePlugin::Builtin);
+
+    fs::remove_dir_all(&plugin_dir).unwrap();
+}
+
+#[cfg(feature = "plugin")]
+#[test]
+fn plugin_panics_do_not_unwind_into_libdecor() {
+    use std::{io, os::unix::io::RawFd, ptr, time::Duration};
+
+    use libdecor::{
+        plugin::{
+            construct, BorderSize, Plugin, PluginContext, PluginFrameRef, StateRef, WeakPluginFrame,
+        },
+        Configuration,
+    };
+
+    struct Faulty;
+
+    impl Plugin for Faulty {
+        type Frame = ();
+
+        fn new(context: PluginContext) -> Option<Self> {
+            context.notify_ready();
+            Some(Faulty)
+        }
+
+        fn fd(&self) -> RawFd {
+            panic!("fd")
+        }
+
+        fn dispatch(&self, _: Option<Duration>) -> io::Result<usize> {
+            panic!("dispatch")
+        }
+
+        fn frame_new(&self, _: WeakPluginFrame) -> Self::Frame {
+            panic!("frame_new")
+        }
+
+        fn frame_commit(
+            &self,
+            _: &PluginFrameRef,
+            _: &Self::Frame,
+            _: &StateRef<'_>,
+            _: Option<&Configuration<'_>>,
+        ) {
+        }
+
+        fn frame_border_size(
+            &self,
+            _: &PluginFrameRef,
+            _: &Self::Frame,
+            _: Option<&Configuration<'_>>,
+        ) -> Option<BorderSize> {
+            None
+        }
+    }
+
+    struct Broken;
+
+    impl Plugin for Broken {
+        type Frame = ();
+
+        fn new(_: PluginContext) -> Option<Self> {
+            panic!("new")
+        }
+
+        fn fd(&self) -> RawFd {
+            -1
+        }
+
+        fn dispatch(&self, _: Option<Duration>) -> io::Result<usize> {
+            Ok(0)
+        }
+
+        fn frame_new(&self, _: WeakPluginFrame) -> Self::Frame {}
+
+        fn frame_commit(
+            &self,
+            _: &PluginFrameRef,
+            _: &Self::Frame,
+            _: &StateRef<'_>,
+            _: Option<&Configuration<'_>>,
+        ) {
+        }
+
+        fn frame_border_size(
+            &self,
+            _: &PluginFrameRef,
+            _: &Self::Frame,
+            _: Option<&Configuration<'_>>,
+        ) -> Option<BorderSize> {
+            None
+        }
+    }
+
+    let connection = Connection::new();
+    let _context: Context = Context::new(connection.display.clone(), |_| {});
+    let context_ptr = *mock::contexts().last().unwrap();
+
+    assert!(unsafe { construct::<Broken>(context_ptr) }.is_null());
+
+    let plugin = unsafe { construct::<Faulty>(context_ptr) };
+    assert!(!plugin.is_null());
+    let (_, interface) = *mock::plugins().last().unwrap();
+    unsafe {
+        assert_eq!(((*interface).get_fd)(plugin), -1);
+        assert_eq!(((*interface).dispatch)(plugin, 0), -1);
+        assert_eq!(((*interface).frame_new)(plugin), ptr::null_mut());
+        ((*interface).destroy)(plugin);
+    }
+}
@@ -0,0 +1,607 @@
+//! A headless Wayland compositor for testing libdecor
+//!
+//! The compositor implements just enough of the core and xdg-shell protocols
+//! (`wl_compositor`, `wl_subcompositor`, `wl_shm`, `wl_seat`, `xdg_wm_base`
+//! and optionally `zxdg_decoration_manager_v1`) to map decorated windows. It
+//! runs on a background thread, listens on a socket in a temporary directory
+//! and records everything interesting for later inspection.
+//!
+//! ```no_run
+//! # use libdecor_test_compositor::{Config, TestCompositor};
+//! # use std::time::Duration;
+//! let compositor = TestCompositor::spawn(Config::default()).unwrap();
+//!
+//! // Connect with e.g. `WAYLAND_DISPLAY=<socket>` or `Display::connect_to_name`
+//! let socket = compositor.socket();
+//!
+//! assert!(compositor.wait_for(Duration::from_secs(5), |records| {
+//!     records.toplevels.len() == 1
+//! }));
+//! compositor.configure(0, (800, 600), Vec::new());
+//! ```
+
+#![warn(missing_docs, missing_debug_implementations)]
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::File,
+    io,
+    os::unix::{io::FromRawFd, net::UnixListener},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use wayland_protocols::{
+    unstable::xdg_decoration::v1::server::{
+        zxdg_decoration_manager_v1, zxdg_toplevel_decoration_v1,
+    },
+    xdg_shell::server::{xdg_surface, xdg_toplevel, xdg_wm_base},
+};
+use wayland_server::{
+    protocol::{
+        wl_buffer, wl_callback, wl_compositor, wl_seat, wl_shm, wl_shm_pool, wl_subcompositor,
+        wl_surface,
+    },
+    Display, Filter, Main,
+};
+
+pub use wayland_protocols::{
+    unstable::xdg_decoration::v1::server::zxdg_toplevel_decoration_v1::Mode as DecorationMode,
+    xdg_shell::server::xdg_toplevel::State as ToplevelState,
+};
+pub use wayland_server::protocol::wl_shm::Format;
+
+/// Configuration of a [`TestCompositor`]
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// Advertise `zxdg_decoration_manager_v1`
+    pub xdg_decoration: bool,
+    /// The decoration mode the compositor answers with, [`None`] to use
+    /// the mode requested by the client
+    pub decoration_mode: Option<DecorationMode>,
+    /// The size of the initial configure, `(0, 0)` lets the client decide
+    pub initial_size: (i32, i32),
+}
+
+/// A committed `wl_shm` buffer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BufferRecord {
+    /// The width of the buffer
+    pub width: i32,
+    /// The height of the buffer
+    pub height: i32,
+    /// The stride of the buffer
+    pub stride: i32,
+    /// The pixel format of the buffer
+    pub format: Format,
+}
+
+/// A `wl_surface` commit
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitRecord {
+    /// The protocol id of the surface
+    pub surface: u32,
+    /// The index of the toplevel if the surface has the toplevel role
+    pub toplevel: Option<usize>,
+    /// The buffer attached at the time of the commit
+    pub buffer: Option<BufferRecord>,
+}
+
+/// The state of an `xdg_toplevel`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToplevelRecord {
+    /// The title set by the client
+    pub title: Option<String>,
+    /// The application id set by the client
+    pub app_id: Option<String>,
+    /// The min size set by the client
+    pub min_size: (i32, i32),
+    /// The max size set by the client
+    pub max_size: (i32, i32),
+    /// The last window geometry set by the client as `(x, y, width, height)`
+    pub window_geometry: Option<(i32, i32, i32, i32)>,
+    /// The serials of all sent configures
+    pub configured: Vec<u32>,
+    /// The serials of all acknowledged configures
+    pub acked: Vec<u32>,
+    /// The negotiated decoration mode
+    pub decoration_mode: Option<DecorationMode>,
+    /// Whether the toplevel has been destroyed
+    pub destroyed: bool,
+}
+
+/// Everything recorded by a [`TestCompositor`]
+#[derive(Debug, Clone, Default)]
+pub struct Records {
+    /// All toplevels in creation order
+    pub toplevels: Vec<ToplevelRecord>,
+    /// All surface commits
+    pub commits: Vec<CommitRecord>,
+}
+
+enum Command {
+    Configure {
+        toplevel: usize,
+        size: (i32, i32),
+        states: Vec<ToplevelState>,
+    },
+    Close {
+        toplevel: usize,
+    },
+    Stop,
+}
+
+/// A headless compositor running on a background thread
+///
+/// The compositor is stopped when dropped.
+#[derive(Debug)]
+pub struct TestCompositor {
+    socket: PathBuf,
+    commands: Sender<Command>,
+    records: Arc<Mutex<Records>>,
+    thread: Option<JoinHandle<()>>,
+    _dir: tempfile::TempDir,
+}
+
+impl std::fmt::Debug for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Command::Configure { toplevel, size, .. } => f
+                .debug_struct("Configure")
+                .field("toplevel", toplevel)
+                .field("size", size)
+                .finish(),
+            Command::Close { toplevel } => {
+                f.debug_struct("Close").field("toplevel", toplevel).finish()
+            }
+            Command::Stop => f.write_str("Stop"),
+        }
+    }
+}
+
+impl TestCompositor {
+    /// Start a new compositor listening on a socket in a temporary directory.
+    pub fn spawn(config: Config) -> io::Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let socket = dir.path().join("wayland-test");
+        let listener = UnixListener::bind(&socket)?;
+
+        let records = Arc::new(Mutex::new(Records::default()));
+        let (commands, receiver) = mpsc::channel();
+
+        let thread = {
+            let records = records.clone();
+            thread::Builder::new()
+                .name("test-compositor".into())
+                .spawn(move || run(listener, config, records, receiver))?
+        };
+
+        Ok(TestCompositor {
+            socket,
+            commands,
+            records,
+            thread: Some(thread),
+            _dir: dir,
+        })
+    }
+
+    /// The absolute path of the listening socket
+    ///
+    /// Can be used as `WAYLAND_DISPLAY` or passed to `Display::connect_to_name`.
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// Send a configure with the given size and states to a toplevel.
+    pub fn configure(&self, toplevel: usize, size: (i32, i32), states: Vec<ToplevelState>) {
+        let _ = self.commands.send(Command::Configure {
+            toplevel,
+            size,
+            states,
+        });
+    }
+
+    /// Ask a toplevel to close.
+    pub fn close(&self, toplevel: usize) {
+        let _ = self.commands.send(Command::Close { toplevel });
+    }
+
+    /// Returns a copy of everything recorded so far.
+    pub fn records(&self) -> Records {
+        self.records.lock().unwrap().clone()
+    }
+
+    /// Wait until `f` returns [`true`] for the recorded state.
+    ///
+    /// Returns [`false`] if the timeout elapsed before.
+    pub fn wait_for<F>(&self, timeout: Duration, f: F) -> bool
+    where
+        F: Fn(&Records) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if f(&self.records.lock().unwrap()) {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
+
+impl Drop for TestCompositor {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct Toplevel {
+    surface: u32,
+    xdg_surface: xdg_surface::XdgSurface,
+    toplevel: xdg_toplevel::XdgToplevel,
+    configured: bool,
+}
+
+struct State {
+    config: Config,
+    records: Arc<Mutex<Records>>,
+    toplevels: Vec<Toplevel>,
+    buffers: HashMap<u32, Option<BufferRecord>>,
+    frame_callbacks: HashMap<u32, Vec<wl_callback::WlCallback>>,
+    serial: u32,
+}
+
+type SharedState = Rc<RefCell<State>>;
+
+impl State {
+    fn records(&self) -> std::sync::MutexGuard<'_, Records> {
+        self.records.lock().unwrap()
+    }
+
+    fn configure(&mut self, index: usize, size: (i32, i32), states: &[ToplevelState]) {
+        self.serial += 1;
+        let serial = self.serial;
+
+        let toplevel = match self.toplevels.get_mut(index) {
+            Some(toplevel) => toplevel,
+            None => return,
+        };
+        let states = states
+            .iter()
+            .flat_map(|state| state.to_raw().to_ne_bytes())
+            .collect();
+        toplevel.toplevel.configure(size.0, size.1, states);
+        toplevel.xdg_surface.configure(serial);
+        toplevel.configured = true;
+
+        self.records().toplevels[index].configured.push(serial);
+    }
+
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::Configure {
+                toplevel,
+                size,
+                states,
+            } => self.configure(toplevel, size, &states),
+            Command::Close { toplevel } => {
+                if let Some(toplevel) = self.toplevels.get(toplevel) {
+                    toplevel.toplevel.close();
+                }
+            }
+            Command::Stop => {}
+        }
+    }
+
+    fn commit(&mut self, surface: &wl_surface::WlSurface) {
+        let id = surface.as_ref().id();
+        let buffer = self.buffers.get(&id).cloned().flatten();
+        let toplevel = self.toplevels.iter().position(|t| t.surface == id);
+
+        self.records().commits.push(CommitRecord {
+            surface: id,
+            toplevel,
+            buffer,
+        });
+
+        for callback in self.frame_callbacks.remove(&id).unwrap_or_default() {
+            callback.done(0);
+        }
+
+        // The initial commit has to be answered with a configure
+        if let Some(index) = toplevel {
+            if !self.toplevels[index].configured {
+                let size = self.config.initial_size;
+                self.configure(index, size, &[]);
+            }
+        }
+    }
+}
+
+fn run(
+    listener: UnixListener,
+    config: Config,
+    records: Arc<Mutex<Records>>,
+    commands: Receiver<Command>,
+) {
+    let mut display = Display::new();
+    if display.add_socket_from(listener).is_err() {
+        return;
+    }
+
+    let state = Rc::new(RefCell::new(State {
+        config: config.clone(),
+        records,
+        toplevels: Vec::new(),
+        buffers: HashMap::new(),
+        frame_callbacks: HashMap::new(),
+        serial: 0,
+    }));
+
+    create_globals(&mut display, &state, &config);
+
+    loop {
+        loop {
+            match commands.try_recv() {
+                Ok(Command::Stop) | Err(TryRecvError::Disconnected) => return,
+                Ok(command) => state.borrow_mut().handle(command),
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+
+        if display.dispatch(Duration::from_millis(5), &mut ()).is_err() {
+            return;
+        }
+        display.flush_clients(&mut ());
+    }
+}
+
+fn create_globals(display: &mut Display, state: &SharedState, config: &Config) {
+    {
+        let state = state.clone();
+        display.create_global::<wl_compositor::WlCompositor, _>(
+            4,
+            Filter::new(
+                move |(compositor, _): (Main<wl_compositor::WlCompositor>, u32), _, _| {
+                    let state = state.clone();
+                    compositor.quick_assign(move |_, request, _| match request {
+                        wl_compositor::Request::CreateSurface { id } => {
+                            assign_surface(id, state.clone())
+                        }
+                        wl_compositor::Request::CreateRegion { id } => {
+                            id.quick_assign(|_, _, _| {})
+                        }
+                        _ => {}
+                    });
+                },
+            ),
+        );
+    }
+
+    display.create_global::<wl_subcompositor::WlSubcompositor, _>(
+        1,
+        Filter::new(
+            |(subcompositor, _): (Main<wl_subcompositor::WlSubcompositor>, u32), _, _| {
+                subcompositor.quick_assign(|_, request, _| {
+                    if let wl_subcompositor::Request::GetSubsurface { id, .. } = request {
+                        id.quick_assign(|_, _, _| {});
+                    }
+                });
+            },
+        ),
+    );
+
+    display.create_global::<wl_shm::WlShm, _>(
+        1,
+        Filter::new(|(shm, _): (Main<wl_shm::WlShm>, u32), _, _| {
+            shm.quick_assign(|_, request, _| {
+                if let wl_shm::Request::CreatePool { id, fd, .. } = request {
+                    // The content of the buffers is not inspected
+                    drop(unsafe { File::from_raw_fd(fd) });
+                    id.quick_assign(|_, request, _| {
+                        if let wl_shm_pool::Request::CreateBuffer {
+                            id,
+                            width,
+                            height,
+                            stride,
+                            format,
+                            ..
+                        } = request
+                        {
+                            let record = BufferRecord {
+                                width,
+                                height,
+                                stride,
+                                format,
+                            };
+                            id.quick_assign(|_, _: wl_buffer::Request, _| {});
+                            id.as_ref().user_data().set(move || record);
+                        }
+                    });
+                }
+            });
+            shm.format(Format::Argb8888);
+            shm.format(Format::Xrgb8888);
+        }),
+    );
+
+    display.create_global::<wl_seat::WlSeat, _>(
+        5,
+        Filter::new(|(seat, version): (Main<wl_seat::WlSeat>, u32), _, _| {
+            seat.quick_assign(|_, _, _| {});
+            seat.capabilities(wl_seat::Capability::empty());
+            if version >= 2 {
+                seat.name("seat0".into());
+            }
+        }),
+    );
+
+    {
+        let state = state.clone();
+        display.create_global::<xdg_wm_base::XdgWmBase, _>(
+            1,
+            Filter::new(
+                move |(wm_base, _): (Main<xdg_wm_base::XdgWmBase>, u32), _, _| {
+                    let state = state.clone();
+                    wm_base.quick_assign(move |_, request, _| match request {
+                        xdg_wm_base::Request::GetXdgSurface { id, surface } => {
+                            assign_xdg_surface(id, surface.as_ref().id(), state.clone())
+                        }
+                        xdg_wm_base::Request::CreatePositioner { id } => {
+                            id.quick_assign(|_, _, _| {})
+                        }
+                        _ => {}
+                    });
+                },
+            ),
+        );
+    }
+
+    if config.xdg_decoration {
+        let state = state.clone();
+        display.create_global::<zxdg_decoration_manager_v1::ZxdgDecorationManagerV1, _>(
+            1,
+            Filter::new(
+                move |(manager, _): (
+                    Main<zxdg_decoration_manager_v1::ZxdgDecorationManagerV1>,
+                    u32,
+                ),
+                      _,
+                      _| {
+                    let state = state.clone();
+                    manager.quick_assign(move |_, request, _| {
+                        if let zxdg_decoration_manager_v1::Request::GetToplevelDecoration {
+                            id,
+                            toplevel,
+                        } = request
+                        {
+                            assign_decoration(id, toplevel, state.clone());
+                        }
+                    });
+                },
+            ),
+        );
+    }
+}
+
+fn assign_surface(surface: Main<wl_surface::WlSurface>, state: SharedState) {
+    surface.quick_assign(move |surface, request, _| {
+        let id = surface.as_ref().id();
+        let mut state = state.borrow_mut();
+        match request {
+            wl_surface::Request::Attach { buffer, .. } => {
+                let buffer =
+                    buffer.and_then(|b| b.as_ref().user_data().get::<BufferRecord>().copied());
+                state.buffers.insert(id, buffer);
+            }
+            wl_surface::Request::Frame { callback } => {
+                state
+                    .frame_callbacks
+                    .entry(id)
+                    .or_default()
+                    .push((*callback).clone());
+            }
+            wl_surface::Request::Commit => state.commit(&surface),
+            wl_surface::Request::Destroy => {
+                state.buffers.remove(&id);
+                state.frame_callbacks.remove(&id);
+            }
+            _ => {}
+        }
+    });
+}
+
+fn assign_xdg_surface(
+    xdg_surface: Main<xdg_surface::XdgSurface>,
+    surface: u32,
+    state: SharedState,
+) {
+    xdg_surface.quick_assign(move |xdg_surface, request, _| match request {
+        xdg_surface::Request::GetToplevel { id } => {
+            let index = {
+                let mut state = state.borrow_mut();
+                state.toplevels.push(Toplevel {
+                    surface,
+                    xdg_surface: (*xdg_surface).clone(),
+                    toplevel: (*id).clone(),
+                    configured: false,
+                });
+                state.records().toplevels.push(ToplevelRecord::default());
+                state.toplevels.len() - 1
+            };
+            assign_toplevel(id, index, state.clone());
+        }
+        xdg_surface::Request::AckConfigure { serial } => {
+            let state = state.borrow();
+            if let Some(index) = state.toplevels.iter().position(|t| t.surface == surface) {
+                state.records().toplevels[index].acked.push(serial);
+            }
+        }
+        xdg_surface::Request::SetWindowGeometry {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            let state = state.borrow();
+            if let Some(index) = state.toplevels.iter().position(|t| t.surface == surface) {
+                state.records().toplevels[index].window_geometry = Some((x, y, width, height));
+            }
+        }
+        _ => {}
+    });
+}
+
+fn assign_toplevel(toplevel: Main<xdg_toplevel::XdgToplevel>, index: usize, state: SharedState) {
+    toplevel.quick_assign(move |_, request, _| {
+        let state = state.borrow();
+        let mut records = state.records();
+        let record = &mut records.toplevels[index];
+        match request {
+            xdg_toplevel::Request::SetTitle { title } => record.title = Some(title),
+            xdg_toplevel::Request::SetAppId { app_id } => record.app_id = Some(app_id),
+            xdg_toplevel::Request::SetMinSize { width, height } => {
+                record.min_size = (width, height)
+            }
+            xdg_toplevel::Request::SetMaxSize { width, height } => {
+                record.max_size = (width, height)
+            }
+            xdg_toplevel::Request::Destroy => record.destroyed = true,
+            _ => {}
+        }
+    });
+}
+
+fn assign_decoration(
+    decoration: Main<zxdg_toplevel_decoration_v1::ZxdgToplevelDecorationV1>,
+    toplevel: xdg_toplevel::XdgToplevel,
+    state: SharedState,
+) {
+    decoration.quick_assign(move |decoration, request, _| {
+        let requested = match request {
+            zxdg_toplevel_decoration_v1::Request::SetMode { mode } => mode,
+            zxdg_toplevel_decoration_v1::Request::UnsetMode => DecorationMode::ClientSide,
+            _ => return,
+        };
+
+        let state = state.borrow();
+        let mode = state.config.decoration_mode.unwrap_or(requested);
+        decoration.configure(mode);
+
+        if let Some(index) = state.toplevels.iter().position(|t| t.toplevel == toplevel) {
+            state.records().toplevels[index].decoration_mode = Some(mode);
+        }
+    });
+}
@@ -0,0 +1,21 @@
+//! Run the test compositor standalone, e.g. for `examples/demo.rs`:
+//!
+//! ```sh
+//! cargo run -p libdecor-test-compositor
+//! WAYLAND_DISPLAY=<printed socket> cargo run --example demo
+//! ```
+
+use libdecor_test_compositor::{Config, TestCompositor};
+
+fn main() {
+    let config = Config {
+        xdg_decoration: std::env::args().any(|arg| arg == "--xdg-decoration"),
+        ..Config::default()
+    };
+    let compositor = TestCompositor::spawn(config).expect("failed to start the compositor");
+    println!("WAYLAND_DISPLAY={}", compositor.socket().display());
+
+    loop {
+        std::thread::park();
+    }
+}
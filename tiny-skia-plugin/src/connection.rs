@@ -0,0 +1,271 @@
+use std::{
+    cell::{Cell, RefCell},
+    io,
+    rc::{Rc, Weak},
+    time::Duration,
+};
+
+use wayland_client::{
+    global_filter,
+    protocol::{
+        wl_compositor::WlCompositor,
+        wl_pointer::{self, WlPointer},
+        wl_seat::{self, WlSeat},
+        wl_shm::WlShm,
+        wl_subcompositor::WlSubcompositor,
+    },
+    Display, EventQueue, GlobalManager, Main,
+};
+
+use crate::frame::{self, Part, SkiaFrame};
+
+/// Globals required to draw the decorations
+pub(crate) struct Globals {
+    pub(crate) compositor: Main<WlCompositor>,
+    pub(crate) subcompositor: Main<WlSubcompositor>,
+    pub(crate) shm: Main<WlShm>,
+}
+
+type Seats = Rc<RefCell<Vec<Rc<Seat>>>>;
+
+/// The connection of the plugin to the compositor
+///
+/// All objects are created on a private event queue which is dispatched
+/// by [`Connection::dispatch`].
+pub(crate) struct Connection {
+    display: Display,
+    event_queue: RefCell<EventQueue>,
+    pub(crate) globals: Rc<Globals>,
+    seats: Seats,
+    _global_manager: GlobalManager,
+}
+
+impl Connection {
+    /// Returns [`None`] if the compositor lacks a required global.
+    pub(crate) fn new(display: Display) -> Option<Self> {
+        let mut event_queue = display.create_event_queue();
+        let attached_display = (*display).clone().attach(event_queue.token());
+
+        let seats: Seats = Rc::new(RefCell::new(Vec::new()));
+        let global_manager = GlobalManager::new_with_cb(
+            &attached_display,
+            global_filter!([WlSeat, 5, {
+                let seats = seats.clone();
+                move |seat: Main<WlSeat>, _: wayland_client::DispatchData| {
+                    seats.borrow_mut().push(Seat::new(seat));
+                }
+            }]),
+        );
+        event_queue.sync_roundtrip(&mut (), |_, _, _| {}).ok()?;
+
+        let globals = Globals {
+            compositor: global_manager.instantiate_range(1, 4).ok()?,
+            subcompositor: global_manager.instantiate_exact(1).ok()?,
+            shm: global_manager.instantiate_exact(1).ok()?,
+        };
+        globals.shm.quick_assign(|_, _, _| {});
+
+        // Receive the seat capabilities and names
+        event_queue.sync_roundtrip(&mut (), |_, _, _| {}).ok()?;
+
+        Some(Connection {
+            display,
+            event_queue: RefCell::new(event_queue),
+            globals: Rc::new(globals),
+            seats,
+            _global_manager: global_manager,
+        })
+    }
+
+    pub(crate) fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// Dispatch the private event queue, waiting up to `timeout` for new
+    /// events, [`None`] waits indefinitely.
+    pub(crate) fn dispatch(&self, timeout: Option<Duration>) -> io::Result<usize> {
+        // Nested dispatching from within a callback is a no-op
+        let mut event_queue = match self.event_queue.try_borrow_mut() {
+            Ok(event_queue) => event_queue,
+            Err(_) => return Ok(0),
+        };
+
+        let dispatched = event_queue.dispatch_pending(&mut (), |_, _, _| {})?;
+        if dispatched > 0 {
+            return Ok(dispatched as usize);
+        }
+
+        if let Err(err) = self.display.flush() {
+            if err.kind() != io::ErrorKind::WouldBlock {
+                return Err(err);
+            }
+        }
+
+        if let Some(guard) = event_queue.prepare_read() {
+            let mut fd = libc::pollfd {
+                fd: self.display.get_connection_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let timeout = timeout
+                .map(|timeout| timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int)
+                .unwrap_or(-1);
+            match unsafe { libc::poll(&mut fd, 1, timeout) } {
+                ret if ret < 0 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                0 => {}
+                _ => {
+                    if let Err(err) = guard.read_events() {
+                        if err.kind() != io::ErrorKind::WouldBlock {
+                            return Err(err);
+                        }
+                    }
+                }
+            }
+        }
+
+        event_queue
+            .dispatch_pending(&mut (), |_, _, _| {})
+            .map(|dispatched| dispatched as usize)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        for seat in self.seats.borrow().iter() {
+            seat.release();
+        }
+        self.globals.subcompositor.destroy();
+    }
+}
+
+/// Pointer handling of a seat for the decorations
+struct Seat {
+    seat: Main<WlSeat>,
+    name: RefCell<Option<String>>,
+    pointer: RefCell<Option<Main<WlPointer>>>,
+    focus: RefCell<Option<(Weak<SkiaFrame>, Part)>>,
+    position: Cell<(f64, f64)>,
+}
+
+impl Seat {
+    fn new(seat: Main<WlSeat>) -> Rc<Self> {
+        let state = Rc::new(Seat {
+            seat: seat.clone(),
+            name: RefCell::new(None),
+            pointer: RefCell::new(None),
+            focus: RefCell::new(None),
+            position: Cell::new((0.0, 0.0)),
+        });
+
+        let weak = Rc::downgrade(&state);
+        seat.quick_assign(move |seat, event, _| {
+            let state = match weak.upgrade() {
+                Some(state) => state,
+                None => return,
+            };
+
+            match event {
+                wl_seat::Event::Capabilities { capabilities } => {
+                    let has_pointer = capabilities.contains(wl_seat::Capability::Pointer);
+                    let mut pointer = state.pointer.borrow_mut();
+                    if has_pointer && pointer.is_none() {
+                        let new_pointer = seat.get_pointer();
+                        let weak = Rc::downgrade(&state);
+                        new_pointer.quick_assign(move |_, event, _| {
+                            if let Some(state) = weak.upgrade() {
+                                state.pointer_event(event);
+                            }
+                        });
+                        *pointer = Some(new_pointer);
+                    } else if !has_pointer {
+                        if let Some(pointer) = pointer.take() {
+                            release_pointer(&pointer);
+                        }
+                    }
+                }
+                wl_seat::Event::Name { name } => *state.name.borrow_mut() = Some(name),
+                _ => {}
+            }
+        });
+
+        state
+    }
+
+    fn focused(&self) -> Option<(Rc<SkiaFrame>, Part)> {
+        let focus = self.focus.borrow().clone();
+        focus.and_then(|(frame, part)| Some((frame.upgrade()?, part)))
+    }
+
+    fn pointer_event(&self, event: wl_pointer::Event) {
+        match event {
+            wl_pointer::Event::Enter {
+                surface,
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                *self.focus.borrow_mut() = frame::decoration_of(&surface);
+                self.position.set((surface_x, surface_y));
+                if let Some((frame, part)) = self.focused() {
+                    frame.pointer_motion(part, Some((surface_x, surface_y)));
+                }
+            }
+            wl_pointer::Event::Leave { .. } => {
+                if let Some((frame, part)) = self.focused() {
+                    frame.pointer_motion(part, None);
+                }
+                *self.focus.borrow_mut() = None;
+            }
+            wl_pointer::Event::Motion {
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                self.position.set((surface_x, surface_y));
+                if let Some((frame, part)) = self.focused() {
+                    frame.pointer_motion(part, Some((surface_x, surface_y)));
+                }
+            }
+            wl_pointer::Event::Button {
+                serial,
+                button,
+                state,
+                ..
+            } => {
+                if let Some((frame, part)) = self.focused() {
+                    let name = self.name.borrow().clone();
+                    frame.pointer_button(
+                        &self.seat,
+                        name.as_deref(),
+                        part,
+                        self.position.get(),
+                        button,
+                        serial,
+                        state == wl_pointer::ButtonState::Pressed,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn release(&self) {
+        if let Some(pointer) = self.pointer.borrow_mut().take() {
+            release_pointer(&pointer);
+        }
+        if self.seat.as_ref().version() >= 5 {
+            self.seat.release();
+        }
+    }
+}
+
+fn release_pointer(pointer: &WlPointer) {
+    if pointer.as_ref().version() >= 3 {
+        pointer.release();
+    }
+}
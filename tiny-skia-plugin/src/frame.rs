@@ -0,0 +1,409 @@
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
+};
+
+use libdecor::{
+    plugin::{PluginFrameRef, WeakPluginFrame},
+    Capabilities, ResizeEdge, WindowState,
+};
+use wayland_client::{
+    protocol::{wl_seat::WlSeat, wl_subsurface::WlSubsurface, wl_surface::WlSurface},
+    Main,
+};
+
+use crate::{
+    connection::Globals,
+    render::{self, Button, Highlight, SHADOW_SIZE, TITLE_HEIGHT},
+};
+
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+
+/// A part of the decorations, each drawn to its own subsurface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Part {
+    /// Above the content
+    Title,
+    /// Below the content and the title bar, also used for resizing
+    Shadow,
+}
+
+/// Stored in the user data of the decoration surfaces
+struct DecorationData {
+    frame: Weak<SkiaFrame>,
+    part: Part,
+}
+
+/// Look up the frame and part of a decoration surface.
+pub(crate) fn decoration_of(surface: &WlSurface) -> Option<(Weak<SkiaFrame>, Part)> {
+    surface
+        .as_ref()
+        .user_data()
+        .get::<DecorationData>()
+        .map(|data| (data.frame.clone(), data.part))
+}
+
+/// Height of the title bar for the given state
+pub(crate) fn title_height(visible: bool, window_state: WindowState) -> i32 {
+    if visible && !window_state.contains(WindowState::FULLSCREEN) {
+        TITLE_HEIGHT
+    } else {
+        0
+    }
+}
+
+fn buttons(capabilities: Capabilities) -> Vec<Button> {
+    let mut buttons = Vec::new();
+    if capabilities.contains(Capabilities::CLOSE) {
+        buttons.push(Button::Close);
+    }
+    if capabilities.contains(Capabilities::RESIZE) {
+        buttons.push(Button::Maximize);
+    }
+    if capabilities.contains(Capabilities::MINIMIZE) {
+        buttons.push(Button::Minimize);
+    }
+    buttons
+}
+
+/// The resize edge at a position on the shadow of the given size
+fn resize_edge((x, y): (f64, f64), (width, height): (i32, i32)) -> ResizeEdge {
+    let corner = f64::from(2 * SHADOW_SIZE);
+    let left = x < corner;
+    let right = x > f64::from(width) - corner;
+    let top = y < corner;
+    let bottom = y > f64::from(height) - corner;
+
+    match (top, bottom, left, right) {
+        (true, _, true, _) => ResizeEdge::TopLeft,
+        (true, _, _, true) => ResizeEdge::TopRight,
+        (_, true, true, _) => ResizeEdge::BottomLeft,
+        (_, true, _, true) => ResizeEdge::BottomRight,
+        (true, _, _, _) => ResizeEdge::Top,
+        (_, true, _, _) => ResizeEdge::Bottom,
+        (_, _, true, _) => ResizeEdge::Left,
+        (_, _, _, true) => ResizeEdge::Right,
+        _ => ResizeEdge::None,
+    }
+}
+
+struct Decoration {
+    surface: Main<WlSurface>,
+    subsurface: Main<WlSubsurface>,
+}
+
+impl Decoration {
+    fn destroy(&self) {
+        self.subsurface.destroy();
+        self.surface.destroy();
+    }
+}
+
+struct FrameState {
+    /// Created on the first commit
+    decorations: Option<(Decoration, Decoration)>,
+    content_size: (i32, i32),
+    window_state: WindowState,
+    hovered: Option<Button>,
+    pressed: Option<Button>,
+    popup_grabs: Vec<String>,
+}
+
+impl Default for FrameState {
+    fn default() -> Self {
+        FrameState {
+            decorations: None,
+            content_size: (0, 0),
+            window_state: WindowState::empty(),
+            hovered: None,
+            pressed: None,
+            popup_grabs: Vec::new(),
+        }
+    }
+}
+
+impl FrameState {
+    fn highlight(&self) -> Option<Highlight> {
+        match (self.hovered, self.pressed) {
+            (Some(hovered), Some(pressed)) if hovered == pressed => {
+                Some(Highlight::Pressed(pressed))
+            }
+            (Some(hovered), _) => Some(Highlight::Hover(hovered)),
+            _ => None,
+        }
+    }
+}
+
+enum Action {
+    Button(Button),
+    Move,
+    Resize(ResizeEdge),
+    ShowWindowMenu(i32, i32),
+    DismissPopup,
+}
+
+/// The data of a frame decorated by the plugin
+pub(crate) struct SkiaFrame {
+    this: Weak<SkiaFrame>,
+    globals: Rc<Globals>,
+    frame: WeakPluginFrame,
+    state: RefCell<FrameState>,
+}
+
+impl SkiaFrame {
+    pub(crate) fn new(globals: Rc<Globals>, frame: WeakPluginFrame) -> Rc<Self> {
+        Rc::new_cyclic(|this| SkiaFrame {
+            this: this.clone(),
+            globals,
+            frame,
+            state: RefCell::new(FrameState::default()),
+        })
+    }
+
+    /// Destroy the decoration surfaces.
+    pub(crate) fn destroy(&self) {
+        if let Some((title, shadow)) = self.state.borrow_mut().decorations.take() {
+            title.destroy();
+            shadow.destroy();
+        }
+    }
+
+    fn create_decoration(&self, parent: &WlSurface, part: Part) -> Decoration {
+        let surface = self.globals.compositor.create_surface();
+        surface.quick_assign(|_, _, _| {});
+        let frame = self.this.clone();
+        surface
+            .as_ref()
+            .user_data()
+            .set(move || DecorationData { frame, part });
+
+        let subsurface = self.globals.subcompositor.get_subsurface(&surface, parent);
+        if part == Part::Shadow {
+            subsurface.place_below(parent);
+        }
+
+        Decoration {
+            surface,
+            subsurface,
+        }
+    }
+
+    /// The application committed a new state.
+    pub(crate) fn commit(
+        &self,
+        frame: &PluginFrameRef,
+        content_size: (i32, i32),
+        window_state: WindowState,
+    ) {
+        let mut state = self.state.borrow_mut();
+        state.content_size = content_size;
+        state.window_state = window_state;
+        if state.decorations.is_none() {
+            let parent = frame.wl_surface();
+            state.decorations = Some((
+                self.create_decoration(&parent, Part::Title),
+                self.create_decoration(&parent, Part::Shadow),
+            ));
+        }
+        self.draw(frame, &state);
+    }
+
+    /// Redraw the decorations and ask the application to apply them.
+    pub(crate) fn redraw(&self) {
+        let frame = match self.frame.upgrade() {
+            Some(frame) => frame,
+            None => return,
+        };
+        self.draw(&frame, &self.state.borrow());
+        frame.toplevel_commit();
+    }
+
+    fn draw(&self, frame: &PluginFrameRef, state: &FrameState) {
+        let (title, shadow) = match &state.decorations {
+            Some(decorations) => decorations,
+            None => return,
+        };
+
+        let (width, height) = state.content_size;
+        let title_height = title_height(frame.is_visible(), state.window_state);
+        let shown = title_height > 0 && width > 0 && height > 0;
+
+        let title_bar = if shown {
+            render::title_bar(
+                width,
+                state.window_state,
+                &buttons(frame.capabilities()),
+                state.highlight(),
+            )
+        } else {
+            None
+        };
+        self.attach(title, title_bar.as_ref(), (0, -title_height));
+
+        let shadow_pixmap = if shown && render::is_floating(state.window_state) {
+            render::shadow(width, height + title_height)
+        } else {
+            None
+        };
+        self.attach(
+            shadow,
+            shadow_pixmap.as_ref(),
+            (-SHADOW_SIZE, -title_height - SHADOW_SIZE),
+        );
+
+        if width > 0 && height > 0 {
+            frame.set_window_geometry(0, -title_height, width, height + title_height);
+        }
+    }
+
+    fn attach(
+        &self,
+        decoration: &Decoration,
+        pixmap: Option<&tiny_skia::Pixmap>,
+        (x, y): (i32, i32),
+    ) {
+        let buffer = pixmap.and_then(|pixmap| {
+            render::buffer(&self.globals.shm, pixmap)
+                .ok()
+                .map(|buffer| (buffer.detach(), pixmap.width(), pixmap.height()))
+        });
+
+        decoration.subsurface.set_position(x, y);
+        match &buffer {
+            Some((buffer, width, height)) => {
+                decoration.surface.attach(Some(buffer), 0, 0);
+                decoration
+                    .surface
+                    .damage_buffer(0, 0, *width as i32, *height as i32);
+            }
+            None => decoration.surface.attach(None, 0, 0),
+        }
+        decoration.surface.commit();
+    }
+
+    pub(crate) fn popup_grab(&self, seat_name: &str) {
+        self.state
+            .borrow_mut()
+            .popup_grabs
+            .push(seat_name.to_owned());
+    }
+
+    pub(crate) fn popup_ungrab(&self, seat_name: &str) {
+        self.state
+            .borrow_mut()
+            .popup_grabs
+            .retain(|name| name != seat_name);
+    }
+
+    fn button_at(&self, part: Part, position: (f64, f64)) -> Option<Button> {
+        if part != Part::Title {
+            return None;
+        }
+        let frame = self.frame.upgrade()?;
+        let width = self.state.borrow().content_size.0;
+        render::button_at(width, &buttons(frame.capabilities()), position)
+    }
+
+    /// The pointer moved over the decorations, [`None`] if it left them.
+    pub(crate) fn pointer_motion(&self, part: Part, position: Option<(f64, f64)>) {
+        let hovered = position.and_then(|position| self.button_at(part, position));
+        let changed = {
+            let mut state = self.state.borrow_mut();
+            let previous = state.highlight();
+            state.hovered = hovered;
+            if position.is_none() {
+                state.pressed = None;
+            }
+            previous != state.highlight()
+        };
+        if changed {
+            self.redraw();
+        }
+    }
+
+    /// Handle a button press or release on one of the decorations.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn pointer_button(
+        &self,
+        seat: &WlSeat,
+        seat_name: Option<&str>,
+        part: Part,
+        position: (f64, f64),
+        button: u32,
+        serial: u32,
+        pressed: bool,
+    ) {
+        let frame = match self.frame.upgrade() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let capabilities = frame.capabilities();
+        let target = self.button_at(part, position);
+
+        let action = {
+            let mut state = self.state.borrow_mut();
+            let grabbed = seat_name
+                .map(|seat_name| state.popup_grabs.iter().any(|name| name == seat_name))
+                .unwrap_or(false);
+
+            match (part, button, pressed) {
+                // Clicking the decorations dismisses popups grabbing the seat
+                (_, _, true) if grabbed => Some(Action::DismissPopup),
+                (Part::Title, BTN_LEFT, true) if target.is_some() => {
+                    state.pressed = target;
+                    None
+                }
+                (Part::Title, BTN_LEFT, true) if capabilities.contains(Capabilities::MOVE) => {
+                    Some(Action::Move)
+                }
+                (Part::Title, BTN_LEFT, false) => match state.pressed.take() {
+                    Some(pressed) if target == Some(pressed) => Some(Action::Button(pressed)),
+                    _ => None,
+                },
+                (Part::Title, BTN_RIGHT, true) => {
+                    Some(Action::ShowWindowMenu(position.0 as i32, position.1 as i32))
+                }
+                (Part::Shadow, BTN_LEFT, true) if capabilities.contains(Capabilities::RESIZE) => {
+                    let size = state.content_size;
+                    let title_height = title_height(frame.is_visible(), state.window_state);
+                    let shadow_size = (
+                        size.0 + 2 * SHADOW_SIZE,
+                        size.1 + title_height + 2 * SHADOW_SIZE,
+                    );
+                    Some(Action::Resize(resize_edge(position, shadow_size)))
+                }
+                _ => None,
+            }
+        };
+
+        // The pressed state of a button might have changed
+        self.redraw();
+
+        match action {
+            Some(Action::DismissPopup) => {
+                if let Some(seat_name) = seat_name {
+                    frame.dismiss_popup(seat_name);
+                }
+            }
+            Some(Action::Button(Button::Close)) => frame.close(),
+            Some(Action::Button(Button::Maximize)) => {
+                if self
+                    .state
+                    .borrow()
+                    .window_state
+                    .contains(WindowState::MAXIMIZED)
+                {
+                    frame.unset_maximized();
+                } else {
+                    frame.set_maximized();
+                }
+            }
+            Some(Action::Button(Button::Minimize)) => frame.set_minimized(),
+            Some(Action::Move) => frame._move(seat, serial),
+            Some(Action::Resize(edge)) => frame.resize(seat, serial, edge),
+            Some(Action::ShowWindowMenu(x, y)) => frame.show_window_menu(seat, serial, x, y),
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! A libdecor plugin drawing the decorations with tiny-skia
+//!
+//! Reference implementation of a decoration plugin using the
+//! [`plugin`](libdecor::plugin) module of libdecor. Draws a title bar with
+//! close, maximize and minimize buttons and a shadow around floating
+//! windows, which is also used for resizing.
+//!
+//! The crate builds `libdecor_tiny_skia.so`, copy it to the plugin
+//! directory of libdecor, e.g. `/usr/lib/libdecor/plugins-1`. The plugin
+//! has the lowest priority and is only used if no other plugin is
+//! available.
+//!
+//! The title is not rendered and the cursor is not changed when hovering
+//! the decorations.
+
+use std::{io, os::unix::io::RawFd, rc::Rc, time::Duration};
+
+use libdecor::{
+    plugin::{BorderSize, Plugin, PluginContext, PluginFrameRef, StateRef, WeakPluginFrame},
+    Configuration,
+};
+
+mod connection;
+mod frame;
+#[doc(hidden)]
+pub mod render;
+
+use connection::Connection;
+use frame::SkiaFrame;
+
+struct SkiaPlugin {
+    connection: Connection,
+}
+
+impl Plugin for SkiaPlugin {
+    type Frame = Rc<SkiaFrame>;
+
+    fn new(context: PluginContext) -> Option<Self> {
+        let connection = Connection::new(context.display())?;
+        // The globals have been bound, the plugin can decorate frames
+        context.notify_ready();
+        Some(SkiaPlugin { connection })
+    }
+
+    fn fd(&self) -> RawFd {
+        self.connection.display().get_connection_fd()
+    }
+
+    fn dispatch(&self, timeout: Option<Duration>) -> io::Result<usize> {
+        self.connection.dispatch(timeout)
+    }
+
+    fn frame_new(&self, frame: WeakPluginFrame) -> Self::Frame {
+        SkiaFrame::new(self.connection.globals.clone(), frame)
+    }
+
+    fn frame_free(&self, _: &PluginFrameRef, data: Self::Frame) {
+        data.destroy();
+    }
+
+    fn frame_commit(
+        &self,
+        frame: &PluginFrameRef,
+        data: &Self::Frame,
+        state: &StateRef<'_>,
+        _: Option<&Configuration<'_>>,
+    ) {
+        data.commit(frame, state.content_size(), state.window_state());
+    }
+
+    fn frame_property_changed(&self, _: &PluginFrameRef, data: &Self::Frame) {
+        data.redraw();
+    }
+
+    fn frame_popup_grab(&self, _: &PluginFrameRef, data: &Self::Frame, seat_name: &str) {
+        data.popup_grab(seat_name);
+    }
+
+    fn frame_popup_ungrab(&self, _: &PluginFrameRef, data: &Self::Frame, seat_name: &str) {
+        data.popup_ungrab(seat_name);
+    }
+
+    fn frame_border_size(
+        &self,
+        frame: &PluginFrameRef,
+        _: &Self::Frame,
+        configuration: Option<&Configuration<'_>>,
+    ) -> Option<BorderSize> {
+        let window_state = configuration
+            .and_then(|configuration| configuration.window_state())
+            .unwrap_or_else(|| frame.window_state());
+
+        Some(BorderSize {
+            top: frame::title_height(frame.is_visible(), window_state),
+            ..BorderSize::default()
+        })
+    }
+}
+
+libdecor::libdecor_plugin! {
+    plugin: SkiaPlugin,
+    description: "libdecor plugin drawing the decorations with tiny-skia",
+    priorities: {
+        _ => libdecor::plugin::PRIORITY_LOW,
+    },
+}
@@ -0,0 +1,270 @@
+//! Drawing of the decorations
+//!
+//! Public for the tests only, not part of the API of the plugin.
+
+use std::{
+    io::{self, Write},
+    os::unix::io::AsRawFd,
+};
+
+use libdecor::WindowState;
+use tiny_skia::{
+    BlendMode, Color, FillRule, LineCap, Paint, Path, PathBuilder, Pixmap, Rect, Stroke, Transform,
+};
+use wayland_client::{
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_shm::{self, WlShm},
+    },
+    Main,
+};
+
+/// Height of the title bar, also the width of a button slot
+pub const TITLE_HEIGHT: i32 = 32;
+/// Size of the shadow around floating windows, also used for resizing
+pub const SHADOW_SIZE: i32 = 16;
+
+const CORNER_RADIUS: f32 = 8.0;
+const GLYPH_SIZE: f32 = 10.0;
+const BUTTON_RADIUS: f32 = 11.0;
+/// Alpha of each shadow layer, stacking up towards the window
+const SHADOW_ALPHA: u8 = 3;
+
+/// A title bar button, laid out from right to left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Close,
+    Maximize,
+    Minimize,
+}
+
+/// Pointer feedback on a button
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    Hover(Button),
+    Pressed(Button),
+}
+
+/// Whether the window is not maximized, tiled or fullscreen
+pub fn is_floating(window_state: WindowState) -> bool {
+    !window_state.intersects(
+        WindowState::MAXIMIZED
+            | WindowState::FULLSCREEN
+            | WindowState::TILED_LEFT
+            | WindowState::TILED_RIGHT
+            | WindowState::TILED_TOP
+            | WindowState::TILED_BOTTOM,
+    )
+}
+
+/// The button at the given position of the title bar
+pub fn button_at(width: i32, buttons: &[Button], (x, _): (f64, f64)) -> Option<Button> {
+    let slot = (f64::from(width) - x) / f64::from(TITLE_HEIGHT);
+    if slot < 0.0 {
+        return None;
+    }
+    buttons.get(slot as usize).copied()
+}
+
+fn paint(color: Color) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.anti_alias = true;
+    paint
+}
+
+/// A rectangle with individually rounded top and bottom corners
+fn rounded_rect(rect: Rect, top_radius: f32, bottom_radius: f32) -> Option<Path> {
+    let (x, y, right, bottom) = (rect.left(), rect.top(), rect.right(), rect.bottom());
+
+    let mut builder = PathBuilder::new();
+    builder.move_to(x + top_radius, y);
+    builder.line_to(right - top_radius, y);
+    builder.quad_to(right, y, right, y + top_radius);
+    builder.line_to(right, bottom - bottom_radius);
+    builder.quad_to(right, bottom, right - bottom_radius, bottom);
+    builder.line_to(x + bottom_radius, bottom);
+    builder.quad_to(x, bottom, x, bottom - bottom_radius);
+    builder.line_to(x, y + top_radius);
+    builder.quad_to(x, y, x + top_radius, y);
+    builder.close();
+    builder.finish()
+}
+
+fn line(from: (f32, f32), to: (f32, f32)) -> Option<Path> {
+    let mut builder = PathBuilder::new();
+    builder.move_to(from.0, from.1);
+    builder.line_to(to.0, to.1);
+    builder.finish()
+}
+
+/// Draw the title bar with its buttons.
+///
+/// The title itself is not rendered.
+pub fn title_bar(
+    width: i32,
+    window_state: WindowState,
+    buttons: &[Button],
+    highlight: Option<Highlight>,
+) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(width as u32, TITLE_HEIGHT as u32)?;
+    let active = window_state.contains(WindowState::ACTIVE);
+
+    let (background, foreground) = if active {
+        (
+            Color::from_rgba8(48, 48, 48, 255),
+            Color::from_rgba8(230, 230, 230, 255),
+        )
+    } else {
+        (
+            Color::from_rgba8(80, 80, 80, 255),
+            Color::from_rgba8(160, 160, 160, 255),
+        )
+    };
+    let radius = if is_floating(window_state) {
+        CORNER_RADIUS
+    } else {
+        0.0
+    };
+    let rect = Rect::from_xywh(0.0, 0.0, width as f32, TITLE_HEIGHT as f32)?;
+    pixmap.fill_path(
+        &rounded_rect(rect, radius, 0.0)?,
+        &paint(background),
+        FillRule::Winding,
+        Transform::identity(),
+        None,
+    );
+
+    let stroke = Stroke {
+        width: 1.5,
+        line_cap: LineCap::Round,
+        ..Stroke::default()
+    };
+    let glyph = paint(foreground);
+
+    for (slot, &button) in buttons.iter().enumerate() {
+        let cx = width as f32 - (slot as f32 + 0.5) * TITLE_HEIGHT as f32;
+        let cy = TITLE_HEIGHT as f32 / 2.0;
+        let (left, top) = (cx - GLYPH_SIZE / 2.0, cy - GLYPH_SIZE / 2.0);
+        let (right, bottom) = (cx + GLYPH_SIZE / 2.0, cy + GLYPH_SIZE / 2.0);
+
+        let feedback = match highlight {
+            Some(Highlight::Hover(b)) if b == button => Some(40),
+            Some(Highlight::Pressed(b)) if b == button => Some(90),
+            _ => None,
+        };
+        if let Some(alpha) = feedback {
+            let color = match button {
+                Button::Close => Color::from_rgba8(220, 60, 60, alpha * 2),
+                _ => Color::from_rgba8(255, 255, 255, alpha),
+            };
+            if let Some(circle) = PathBuilder::from_circle(cx, cy, BUTTON_RADIUS) {
+                pixmap.fill_path(
+                    &circle,
+                    &paint(color),
+                    FillRule::Winding,
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+
+        let paths = match button {
+            Button::Close => vec![
+                line((left, top), (right, bottom)),
+                line((right, top), (left, bottom)),
+            ],
+            Button::Maximize if window_state.contains(WindowState::MAXIMIZED) => {
+                let offset = GLYPH_SIZE / 4.0;
+                vec![
+                    Rect::from_xywh(left, top + offset, GLYPH_SIZE - offset, GLYPH_SIZE - offset)
+                        .map(PathBuilder::from_rect),
+                    line((left + offset, top), (right, top)),
+                    line((right, top), (right, bottom - offset)),
+                ]
+            }
+            Button::Maximize => {
+                vec![Rect::from_ltrb(left, top, right, bottom).map(PathBuilder::from_rect)]
+            }
+            Button::Minimize => vec![line((left, bottom - 1.0), (right, bottom - 1.0))],
+        };
+        for path in paths.into_iter().flatten() {
+            pixmap.stroke_path(&path, &glyph, &stroke, Transform::identity(), None);
+        }
+    }
+
+    Some(pixmap)
+}
+
+/// Draw the shadow around a window of the given size.
+///
+/// The area covered by the window itself is left transparent.
+pub fn shadow(width: i32, height: i32) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(
+        (width + 2 * SHADOW_SIZE) as u32,
+        (height + 2 * SHADOW_SIZE) as u32,
+    )?;
+
+    let layer = paint(Color::from_rgba8(0, 0, 0, SHADOW_ALPHA));
+    for i in 0..SHADOW_SIZE {
+        let inset = i as f32;
+        let rect = Rect::from_xywh(
+            inset,
+            inset,
+            (width + 2 * (SHADOW_SIZE - i)) as f32,
+            (height + 2 * (SHADOW_SIZE - i)) as f32,
+        )?;
+        let radius = CORNER_RADIUS + (SHADOW_SIZE - i) as f32;
+        pixmap.fill_path(
+            &rounded_rect(rect, radius, radius)?,
+            &layer,
+            FillRule::Winding,
+            Transform::identity(),
+            None,
+        );
+    }
+
+    let clear = Paint {
+        blend_mode: BlendMode::Clear,
+        ..Paint::default()
+    };
+    let window = Rect::from_xywh(
+        SHADOW_SIZE as f32,
+        SHADOW_SIZE as f32,
+        width as f32,
+        height as f32,
+    )?;
+    pixmap.fill_rect(window, &clear, Transform::identity(), None);
+
+    Some(pixmap)
+}
+
+/// Create an ARGB8888 buffer holding the pixmap.
+///
+/// The buffer destroys itself once released by the compositor.
+pub fn buffer(shm: &WlShm, pixmap: &Pixmap) -> io::Result<Main<WlBuffer>> {
+    // tiny-skia stores premultiplied RGBA, wl_shm expects premultiplied
+    // ARGB in native (little) endian, i.e. BGRA in memory
+    let bytes: Vec<u8> = pixmap
+        .data()
+        .chunks_exact(4)
+        .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+        .collect();
+    let mut file = tempfile::tempfile()?;
+    file.write_all(&bytes)?;
+
+    let (width, height) = (pixmap.width() as i32, pixmap.height() as i32);
+    let stride = width * 4;
+    // The file descriptor is duplicated by libwayland and can be closed
+    let pool = shm.create_pool(file.as_raw_fd(), stride * height);
+    let buffer = pool.create_buffer(0, width, height, stride, wl_shm::Format::Argb8888);
+    pool.destroy();
+
+    buffer.quick_assign(|buffer, event, _| {
+        if let wl_buffer::Event::Release = event {
+            buffer.destroy();
+        }
+    });
+
+    Ok(buffer)
+}
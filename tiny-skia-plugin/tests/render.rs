@@ -0,0 +1,170 @@
+//! Tests of the drawing of the decorations
+
+use decor_tiny_skia::render::{self, Button, Highlight, SHADOW_SIZE, TITLE_HEIGHT};
+use libdecor::WindowState;
+use tiny_skia::Pixmap;
+
+const WIDTH: i32 = 200;
+const BUTTONS: &[Button] = &[Button::Close, Button::Maximize, Button::Minimize];
+
+fn title_bar(window_state: WindowState, highlight: Option<Highlight>) -> Pixmap {
+    render::title_bar(WIDTH, window_state, BUTTONS, highlight).unwrap()
+}
+
+/// Returns the color of the pixel as `(red, green, blue, alpha)`.
+fn rgba(pixmap: &Pixmap, x: i32, y: i32) -> (u8, u8, u8, u8) {
+    let pixel = pixmap.pixel(x as u32, y as u32).unwrap();
+    (pixel.red(), pixel.green(), pixel.blue(), pixel.alpha())
+}
+
+/// Center of the slot of the button
+fn button_center(button: Button) -> (i32, i32) {
+    let slot = BUTTONS.iter().position(|&b| b == button).unwrap() as i32;
+    (
+        WIDTH - slot * TITLE_HEIGHT - TITLE_HEIGHT / 2,
+        TITLE_HEIGHT / 2,
+    )
+}
+
+/// A pixel inside of the highlight of the button, but outside of its glyph
+fn highlight_pixel(pixmap: &Pixmap, button: Button) -> (u8, u8, u8, u8) {
+    let (x, y) = button_center(button);
+    rgba(pixmap, x, y + 8)
+}
+
+/// All pixels in the slot of the button
+fn button_slot(pixmap: &Pixmap, button: Button) -> Vec<(u8, u8, u8, u8)> {
+    let (cx, _) = button_center(button);
+    let left = cx - TITLE_HEIGHT / 2;
+    (0..TITLE_HEIGHT)
+        .flat_map(|y| (left..left + TITLE_HEIGHT).map(move |x| (x, y)))
+        .map(|(x, y)| rgba(pixmap, x, y))
+        .collect()
+}
+
+#[test]
+fn active_windows_are_darker() {
+    let active = title_bar(WindowState::ACTIVE, None);
+    let inactive = title_bar(WindowState::empty(), None);
+
+    assert_eq!(active.width(), WIDTH as u32);
+    assert_eq!(active.height(), TITLE_HEIGHT as u32);
+    assert_eq!(rgba(&active, 40, 16), (48, 48, 48, 255));
+    assert_eq!(rgba(&inactive, 40, 16), (80, 80, 80, 255));
+    assert_ne!(
+        button_slot(&active, Button::Close),
+        button_slot(&inactive, Button::Close)
+    );
+}
+
+#[test]
+fn floating_windows_have_rounded_corners() {
+    let floating = title_bar(WindowState::ACTIVE, None);
+    assert_eq!(rgba(&floating, 0, 0).3, 0);
+    assert_eq!(rgba(&floating, WIDTH - 1, 0).3, 0);
+    // The bottom is attached to the content
+    assert_eq!(rgba(&floating, 0, TITLE_HEIGHT - 1).3, 255);
+
+    for state in [
+        WindowState::MAXIMIZED,
+        WindowState::TILED_LEFT,
+        WindowState::TILED_RIGHT | WindowState::TILED_TOP,
+        WindowState::FULLSCREEN,
+    ] {
+        let pixmap = title_bar(WindowState::ACTIVE | state, None);
+        assert_eq!(rgba(&pixmap, 0, 0), (48, 48, 48, 255), "{:?}", state);
+        assert_eq!(
+            rgba(&pixmap, WIDTH - 1, 0),
+            (48, 48, 48, 255),
+            "{:?}",
+            state
+        );
+    }
+}
+
+#[test]
+fn maximized_windows_show_the_restore_glyph() {
+    let floating = title_bar(WindowState::ACTIVE, None);
+    let maximized = title_bar(WindowState::ACTIVE | WindowState::MAXIMIZED, None);
+    let tiled = title_bar(WindowState::ACTIVE | WindowState::TILED_LEFT, None);
+
+    assert_ne!(
+        button_slot(&floating, Button::Maximize),
+        button_slot(&maximized, Button::Maximize)
+    );
+    // Tiled windows can still be maximized
+    assert_eq!(
+        button_slot(&floating, Button::Maximize),
+        button_slot(&tiled, Button::Maximize)
+    );
+    assert_eq!(
+        button_slot(&floating, Button::Minimize),
+        button_slot(&maximized, Button::Minimize)
+    );
+}
+
+#[test]
+fn hovered_and_pressed_buttons_are_highlighted() {
+    let plain = title_bar(WindowState::ACTIVE, None);
+    let hovered = title_bar(
+        WindowState::ACTIVE,
+        Some(Highlight::Hover(Button::Maximize)),
+    );
+    let pressed = title_bar(
+        WindowState::ACTIVE,
+        Some(Highlight::Pressed(Button::Maximize)),
+    );
+
+    let (plain_red, ..) = highlight_pixel(&plain, Button::Maximize);
+    let (hovered_red, ..) = highlight_pixel(&hovered, Button::Maximize);
+    let (pressed_red, ..) = highlight_pixel(&pressed, Button::Maximize);
+    assert_eq!(plain_red, 48);
+    assert!(plain_red < hovered_red, "{} < {}", plain_red, hovered_red);
+    assert!(
+        hovered_red < pressed_red,
+        "{} < {}",
+        hovered_red,
+        pressed_red
+    );
+
+    // Only the highlighted button changes
+    for button in [Button::Close, Button::Minimize] {
+        assert_eq!(button_slot(&plain, button), button_slot(&hovered, button));
+        assert_eq!(button_slot(&plain, button), button_slot(&pressed, button));
+    }
+}
+
+#[test]
+fn the_close_button_is_highlighted_red() {
+    let hovered = title_bar(WindowState::ACTIVE, Some(Highlight::Hover(Button::Close)));
+    let (red, green, blue, _) = highlight_pixel(&hovered, Button::Close);
+    assert!(red > green && red > blue, "({}, {}, {})", red, green, blue);
+}
+
+#[test]
+fn buttons_are_hit_from_the_right() {
+    let y = f64::from(TITLE_HEIGHT / 2);
+    for &button in BUTTONS {
+        let (x, _) = button_center(button);
+        assert_eq!(
+            render::button_at(WIDTH, BUTTONS, (f64::from(x), y)),
+            Some(button)
+        );
+    }
+    assert_eq!(render::button_at(WIDTH, BUTTONS, (40.0, y)), None);
+}
+
+#[test]
+fn the_shadow_surrounds_the_window() {
+    let (width, height) = (100, 80);
+    let shadow = render::shadow(width, height).unwrap();
+
+    assert_eq!(shadow.width(), (width + 2 * SHADOW_SIZE) as u32);
+    assert_eq!(shadow.height(), (height + 2 * SHADOW_SIZE) as u32);
+    // Darkest next to the window, transparent under it
+    let (.., outer) = rgba(&shadow, SHADOW_SIZE / 2, SHADOW_SIZE + height / 2);
+    let (.., inner) = rgba(&shadow, SHADOW_SIZE - 1, SHADOW_SIZE + height / 2);
+    let (.., window) = rgba(&shadow, SHADOW_SIZE + width / 2, SHADOW_SIZE + height / 2);
+    assert!(0 < outer && outer < inner, "0 < {} < {}", outer, inner);
+    assert_eq!(window, 0);
+}